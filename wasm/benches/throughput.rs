@@ -0,0 +1,162 @@
+//! Parsing and evaluation throughput, run natively with
+//!
+//! ```sh
+//! cargo bench --bench throughput
+//! ```
+//!
+//! Criterion keeps the previous run's numbers under `target/criterion/`, and
+//! reports the change against them, so running the benchmarks before and after a
+//! change shows how much it helped (or hurt).
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use rules_engine::components::instruction::Instruction;
+use rules_engine::components::program::Program;
+use rules_engine::components::robot::Robot;
+use rules_engine::components::script;
+use rules_engine::components::tabletop::Tabletop;
+
+/// One of each of the commands batch jobs send the most, in a few spellings.
+const COMMANDS: &[&str] = &[
+    "PLACE 1,2,NORTH",
+    "place 1, 2, north",
+    "MOVE",
+    "move 3 as-far-as-possible",
+    "LEFT",
+    "right",
+    "STRAFE LEFT",
+    "FACE WEST",
+    "REPORT",
+    "REPORT JSON",
+    "OBSTACLE 3,3",
+    "LET $STEPS = $ROBOT_X * 2 + 1",
+];
+
+/// A walk around the edge of a 5x5 tabletop, that ends where it started.
+const LAP: &[&str] = &[
+    "PLACE 0,0,NORTH",
+    "MOVE",
+    "MOVE",
+    "MOVE",
+    "MOVE",
+    "RIGHT",
+    "MOVE 4",
+    "RIGHT",
+    "MOVE 4",
+    "RIGHT",
+    "MOVE 4",
+    "RIGHT",
+    "REPORT",
+];
+
+const SCRIPT: &str = "
+PLACE 0,0,NORTH
+DEFINE SQUARE
+  REPEAT 4 { MOVE 4; RIGHT }
+END
+LET $LAPS = 0
+REPEAT 3
+  SQUARE
+  LET $LAPS = $LAPS + 1
+END
+WHILE NOT FACING WEST { LEFT }
+IF AT 0,0 THEN REPORT ELSE REPORT JSON
+";
+
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for command in COMMANDS {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(command),
+            command,
+            |b, command| b.iter(|| Instruction::parse(black_box(command))),
+        );
+    }
+
+    group.throughput(Throughput::Elements(COMMANDS.len() as u64));
+    group.bench_function("all commands", |b| {
+        b.iter(|| {
+            for command in COMMANDS {
+                let _ = black_box(Instruction::parse(black_box(command)));
+            }
+        })
+    });
+    group.finish();
+
+    c.bench_function("parse script", |b| {
+        b.iter(|| script::parse(black_box(SCRIPT)))
+    });
+}
+
+fn evaluation(c: &mut Criterion) {
+    let tabletop = Tabletop::new(5, 5).unwrap();
+
+    let mut group = c.benchmark_group("evaluate");
+    group.throughput(Throughput::Elements(LAP.len() as u64));
+    group.bench_function("lap", |b| {
+        let mut robot = Robot::create(&tabletop).unwrap();
+        b.iter(|| {
+            for command in LAP {
+                let _ = black_box(robot.evaluate(black_box(command)));
+            }
+            robot.clear_trail();
+        })
+    });
+
+    // :: The same lap, on unbounded (and very large) tabletops strewn with
+    //    obstacles everywhere but along it; checking each move against them
+    //    should cost about as much as on the small tabletop.
+    let strewn = |mut tabletop: Tabletop| {
+        for x in 0..200 {
+            for y in 0..200 {
+                let off_lap = x > 4 || y > 4 || (x > 0 && x < 4 && y > 0 && y < 4);
+                if off_lap {
+                    tabletop.add_obstacle(x, y).unwrap();
+                }
+            }
+        }
+
+        tabletop
+    };
+
+    let boards = [
+        ("lap, unbounded", strewn(Tabletop::unbounded())),
+        (
+            "lap, large",
+            strewn(Tabletop::large(i64::MAX, i64::MAX).unwrap()),
+        ),
+    ];
+
+    for (name, tabletop) in boards.iter() {
+        group.bench_function(*name, |b| {
+            let mut robot = Robot::create(tabletop).unwrap();
+            b.iter(|| {
+                for command in LAP {
+                    let _ = black_box(robot.evaluate(black_box(command)));
+                }
+                robot.clear_trail();
+            })
+        });
+    }
+    group.finish();
+
+    c.bench_function("run script", |b| {
+        let mut robot = Robot::create(&tabletop).unwrap();
+        b.iter(|| {
+            let _ = black_box(robot.run_script(black_box(SCRIPT)));
+            robot.clear_trail();
+        })
+    });
+
+    c.bench_function("run program", |b| {
+        let mut robot = Robot::create(&tabletop).unwrap();
+        let program = Program::compile(SCRIPT).unwrap();
+        b.iter(|| {
+            let _ = black_box(robot.run_program(black_box(&program)));
+            robot.clear_trail();
+        })
+    });
+}
+
+criterion_group!(benches, parsing, evaluation);
+criterion_main!(benches);
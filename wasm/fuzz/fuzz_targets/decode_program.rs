@@ -0,0 +1,27 @@
+//! Loads (and, if that succeeds, runs) an arbitrary encoded program. Input that
+//! compiles as a script (such as the seed fixtures) is also saved and loaded back.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rules_engine::components::program::Program;
+use rules_engine::components::robot::Robot;
+use rules_engine::components::tabletop::Tabletop;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(program) = std::str::from_utf8(data).map(Program::compile) {
+        if let Ok(program) = program {
+            assert_eq!(Program::from_bytes(&program.to_bytes()), Ok(program));
+        }
+    }
+
+    if let Ok(program) = Program::from_bytes(data) {
+        assert_eq!(Program::from_bytes(&program.to_bytes()).as_ref(), Ok(&program));
+
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        robot.set_step_limit(10_000);
+
+        let _ = robot.run_program(&program);
+    }
+});
@@ -0,0 +1,87 @@
+//! Runs a sequence of commands, one line at a time (as `Robot::evaluate_command`
+//! does), against an arbitrary tabletop.
+//!
+//! `evaluate_command` itself returns a `JsValue`, which can only be created on a
+//! `wasm32` target; `Robot::evaluate` is what it wraps, and is fuzzed instead.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rules_engine::components::robot::Robot;
+use rules_engine::components::tabletop::Tabletop;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    width: i8,
+    height: i8,
+    layout: Layout,
+    move_policy: bool,
+    energy: Option<(u8, u8, u8)>,
+    commands: Vec<String>,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Layout {
+    Obstacles(Vec<(i8, i8)>),
+    Unbounded(Vec<(i8, i8)>),
+    Random { density: u8, seed: u32 },
+    Maze { seed: u32 },
+    Rooms { seed: u32 },
+}
+
+fuzz_target!(|input: Input| {
+    // :: Large tabletops only slow the fuzzer down; negative (and zero) sizes
+    //    are kept, to check that they are rejected.
+    let (width, height) = (input.width as i32 % 32, input.height as i32 % 32);
+    let tabletop = match input.layout {
+        Layout::Obstacles(obstacles) => Tabletop::new(width, height).map(|mut tabletop| {
+            for (x, y) in obstacles {
+                let _ = tabletop.add_obstacle(x.into(), y.into());
+            }
+
+            tabletop
+        }),
+
+        Layout::Unbounded(obstacles) => {
+            let mut tabletop = Tabletop::unbounded();
+            for (x, y) in obstacles {
+                let _ = tabletop.add_obstacle(x.into(), y.into());
+            }
+
+            Ok(tabletop)
+        }
+
+        Layout::Random { density, seed } => {
+            Tabletop::with_random_obstacles(width, height, density as f64 / 255.0, seed)
+        }
+
+        Layout::Maze { seed } => Tabletop::with_maze(width, height, seed),
+        Layout::Rooms { seed } => Tabletop::with_rooms(width, height, seed),
+    };
+
+    let mut robot = match tabletop.and_then(|tabletop| Robot::create(&tabletop)) {
+        Ok(robot) => robot,
+        Err(_) => return,
+    };
+
+    // :: Keep runaway loops from being mistaken for hangs.
+    robot.set_step_limit(10_000);
+
+    if input.move_policy {
+        let _ = robot.set_move_policy("AS_FAR_AS_POSSIBLE");
+    }
+
+    if let Some((capacity, move_cost, turn_cost)) = input.energy {
+        robot.enable_energy(capacity.into(), move_cost.into(), turn_cost.into());
+    }
+
+    for command in input.commands.iter() {
+        let _ = robot.evaluate(command);
+
+        // :: Whatever the commands were, the robot must still be somewhere valid.
+        if let Some(position) = robot.position() {
+            assert!(robot.tabletop().request_place(&position).is_ok());
+        }
+    }
+});
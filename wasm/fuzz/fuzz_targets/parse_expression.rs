@@ -0,0 +1,12 @@
+//! Translates (and, if that succeeds, computes) an arbitrary expression.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rules_engine::components::expression::Expr;
+
+fuzz_target!(|plaintext: &str| {
+    if let Ok(expr) = Expr::parse(plaintext) {
+        let _ = expr.evaluate(|name| Some(name.len() as i64));
+    }
+});
@@ -0,0 +1,11 @@
+//! Translating any single line of text should either succeed or be rejected;
+//! it should never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rules_engine::components::instruction::Instruction;
+
+fuzz_target!(|plaintext: &str| {
+    let _ = Instruction::parse(plaintext);
+});
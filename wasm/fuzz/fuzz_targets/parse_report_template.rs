@@ -0,0 +1,22 @@
+//! Translates an arbitrary report template (as given to `--report`, or
+//! `Robot::set_report_template`), and fills it in.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rules_engine::components::common::{Orientation, Position};
+use rules_engine::components::report::{ReportFormat, Status, Template};
+
+fuzz_target!(|plaintext: &str| {
+    let _ = ReportFormat::parse(plaintext);
+
+    if let Ok(template) = Template::parse(plaintext) {
+        let status = Status {
+            id: 1,
+            position: Some(Position { x: 2, y: 3 }),
+            orientation: Some(Orientation::East),
+        };
+
+        let _ = status.format(&ReportFormat::Template(template));
+    }
+});
@@ -0,0 +1,15 @@
+//! Translates (and, if that succeeds, runs) an arbitrary multi-line script.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rules_engine::components::robot::Robot;
+use rules_engine::components::tabletop::Tabletop;
+
+fuzz_target!(|script: &str| {
+    let tabletop = Tabletop::new(5, 5).unwrap();
+    let mut robot = Robot::create(&tabletop).unwrap();
+    robot.set_step_limit(10_000);
+
+    let _ = robot.check_script(script);
+});
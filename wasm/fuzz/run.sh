@@ -0,0 +1,35 @@
+#!/bin/sh
+# Builds and runs a fuzz target the way `cargo fuzz run` does, for machines
+# without cargo-fuzz (e.g. with no network access to install it). Any further
+# arguments are handed to libFuzzer, e.g.
+#
+#   ./run.sh parse_instruction -max_total_time=60
+#
+# Needs a nightly toolchain, a C++ compiler, and the dependencies in
+# Cargo.toml already in the local cargo registry.
+set -e
+
+if [ $# -lt 1 ]; then
+    echo "Usage: $0 TARGET [LIBFUZZER OPTIONS]" >&2
+    exit 1
+fi
+
+target=$1
+shift
+
+cd "$(dirname "$0")"
+host=$(rustc +nightly -vV | sed -n 's/^host: //p')
+
+# :: The same instrumentation cargo-fuzz enables; `--target` keeps it away from
+#    build scripts and proc macros, which are not linked against libFuzzer.
+RUSTFLAGS="-Cpasses=sancov-module \
+    -Cllvm-args=-sanitizer-coverage-level=4 \
+    -Cllvm-args=-sanitizer-coverage-inline-8bit-counters \
+    -Cllvm-args=-sanitizer-coverage-pc-table \
+    -Cllvm-args=-sanitizer-coverage-trace-compares \
+    --cfg fuzzing -Cdebug-assertions -Coverflow-checks" \
+    cargo +nightly build --offline --release --target "$host" --bin "$target"
+
+mkdir -p "corpus/$target" "artifacts/$target"
+exec "${CARGO_TARGET_DIR:-target}/$host/release/$target" "corpus/$target" ../tests/fixtures \
+    -artifact_prefix="artifacts/$target/" "$@"
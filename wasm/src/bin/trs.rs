@@ -0,0 +1,363 @@
+//! `trs`: runs Toy Robot commands from a file (or stdin), outside of the browser.
+//!
+//! ```text
+//! trs --size 5x5 --svg tabletop.svg --trail commands.txt
+//! trs --gif replay.gif --cell-size 24 --frame-delay 250 < commands.txt
+//! ```
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process;
+use std::rc::Rc;
+
+use rules_engine::components::replay::{self, Replay};
+use rules_engine::components::robot::Robot;
+use rules_engine::components::stream;
+use rules_engine::components::tabletop::{Bounds, Tabletop};
+
+const USAGE: &str = "\
+Usage: trs [OPTIONS] [FILE]
+
+Runs the commands in FILE (or stdin, if omitted) one line at a time, printing
+whatever they report. A line may hold several commands, separated by `;`, and
+anything after a `#` is a comment. Rejected commands are reported on stderr, and skipped.
+If any EXPECT command does not hold, trs exits with a non-zero status.
+
+Options:
+  --size WIDTHxHEIGHT  Size of the tabletop (default: 5x5); up to 64-bit
+                       dimensions, or `unbounded` for one without any edges
+  --script             Run FILE as a single script, so that blocks (e.g. REPEAT)
+                       can span several lines; stops at the first error
+                       (other than a failed EXPECT)
+  --report FORMAT      Format of plain REPORTs: plain (the default), json, csv,
+                       or a template such as \"{x}:{y} facing {f}\"
+  --svg PATH           Afterwards, write an SVG drawing of the tabletop to PATH
+  --trail              Include the robot's trail in drawings
+  --no-history         Don't keep track of where the robot has been (its trail,
+                       and the positions counted as visited), so that endless
+                       input runs without using up more and more memory
+  --gif PATH           Write an animated GIF replay to PATH, with a frame for
+                       every command (including, with --script, those run by
+                       blocks and procedures)
+  --cell-size PIXELS   Size of each position in the replay (default: 32)
+  --frame-delay MS     How long each frame of the replay is shown (default: 500)
+  -h, --help           Show this message";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    bounds: Bounds,
+    script: bool,
+    report: Option<String>,
+    input: Option<String>,
+    svg: Option<String>,
+    trail: bool,
+    history: bool,
+    gif: Option<String>,
+    cell_size: u32,
+    frame_delay: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            bounds: Bounds::Sized {
+                width: 5,
+                height: 5,
+            },
+            script: false,
+            report: None,
+            input: None,
+            svg: None,
+            trail: false,
+            history: true,
+            gif: None,
+            cell_size: replay::DEFAULT_CELL_SIZE,
+            frame_delay: replay::DEFAULT_FRAME_DELAY,
+        }
+    }
+}
+
+// :: ---
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Err(message) = parse_options(&args).and_then(|options| run(&options)) {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or(format!("Option {} needs a value.\n\n{}", arg, USAGE))
+        };
+
+        match arg.as_str() {
+            "--size" => options.bounds = parse_size(&value()?)?,
+
+            "--script" => options.script = true,
+            "--report" => options.report = Some(value()?),
+            "--svg" => options.svg = Some(value()?),
+            "--trail" => options.trail = true,
+            "--no-history" => options.history = false,
+            "--gif" => options.gif = Some(value()?),
+            "--cell-size" => options.cell_size = parse_number(arg, &value()?)?,
+            "--frame-delay" => options.frame_delay = parse_number(arg, &value()?)?,
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("Option {} was not recognized.\n\n{}", arg, USAGE))
+            }
+
+            _ if options.input.is_some() => {
+                return Err(format!("Only one input file can be given.\n\n{}", USAGE))
+            }
+
+            _ => options.input = Some(arg.clone()),
+        }
+    }
+
+    if options.trail && !options.history {
+        return Err(
+            "Option --trail needs the robot's history; it cannot be used with --no-history."
+                .to_string(),
+        );
+    }
+
+    Ok(options)
+}
+
+/// Parses a `WIDTHxHEIGHT` size, e.g. `8x6`; or `unbounded`.
+fn parse_size(plaintext: &str) -> Result<Bounds, String> {
+    let plaintext = plaintext.to_lowercase();
+    if plaintext == "unbounded" {
+        return Ok(Bounds::Unbounded);
+    }
+
+    let invalid = || format!("Size {} is invalid; expected e.g. 5x5.", plaintext);
+    let (width, height) = plaintext
+        .split_once('x')
+        .map(|(width, height)| (width.parse::<i64>(), height.parse::<i64>()))
+        .ok_or_else(invalid)?;
+
+    match (width, height) {
+        (Ok(width), Ok(height)) => Ok(Bounds::Sized { width, height }),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_number(option: &str, plaintext: &str) -> Result<u32, String> {
+    plaintext
+        .parse::<u32>()
+        .map_err(|_| format!("Option {} needs a number, not {}.", option, plaintext))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    // :: Commands are run as they are read, so that trs can follow a pipe from
+    //    another process; only scripts are read in full first.
+    let stdin = io::stdin();
+    let (mut input, source): (Box<dyn BufRead>, &str) = match options.input.as_deref() {
+        None | Some("-") => (Box::new(stdin.lock()), "stdin"),
+        Some(path) => {
+            let file = fs::File::open(path)
+                .map_err(|error| format!("Could not read {}: {}", path, error))?;
+
+            (Box::new(BufReader::new(file)), path)
+        }
+    };
+
+    let tabletop = match options.bounds {
+        Bounds::Sized { width, height } => Tabletop::large(width, height)?,
+        Bounds::Unbounded => Tabletop::unbounded(),
+    };
+
+    let mut robot = Robot::create(&tabletop)?;
+    robot.set_recording(options.history);
+
+    match options.report.as_deref() {
+        Some(template) if template.contains('{') => robot.set_report_template(template)?,
+        Some(format) => robot.set_report_format(format)?,
+        None => (),
+    }
+
+    let replay = match options.gif {
+        Some(_) => Some(Rc::new(RefCell::new(Replay::new(
+            &robot,
+            options.cell_size,
+            options.frame_delay,
+        )?))),
+        None => None,
+    };
+
+    let capture = |robot: &Robot| match &replay {
+        Some(replay) => replay.borrow_mut().capture(robot),
+        None => Ok(()),
+    };
+
+    // :: Failed EXPECTs are reported like any other rejection, but don't stop a
+    //    script; they do make the run as a whole fail, once it is done.
+    let mut failed_expectations = 0;
+
+    capture(&robot)?;
+    match options.script {
+        true => {
+            let mut script = String::new();
+            input
+                .read_to_string(&mut script)
+                .map_err(|error| format!("Could not read {}: {}", source, error))?;
+
+            // :: Replays get a frame for every command run, including those run
+            //    by blocks and procedures.
+            for (line, instruction) in robot.parse_script_to_run(&script)? {
+                let result = match &replay {
+                    Some(replay) => robot.run_observed(&instruction, replay::observer(replay)),
+                    None => robot.run_instruction(&instruction),
+                };
+
+                match result {
+                    Ok(Some(output)) => println!("{}", output),
+                    Ok(None) => (),
+                    Err(message) if robot.expectation_failed() => {
+                        eprintln!("Line {}: {}", line, message);
+                        failed_expectations += 1;
+                    }
+
+                    Err(message) => return Err(format!("Line {}: {}", line, message)),
+                }
+            }
+        }
+
+        false => {
+            let (stdout, stderr) = (io::stdout(), io::stderr());
+            let summary = stream::run_reader(&mut robot, input, |robot, line| {
+                line.write_to(&mut stdout.lock(), &mut stderr.lock())?;
+                capture(robot)
+            })?;
+
+            failed_expectations = summary.failed_expectations;
+        }
+    }
+
+    if let (Some(path), Some(replay)) = (options.gif.as_deref(), replay) {
+        let replay = Rc::try_unwrap(replay)
+            .map_err(|_| "Replay is still being recorded.".to_string())?
+            .into_inner();
+
+        fs::write(path, replay.finish())
+            .map_err(|error| format!("Could not write {}: {}", path, error))?;
+    }
+
+    if let Some(path) = options.svg.as_deref() {
+        fs::write(path, robot.render_svg(options.trail)?)
+            .map_err(|error| format!("Could not write {}: {}", path, error))?;
+    }
+
+    match failed_expectations {
+        0 => Ok(()),
+        count => Err(format!("{} expectation(s) failed.", count)),
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_are_parsed_correctly() {
+        assert_eq!(parse_options(&[]), Ok(Options::default()));
+
+        let options = parse_options(&arguments(&[
+            "--size", "8x6", "--svg", "out.svg", "--trail", "in.txt",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            options,
+            Options {
+                bounds: Bounds::Sized {
+                    width: 8,
+                    height: 6,
+                },
+                script: false,
+                input: Some("in.txt".to_string()),
+                svg: Some("out.svg".to_string()),
+                trail: true,
+                ..Options::default()
+            }
+        );
+    }
+
+    #[test]
+    fn history_can_be_turned_off_unless_drawn() {
+        let options = parse_options(&arguments(&["--no-history"])).unwrap();
+        assert!(!options.history);
+
+        assert!(parse_options(&arguments(&["--no-history", "--svg", "out.svg"])).is_ok());
+        assert!(parse_options(&arguments(&["--trail", "--no-history"])).is_err());
+    }
+
+    #[test]
+    fn replay_options_are_parsed_correctly() {
+        let options = parse_options(&arguments(&[
+            "--gif",
+            "out.gif",
+            "--cell-size",
+            "16",
+            "--frame-delay",
+            "100",
+        ]))
+        .unwrap();
+
+        assert_eq!(options.gif, Some("out.gif".to_string()));
+        assert_eq!((options.cell_size, options.frame_delay), (16, 100));
+    }
+
+    #[test]
+    fn report_format_is_parsed_correctly() {
+        let options = parse_options(&arguments(&["--report", "{x}:{y}", "-"])).unwrap();
+
+        assert_eq!(options.report, Some("{x}:{y}".to_string()));
+        assert_eq!(options.input, Some("-".to_string()));
+    }
+
+    #[test]
+    fn large_and_unbounded_sizes_are_parsed_correctly() {
+        let options = parse_options(&arguments(&["--size", "10000000000x3"])).unwrap();
+        assert_eq!(
+            options.bounds,
+            Bounds::Sized {
+                width: 10_000_000_000,
+                height: 3
+            }
+        );
+
+        let options = parse_options(&arguments(&["--size", "Unbounded"])).unwrap();
+        assert_eq!(options.bounds, Bounds::Unbounded);
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        assert!(parse_options(&arguments(&["--size"])).is_err());
+        assert!(parse_options(&arguments(&["--size", "8 by 6"])).is_err());
+        assert!(parse_options(&arguments(&["--colour"])).is_err());
+        assert!(parse_options(&arguments(&["--cell-size", "big"])).is_err());
+        assert!(parse_options(&arguments(&["a.txt", "b.txt"])).is_err());
+    }
+}
@@ -0,0 +1,538 @@
+use super::command::{Arguments, Command};
+use super::common::*;
+use super::coverage;
+use super::expression::Expr;
+use super::instruction::{Expectation, Instruction};
+use super::report::ReportFormat;
+use super::robot::CommandContext;
+
+// :: ---
+
+/// `PLACE x,y,orientation`
+pub struct Place;
+
+/// `OBSTACLE x,y`
+pub struct Obstacle;
+
+/// `CHARGER x,y`
+pub struct Charger;
+
+/// `MOVE`, or `MOVE steps [policy]`
+pub struct Move;
+
+/// `LEFT` and `RIGHT`
+pub struct Turn;
+
+/// `REPORT`, `REPORT JSON`, `REPORT CSV`, `REPORT STATS`, `REPORT CHARGE`,
+/// `REPORT TRAIL`, or `REPORT MAP`
+pub struct Report;
+
+/// `BACK`
+pub struct Back;
+
+/// `STRAFE LEFT` and `STRAFE RIGHT`
+pub struct Strafe;
+
+/// `UTURN`
+pub struct UTurn;
+
+/// `FACE orientation`
+pub struct Face;
+
+/// `SWEEP`, or `SWEEP PLAN`
+pub struct Sweep;
+
+/// `EXPECT x,y,orientation`, `EXPECT NOT_PLACED`, `EXPECT BLOCKED`, or
+/// `EXPECT OBSTACLE x,y`
+pub struct Expect;
+
+/// The keywords of the built-in commands, and the commands they map to.
+pub fn all() -> Vec<(&'static str, Box<dyn Command>)> {
+    vec![
+        ("PLACE", Box::new(Place)),
+        ("OBSTACLE", Box::new(Obstacle)),
+        ("CHARGER", Box::new(Charger)),
+        ("MOVE", Box::new(Move)),
+        ("LEFT", Box::new(Turn)),
+        ("RIGHT", Box::new(Turn)),
+        ("REPORT", Box::new(Report)),
+        ("BACK", Box::new(Back)),
+        ("STRAFE", Box::new(Strafe)),
+        ("UTURN", Box::new(UTurn)),
+        ("FACE", Box::new(Face)),
+        ("SWEEP", Box::new(Sweep)),
+        ("EXPECT", Box::new(Expect)),
+    ]
+}
+
+// :: ---
+
+impl Command for Place {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        let mut args = args.split(',');
+        let (x, y, orientation) = match (args.next(), args.next(), args.next()) {
+            (Some(x), Some(y), Some(orientation)) => (x, y, orientation),
+            _ => return Err(incomplete_arguments(keyword)),
+        };
+
+        // :: ---
+
+        let rx = Expr::parse(x);
+        let ry = Expr::parse(y);
+        let rorientation = Orientation::parse(orientation);
+
+        // :: If x, y, and orientation all parsed correctly
+        if let (Ok(x), Ok(y), Ok(orientation)) = (rx, ry, rorientation) {
+            Ok(Instruction::Place { x, y, orientation })
+        } else {
+            Err(invalid_arguments(keyword))
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Place { x, y, orientation } => {
+                let position = context.resolve_position(x, y)?;
+                context.place(position, *orientation)
+            }
+
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Obstacle {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        let mut args = args.split(',');
+        let (x, y) = match (args.next(), args.next()) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return Err(incomplete_arguments(keyword)),
+        };
+
+        let rx = Expr::parse(x);
+        let ry = Expr::parse(y);
+
+        // :: If x and y are parsed correctly
+        if let (Ok(x), Ok(y)) = (rx, ry) {
+            Ok(Instruction::Obstacle { x, y })
+        } else {
+            Err(invalid_arguments(keyword))
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Obstacle { x, y } => {
+                let position = context.resolve_position(x, y)?;
+                context.add_obstacle(position)
+            }
+
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Charger {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        match Obstacle.parse(keyword, args)? {
+            Instruction::Obstacle { x, y } => Ok(Instruction::Charger { x, y }),
+            _ => Err(invalid_arguments(keyword)),
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Charger { x, y } => {
+                let position = context.resolve_position(x, y)?;
+                context.add_charging_station(position)
+            }
+
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Move {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        if args.is_empty() {
+            return Ok(Instruction::Move);
+        }
+
+        let (steps, policy) = match args.split_last() {
+            Some((word, steps)) => match MovePolicy::from_name(word) {
+                Some(policy) => (steps, Some(policy)),
+                None => (args, None),
+            },
+
+            None => (args, None),
+        };
+
+        match steps.is_empty() {
+            true => Err(incomplete_arguments(keyword)),
+            false => match Expr::parse(steps.as_str()) {
+                Ok(steps) => Ok(Instruction::MoveBy { steps, policy }),
+                Err(_) => Err(invalid_arguments(keyword)),
+            },
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Move => context.move_forward(),
+            Instruction::MoveBy { steps, policy } => {
+                let steps = context.resolve(steps)?;
+                let policy = policy.unwrap_or_else(|| context.move_policy());
+
+                context.move_by(steps, policy)
+            }
+
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Turn {
+    fn parse(&self, keyword: &str, _args: Arguments) -> Result<Instruction, String> {
+        match keyword {
+            "LEFT" => Ok(Instruction::Left),
+            "RIGHT" => Ok(Instruction::Right),
+            _ => Err(unrecognized_command(keyword)),
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Left => context.turn_left(),
+            Instruction::Right => context.turn_right(),
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Report {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        // :: Anything other than a single known format is rejected, rather than
+        //    falling back to the plain report, so that a mistyped format (e.g.
+        //    `REPORT JOSN`) isn't mistaken for the one asked for.
+        let format = match args.len() {
+            0 => return Ok(Instruction::Report),
+            1 => args.as_str(),
+            _ => return Err(invalid_arguments(keyword)),
+        };
+
+        match format {
+            _ if format.eq_ignore_ascii_case("STATS") => Ok(Instruction::ReportStats),
+            _ if format.eq_ignore_ascii_case("CHARGE") => Ok(Instruction::ReportCharge),
+            _ if format.eq_ignore_ascii_case("JSON") => Ok(Instruction::ReportJson),
+            _ if format.eq_ignore_ascii_case("CSV") => Ok(Instruction::ReportCsv),
+            _ if format.eq_ignore_ascii_case("TRAIL") => Ok(Instruction::ReportTrail),
+            _ if format.eq_ignore_ascii_case("MAP") => Ok(Instruction::ReportMap),
+            _ => Err(invalid_arguments(keyword)),
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Report => context.report(),
+            Instruction::ReportStats => Ok(Some(context.stats().to_string())),
+            Instruction::ReportCharge => context.report_charge(),
+            Instruction::ReportJson => context.report_as(&ReportFormat::Json),
+            Instruction::ReportCsv => context.report_as(&ReportFormat::Csv),
+            Instruction::ReportTrail => Ok(Some(context.report_trail())),
+            Instruction::ReportMap => context.render_ascii(true).map(Some),
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Back {
+    fn parse(&self, _keyword: &str, _args: Arguments) -> Result<Instruction, String> {
+        Ok(Instruction::Back)
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Back => context.move_back(),
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Strafe {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        match args.first() {
+            Some(side) if side.eq_ignore_ascii_case("LEFT") => Ok(Instruction::StrafeLeft),
+            Some(side) if side.eq_ignore_ascii_case("RIGHT") => Ok(Instruction::StrafeRight),
+            Some(_) => Err(invalid_arguments(keyword)),
+            None => Err(incomplete_arguments(keyword)),
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::StrafeLeft => context.strafe(true),
+            Instruction::StrafeRight => context.strafe(false),
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for UTurn {
+    fn parse(&self, _keyword: &str, _args: Arguments) -> Result<Instruction, String> {
+        Ok(Instruction::UTurn)
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::UTurn => context.turn_around(),
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Face {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        match args.first().map(Orientation::parse) {
+            Some(Ok(orientation)) => Ok(Instruction::Face(orientation)),
+            Some(Err(_)) => Err(invalid_arguments(keyword)),
+            None => Err(incomplete_arguments(keyword)),
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        match instruction {
+            Instruction::Face(orientation) => context.face(*orientation),
+            _ => Err(unexpected_instruction(instruction)),
+        }
+    }
+}
+
+impl Command for Sweep {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        match args.first() {
+            None => Ok(Instruction::Sweep { dry_run: false }),
+            Some(word) if word.eq_ignore_ascii_case("PLAN") => {
+                Ok(Instruction::Sweep { dry_run: true })
+            }
+            Some(_) => Err(invalid_arguments(keyword)),
+        }
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        let dry_run = match instruction {
+            Instruction::Sweep { dry_run } => *dry_run,
+            _ => return Err(unexpected_instruction(instruction)),
+        };
+
+        let (position, orientation) = match (context.position(), context.orientation()) {
+            (Some(position), Some(orientation)) => (position, orientation),
+            _ => return Err("Robot is not placed; discarding instruction.".to_string()),
+        };
+
+        let plan = coverage::plan(
+            context.tabletop(),
+            position,
+            orientation,
+            context.remaining_steps(),
+        )?;
+        context.take_steps(plan.planning_steps)?;
+
+        match dry_run {
+            true => Ok(Some(format!("{}\n{}", plan.to_commands(), plan.summary()))),
+            false => {
+                context.execute(&plan.instructions)?;
+                Ok(Some(plan.summary()))
+            }
+        }
+    }
+}
+
+impl Command for Expect {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        let expectation = match args.split_first() {
+            None => return Err(incomplete_arguments(keyword)),
+            Some((word, rest)) if word.eq_ignore_ascii_case("NOT_PLACED") && rest.is_empty() => {
+                Expectation::NotPlaced
+            }
+
+            Some((word, rest)) if word.eq_ignore_ascii_case("BLOCKED") && rest.is_empty() => {
+                Expectation::Blocked
+            }
+
+            Some((word, rest)) if word.eq_ignore_ascii_case("OBSTACLE") => {
+                match Obstacle.parse(keyword, rest)? {
+                    Instruction::Obstacle { x, y } => Expectation::Obstacle { x, y },
+                    _ => return Err(invalid_arguments(keyword)),
+                }
+            }
+
+            Some(_) => match Place.parse(keyword, args)? {
+                Instruction::Place { x, y, orientation } => Expectation::At { x, y, orientation },
+                _ => return Err(invalid_arguments(keyword)),
+            },
+        };
+
+        Ok(Instruction::Expect(expectation))
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        let expectation = match instruction {
+            Instruction::Expect(expectation) => expectation,
+            _ => return Err(unexpected_instruction(instruction)),
+        };
+
+        let placed = context.position().zip(context.orientation());
+        let describe_robot = || match placed {
+            Some((position, orientation)) => format!(
+                "it is at {},{},{}",
+                position.x,
+                position.y,
+                orientation.as_str()
+            ),
+
+            None => "it is not placed".to_string(),
+        };
+
+        match expectation {
+            Expectation::At { x, y, orientation } => {
+                let position = context.resolve_position(x, y)?;
+                match placed == Some((position, *orientation)) {
+                    true => Ok(None),
+                    false => Err(failed_expectation(
+                        context,
+                        &format!(
+                            "robot at {},{},{}",
+                            position.x,
+                            position.y,
+                            orientation.as_str()
+                        ),
+                        &describe_robot(),
+                    )),
+                }
+            }
+
+            Expectation::NotPlaced => match placed {
+                None => Ok(None),
+                Some(_) => Err(failed_expectation(
+                    context,
+                    "robot not to be placed",
+                    &describe_robot(),
+                )),
+            },
+
+            Expectation::Blocked => match placed {
+                None => Err(failed_expectation(
+                    context,
+                    "robot to be blocked",
+                    &describe_robot(),
+                )),
+                Some((position, orientation)) => {
+                    // :: Nothing lies beyond the last representable position.
+                    let ahead = match position.neighbour(orientation) {
+                        Some(ahead) => ahead,
+                        None => return Ok(None),
+                    };
+
+                    match context.tabletop().request_place(&ahead) {
+                        Err(_) => Ok(None),
+                        Ok(_) => Err(failed_expectation(
+                            context,
+                            "robot to be blocked",
+                            &format!("{},{} ahead of it is clear", ahead.x, ahead.y),
+                        )),
+                    }
+                }
+            },
+
+            Expectation::Obstacle { x, y } => {
+                let position = context.resolve_position(x, y)?;
+                match context.tabletop().obstacles().contains(&position) {
+                    true => Ok(None),
+                    false => Err(failed_expectation(
+                        context,
+                        &format!("an obstacle at {},{}", position.x, position.y),
+                        "there is none",
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Rejects an `EXPECT` that did not hold, marking it as such so that runners
+/// can tell it apart from any other rejection (see `Robot::expectation_failed`).
+fn failed_expectation(context: &mut CommandContext, expected: &str, actual: &str) -> String {
+    context.fail_expectation();
+    format!("Expectation failed: expected {}, but {}.", expected, actual)
+}
+
+// :: ---
+
+fn incomplete_arguments(keyword: &str) -> String {
+    format!("Arguments for command {} were incomplete.", keyword)
+}
+
+fn invalid_arguments(keyword: &str) -> String {
+    format!("Arguments for command {} were invalid.", keyword)
+}
+
+fn unrecognized_command(keyword: &str) -> String {
+    format!("Command {} was not recognized or is malformed.", keyword)
+}
+
+/// Commands are only ever handed the instructions they themselves produced,
+/// so this really should not come up.
+fn unexpected_instruction(instruction: &Instruction) -> String {
+    format!(
+        "Instruction {} was handed to the wrong command.",
+        instruction.keyword()
+    )
+}
@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use js_sys::{Array, Function, Object, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use super::builtins;
+use super::expression::is_identifier;
+use super::instruction::{Instruction, KEYWORDS};
+use super::robot::CommandContext;
+
+// :: ---
+
+/// A command that a Robot can be given, e.g. `MOVE` or `PLACE 1,2,NORTH`.
+///
+/// Commands come in two halves: `parse` translates the words following the
+/// command keyword into an `Instruction` (so that malformed commands are caught
+/// before anything runs), and `execute` carries that instruction out.
+pub trait Command {
+    /// Translates the text that followed `keyword` (which is handed over as it
+    /// was registered, i.e. uppercased) into an `Instruction`.
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String>;
+
+    /// Carries out an `Instruction` previously produced by `parse`, returning
+    /// any output it produced.
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String>;
+}
+
+/// The (trimmed) text following a command keyword, as written.
+///
+/// Arguments borrow from the command being parsed, so that commands can be
+/// picked apart without copying (or uppercasing) them first; keywords among
+/// them should be matched case-insensitively, e.g. with `eq_ignore_ascii_case`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Arguments<'a> {
+    text: &'a str,
+}
+
+impl<'a> Arguments<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Arguments { text: text.trim() }
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// The number of (whitespace-separated) words.
+    pub fn len(&self) -> usize {
+        self.words().count()
+    }
+
+    pub fn words(&self) -> std::str::SplitWhitespace<'a> {
+        self.text.split_whitespace()
+    }
+
+    pub fn first(&self) -> Option<&'a str> {
+        self.words().next()
+    }
+
+    /// The first word, and the arguments that follow it.
+    pub fn split_first(&self) -> Option<(&'a str, Arguments<'a>)> {
+        match self.text.find(char::is_whitespace) {
+            Some(index) => Some((&self.text[..index], Arguments::new(&self.text[index..]))),
+            None if self.is_empty() => None,
+            None => Some((self.text, Arguments::default())),
+        }
+    }
+
+    /// The last word, and the arguments that precede it.
+    pub fn split_last(&self) -> Option<(&'a str, Arguments<'a>)> {
+        match self.text.rfind(char::is_whitespace) {
+            Some(index) => Some((
+                self.text[index..].trim_start(),
+                Arguments::new(&self.text[..index]),
+            )),
+            None if self.is_empty() => None,
+            None => Some((self.text, Arguments::default())),
+        }
+    }
+
+    /// The (trimmed) fragments between occurrences of `separator`, e.g. the
+    /// `1`, `2` and `NORTH` of `PLACE 1, 2, NORTH`.
+    pub fn split(&self, separator: char) -> impl Iterator<Item = &'a str> {
+        self.text.split(separator).map(str::trim)
+    }
+}
+
+/// Maps command keywords to the `Command`s that handle them.
+///
+/// The default registry contains just the built-in commands (`PLACE`, `MOVE`, etc.).
+#[derive(Clone)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Rc<dyn Command>>,
+}
+
+impl CommandRegistry {
+    /// Registers `command` to handle `keyword` (case-insensitive).
+    ///
+    /// Keywords already taken (by either a built-in command, a language construct,
+    /// or a previously registered command) cannot be registered over.
+    pub fn register<C>(&mut self, keyword: &str, command: C) -> Result<(), String>
+    where
+        C: Command + 'static,
+    {
+        let keyword = keyword.trim().to_uppercase();
+
+        if !is_identifier(&keyword) {
+            return Err(format!("Command name {} is invalid.", keyword));
+        }
+
+        if KEYWORDS.contains(&keyword.as_str()) || self.contains(&keyword) {
+            return Err(format!("Command {} is already defined.", keyword));
+        }
+
+        self.commands.insert(keyword, Rc::new(command));
+        Ok(())
+    }
+
+    /// The command registered to handle `keyword` (in any case), if any.
+    pub fn get(&self, keyword: &str) -> Option<Rc<dyn Command>> {
+        self.find(keyword).map(|(_, command)| Rc::clone(command))
+    }
+
+    /// The command registered to handle `keyword` (in any case), along with
+    /// the keyword as it was registered.
+    pub fn find(&self, keyword: &str) -> Option<(&str, &Rc<dyn Command>)> {
+        // :: Keywords are mostly given in uppercase already (and always are, when
+        //    running instructions), so only fall back to comparing them one by
+        //    one (rather than uppercasing the keyword) when that misses.
+        self.commands
+            .get_key_value(keyword)
+            .or_else(|| {
+                self.commands
+                    .iter()
+                    .find(|(registered, _)| registered.eq_ignore_ascii_case(keyword))
+            })
+            .map(|(registered, command)| (registered.as_str(), command))
+    }
+
+    pub fn contains(&self, keyword: &str) -> bool {
+        self.find(keyword).is_some()
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let commands = builtins::all()
+            .into_iter()
+            .map(|(keyword, command)| (keyword.to_string(), Rc::from(command)))
+            .collect();
+
+        CommandRegistry { commands }
+    }
+}
+
+thread_local! {
+    static BUILT_IN_COMMANDS: CommandRegistry = CommandRegistry::default();
+}
+
+/// Runs `f` against a (shared) registry of just the built-in commands.
+pub(crate) fn with_built_in_commands<R, F>(f: F) -> R
+where
+    F: FnOnce(&CommandRegistry) -> R,
+{
+    BUILT_IN_COMMANDS.with(f)
+}
+
+// :: ---
+
+/// A command handled by a JS function, as registered via `Robot::register_command`.
+///
+/// The function is called with the command arguments (as an array of uppercased
+/// words) and a snapshot of the robot state (`{ placed, x, y, orientation, width,
+/// height }`). It may return either:
+///
+/// - nothing, if the command has no further effect;
+/// - a string of commands (separated by newlines or `;`) for the robot to run; or
+/// - an object `{ run, output }`, where both fields are optional, and `output`
+///   is reported as the output of the command.
+///
+/// Errors thrown by the function reject the command.
+pub struct JsCommand {
+    handler: Function,
+}
+
+impl JsCommand {
+    pub fn new(handler: Function) -> Self {
+        JsCommand { handler }
+    }
+
+    fn snapshot(context: &CommandContext) -> Result<JsValue, String> {
+        let state = Object::new();
+        let position = context.position();
+
+        let fields: [(&str, JsValue); 6] = [
+            ("placed", context.is_placed().into()),
+            (
+                "x",
+                position.map_or(JsValue::NULL, |position| (position.x as f64).into()),
+            ),
+            (
+                "y",
+                position.map_or(JsValue::NULL, |position| (position.y as f64).into()),
+            ),
+            (
+                "orientation",
+                context
+                    .orientation()
+                    .map_or(JsValue::NULL, |orientation| orientation.as_str().into()),
+            ),
+            (
+                "width",
+                context
+                    .tabletop()
+                    .width()
+                    .map_or(JsValue::NULL, |width| (width as f64).into()),
+            ),
+            (
+                "height",
+                context
+                    .tabletop()
+                    .height()
+                    .map_or(JsValue::NULL, |height| (height as f64).into()),
+            ),
+        ];
+
+        for (name, value) in fields.iter() {
+            Reflect::set(&state, &JsValue::from_str(name), value).map_err(to_message)?;
+        }
+
+        Ok(state.into())
+    }
+}
+
+impl Command for JsCommand {
+    fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+        Ok(Instruction::Custom {
+            keyword: keyword.to_string(),
+            args: args.words().map(|arg| arg.to_uppercase()).collect(),
+        })
+    }
+
+    fn execute(
+        &self,
+        instruction: &Instruction,
+        context: &mut CommandContext,
+    ) -> Result<Option<String>, String> {
+        let args = match instruction {
+            Instruction::Custom { args, .. } => args
+                .iter()
+                .map(|arg| JsValue::from_str(arg))
+                .collect::<Array>(),
+
+            _ => Array::new(),
+        };
+
+        let state = JsCommand::snapshot(context)?;
+        let result = self
+            .handler
+            .call2(&JsValue::NULL, &args, &state)
+            .map_err(to_message)?;
+
+        // :: ---
+
+        if let Some(commands) = result.as_string() {
+            context.run(&commands)?;
+            return Ok(None);
+        }
+
+        if !result.is_object() {
+            return Ok(None);
+        }
+
+        let field = |name: &str| {
+            Reflect::get(&result, &JsValue::from_str(name))
+                .ok()
+                .and_then(|value| value.as_string())
+        };
+
+        if let Some(commands) = field("run") {
+            context.run(&commands)?;
+        }
+
+        Ok(field("output"))
+    }
+}
+
+fn to_message(error: JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => error.message().into(),
+        None => error
+            .as_string()
+            .unwrap_or_else(|| "Command handler failed.".to_string()),
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::robot::Robot;
+    use crate::components::tabletop::Tabletop;
+
+    /// `ZIGZAG n`: alternates between moving forward and turning, `n` times over.
+    struct Zigzag;
+
+    impl Command for Zigzag {
+        fn parse(&self, keyword: &str, args: Arguments) -> Result<Instruction, String> {
+            match args.first().map(|arg| (arg, arg.parse::<u32>())) {
+                Some((arg, Ok(_))) => Ok(Instruction::Custom {
+                    keyword: keyword.to_string(),
+                    args: vec![arg.to_string()],
+                }),
+
+                _ => Err(format!("Arguments for command {} were invalid.", keyword)),
+            }
+        }
+
+        fn execute(
+            &self,
+            instruction: &Instruction,
+            context: &mut CommandContext,
+        ) -> Result<Option<String>, String> {
+            let times = match instruction {
+                Instruction::Custom { args, .. } => args[0].parse::<u32>().unwrap(),
+                _ => unreachable!(),
+            };
+
+            for turn in 0..times {
+                context.move_forward()?;
+                match turn % 2 {
+                    0 => context.turn_right()?,
+                    _ => context.turn_left()?,
+                };
+            }
+
+            Ok(Some(format!("Zigzagged {} times.", times)))
+        }
+    }
+
+    /// `HOME`: places the robot back at the origin, by running other commands.
+    struct Home;
+
+    impl Command for Home {
+        fn parse(&self, keyword: &str, _args: Arguments) -> Result<Instruction, String> {
+            Ok(Instruction::Custom {
+                keyword: keyword.to_string(),
+                args: vec![],
+            })
+        }
+
+        fn execute(
+            &self,
+            _instruction: &Instruction,
+            context: &mut CommandContext,
+        ) -> Result<Option<String>, String> {
+            context.run("PLACE 0,0,NORTH; REPORT")?;
+            Ok(None)
+        }
+    }
+
+    fn create_robot() -> Robot {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        robot.register("zigzag", Zigzag).unwrap();
+        robot.register("HOME", Home).unwrap();
+        robot
+    }
+
+    #[test]
+    fn built_in_commands_are_registered_by_default() {
+        let registry = CommandRegistry::default();
+
+        for keyword in ["PLACE", "OBSTACLE", "MOVE", "LEFT", "RIGHT", "REPORT"].iter() {
+            assert!(registry.contains(keyword));
+        }
+
+        assert!(!registry.contains("REPEAT"));
+        assert!(!registry.contains("ZIGZAG"));
+    }
+
+    #[test]
+    fn commands_are_found_in_any_case() {
+        let registry = CommandRegistry::default();
+
+        assert!(registry.contains("Move"));
+        assert_eq!(
+            registry.find("place").map(|(keyword, _)| keyword),
+            Some("PLACE")
+        );
+    }
+
+    #[test]
+    fn arguments_are_picked_apart_as_written() {
+        let args = Arguments::new("  1, 2 , north  ");
+        assert_eq!(args.as_str(), "1, 2 , north");
+        assert_eq!(
+            args.split(',').collect::<Vec<&str>>(),
+            vec!["1", "2", "north"]
+        );
+
+        let args = Arguments::new("$n + 1  as-far-as-possible");
+        assert_eq!(args.len(), 4);
+
+        let (first, rest) = args.split_first().unwrap();
+        assert_eq!((first, rest.as_str()), ("$n", "+ 1  as-far-as-possible"));
+
+        let (last, rest) = args.split_last().unwrap();
+        assert_eq!((last, rest.as_str()), ("as-far-as-possible", "$n + 1"));
+
+        assert_eq!(
+            Arguments::new("MOVE").split_first(),
+            Some(("MOVE", Arguments::default()))
+        );
+        assert_eq!(Arguments::new(" \t ").split_first(), None);
+        assert_eq!(Arguments::new("").split_last(), None);
+    }
+
+    #[test]
+    fn taken_or_invalid_keywords_cannot_be_registered() {
+        let mut registry = CommandRegistry::default();
+
+        assert!(registry.register("MOVE", Home).is_err());
+        assert!(registry.register("repeat", Home).is_err());
+        assert!(registry.register("GO HOME", Home).is_err());
+        assert!(registry.register("1UP", Home).is_err());
+
+        assert!(registry.register("HOME", Home).is_ok());
+        assert!(registry.register("home", Home).is_err());
+    }
+
+    #[test]
+    fn custom_commands_can_be_evaluated() {
+        let mut robot = create_robot();
+
+        assert!(robot.evaluate("PLACE 0,0,NORTH").is_ok());
+        assert_eq!(
+            robot.evaluate("ZIGZAG 3"),
+            Ok(Some("Zigzagged 3 times.".to_string()))
+        );
+
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("1,2,EAST".to_string())));
+    }
+
+    #[test]
+    fn custom_commands_validate_their_own_arguments() {
+        let mut robot = create_robot();
+
+        assert!(robot.evaluate("PLACE 0,0,NORTH").is_ok());
+        assert!(robot.evaluate("ZIGZAG").is_err());
+        assert!(robot.evaluate("ZIGZAG north").is_err());
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("0,0,NORTH".to_string())));
+    }
+
+    #[test]
+    fn custom_commands_can_run_other_commands() {
+        let mut robot = create_robot();
+
+        assert_eq!(robot.evaluate("home"), Ok(Some("0,0,NORTH".to_string())));
+        assert_eq!(
+            robot.run_script("MOVE\nREPEAT 2 { HOME }"),
+            Ok(vec!["0,0,NORTH".to_string(), "0,0,NORTH".to_string()])
+        );
+    }
+
+    #[test]
+    fn custom_commands_share_the_step_limit() {
+        let mut robot = create_robot();
+        robot.set_step_limit(3);
+
+        // :: `HOME` itself, then `PLACE` and `REPORT`.
+        assert!(robot.evaluate("HOME").is_ok());
+        assert!(robot.run_script("HOME; MOVE").is_err());
+    }
+
+    #[test]
+    fn custom_commands_are_not_known_to_other_robots() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        // :: Without a `HOME` command, this is just a call to an unknown procedure.
+        assert!(robot.evaluate("HOME").is_err());
+        assert!(create_robot().evaluate("HOME").is_ok());
+    }
+
+    #[test]
+    fn procedures_cannot_be_named_after_custom_commands() {
+        let mut robot = create_robot();
+        assert!(robot.run_script("DEFINE HOME\n  MOVE\nEND").is_err());
+    }
+}
@@ -14,20 +14,209 @@ pub enum Orientation {
 
 impl Orientation {
     pub fn parse(plaintext: &str) -> Result<Orientation, String> {
-        match plaintext.to_uppercase().as_str() {
-            "NORTH" => Ok(Orientation::North),
-            "EAST" => Ok(Orientation::East),
-            "WEST" => Ok(Orientation::West),
-            "SOUTH" => Ok(Orientation::South),
+        let orientations = [
+            Orientation::North,
+            Orientation::East,
+            Orientation::West,
+            Orientation::South,
+        ];
 
-            _ => Err(format!("Unexpected orientation: {}", plaintext)),
+        orientations
+            .iter()
+            .find(|orientation| orientation.as_str().eq_ignore_ascii_case(plaintext))
+            .copied()
+            .ok_or_else(|| format!("Unexpected orientation: {}", plaintext))
+    }
+
+    /// The plaintext name of this orientation, as used in commands and reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Orientation::North => "NORTH",
+            Orientation::East => "EAST",
+            Orientation::South => "SOUTH",
+            Orientation::West => "WEST",
+        }
+    }
+
+    /// The opposite orientation, faced after turning 180 degrees.
+    pub fn reversed(self) -> Orientation {
+        match self {
+            Orientation::North => Orientation::South,
+            Orientation::South => Orientation::North,
+            Orientation::East => Orientation::West,
+            Orientation::West => Orientation::East,
+        }
+    }
+
+    /// The orientation faced after turning 90 degrees to the left.
+    pub fn turned_left(self) -> Orientation {
+        match self {
+            Orientation::North => Orientation::West,
+            Orientation::West => Orientation::South,
+            Orientation::South => Orientation::East,
+            Orientation::East => Orientation::North,
+        }
+    }
+
+    /// The orientation faced after turning 90 degrees to the right.
+    pub fn turned_right(self) -> Orientation {
+        match self {
+            Orientation::North => Orientation::East,
+            Orientation::East => Orientation::South,
+            Orientation::South => Orientation::West,
+            Orientation::West => Orientation::North,
         }
     }
 }
 
+/// How a multi-step movement (e.g. `MOVE 3`) is handled, when something is in the way.
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MovePolicy {
+    /// The movement is rejected outright, and the robot stays where it is.
+    AllOrNothing,
+    /// The robot moves up until the last free position before the obstruction.
+    AsFarAsPossible,
+}
+
+impl MovePolicy {
+    pub fn parse(plaintext: &str) -> Result<MovePolicy, String> {
+        MovePolicy::from_name(plaintext)
+            .ok_or_else(|| format!("Unexpected move policy: {}", plaintext))
+    }
+
+    /// The policy named `name`, in any case, and with either underscores or
+    /// dashes (e.g. `AS_FAR_AS_POSSIBLE`, or `as-far-as-possible`).
+    pub fn from_name(name: &str) -> Option<MovePolicy> {
+        let matches = |policy: &str| {
+            name.len() == policy.len()
+                && name.bytes().zip(policy.bytes()).all(|(given, expected)| {
+                    given.to_ascii_uppercase() == expected || (given == b'-' && expected == b'_')
+                })
+        };
+
+        if matches("ALL_OR_NOTHING") {
+            Some(MovePolicy::AllOrNothing)
+        } else if matches("AS_FAR_AS_POSSIBLE") {
+            Some(MovePolicy::AsFarAsPossible)
+        } else {
+            None
+        }
+    }
+}
+
+/// How much energy a Robot can hold, and how much its movements cost.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnergyModel {
+    pub capacity: u32,
+    /// The cost of moving a single unit, in any direction.
+    pub move_cost: u32,
+    /// The cost of any change in orientation (e.g. `LEFT`, `UTURN`, `FACE`).
+    pub turn_cost: u32,
+}
+
+/// A single coordinate of a `Position`.
+///
+/// Coordinates are 64-bit, so that very large (or unbounded) Tabletops can be
+/// moved around on; all arithmetic on them is checked.
+pub type Coordinate = i64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[wasm_bindgen]
 pub struct Position {
-    pub x: i32,
-    pub y: i32,
+    #[wasm_bindgen(skip)]
+    pub x: Coordinate,
+    #[wasm_bindgen(skip)]
+    pub y: Coordinate,
+}
+
+/// JS-facing accessors: `Coordinate`s would otherwise show up as BigInts, so
+/// they are read as plain numbers (exact up to 2^53), and written as 32-bit ones.
+#[wasm_bindgen]
+impl Position {
+    #[wasm_bindgen(getter = x)]
+    pub fn x_to_js(&self) -> f64 {
+        self.x as f64
+    }
+
+    #[wasm_bindgen(setter = x)]
+    pub fn set_x_from_js(&mut self, x: i32) {
+        self.x = x.into();
+    }
+
+    #[wasm_bindgen(getter = y)]
+    pub fn y_to_js(&self) -> f64 {
+        self.y as f64
+    }
+
+    #[wasm_bindgen(setter = y)]
+    pub fn set_y_from_js(&mut self, y: i32) {
+        self.y = y.into();
+    }
+}
+
+impl Position {
+    /// The position one unit away from this one, in the direction of `orientation`;
+    /// or `None`, if that is past the range of a `Coordinate`.
+    pub fn neighbour(self, orientation: Orientation) -> Option<Position> {
+        let Position { x, y } = self;
+
+        Some(match orientation {
+            Orientation::North => Position {
+                x,
+                y: y.checked_add(1)?,
+            },
+            Orientation::South => Position {
+                x,
+                y: y.checked_sub(1)?,
+            },
+            Orientation::East => Position {
+                x: x.checked_add(1)?,
+                y,
+            },
+            Orientation::West => Position {
+                x: x.checked_sub(1)?,
+                y,
+            },
+        })
+    }
+
+    /// The position `units` away from this one, in the direction of `orientation`;
+    /// or `None`, if that is past the range of a `Coordinate`.
+    pub fn moved_by(self, orientation: Orientation, units: Coordinate) -> Option<Position> {
+        let Position { x, y } = self;
+
+        Some(match orientation {
+            Orientation::North => Position {
+                x,
+                y: y.checked_add(units)?,
+            },
+            Orientation::South => Position {
+                x,
+                y: y.checked_sub(units)?,
+            },
+            Orientation::East => Position {
+                x: x.checked_add(units)?,
+                y,
+            },
+            Orientation::West => Position {
+                x: x.checked_sub(units)?,
+                y,
+            },
+        })
+    }
+
+    /// How many units ahead of this position `other` is, going in the direction
+    /// of `orientation`; or `None`, if it isn't straight ahead (or is more than
+    /// a `Coordinate` away).
+    pub fn units_towards(self, other: Position, orientation: Orientation) -> Option<Coordinate> {
+        let units = match orientation {
+            Orientation::North if other.x == self.x => other.y.checked_sub(self.y),
+            Orientation::South if other.x == self.x => self.y.checked_sub(other.y),
+            Orientation::East if other.y == self.y => other.x.checked_sub(self.x),
+            Orientation::West if other.y == self.y => self.x.checked_sub(other.x),
+            _ => None,
+        };
+
+        units.filter(|units| *units >= 0)
+    }
 }
@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::common::*;
+use super::instruction::Instruction;
+use super::tabletop::Tabletop;
+
+// :: ---
+
+/// A route that takes a robot over every free position it can reach on a Tabletop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoveragePlan {
+    /// The route, as primitive `Move`, `Left` and `Right` instructions only.
+    pub instructions: Vec<Instruction>,
+    /// How many distinct positions the route visits (including the starting one).
+    pub visited: usize,
+    /// How many moves land on a position that was already visited.
+    pub revisits: usize,
+    /// Free positions that cannot be reached from the start at all.
+    pub unreachable: Vec<Position>,
+    /// How many steps planning took, at one per position searched.
+    pub planning_steps: u32,
+}
+
+impl CoveragePlan {
+    /// The route as plaintext commands, e.g. `MOVE; RIGHT; MOVE`.
+    pub fn to_commands(&self) -> String {
+        self.instructions
+            .iter()
+            .map(|instruction| instruction.keyword())
+            .collect::<Vec<&str>>()
+            .join("; ")
+    }
+
+    pub fn summary(&self) -> String {
+        let unreachable = match self.unreachable.is_empty() {
+            true => "none".to_string(),
+            false => self
+                .unreachable
+                .iter()
+                .map(|position| format!("({}, {})", position.x, position.y))
+                .collect::<Vec<String>>()
+                .join(" "),
+        };
+
+        format!(
+            "Covered {} positions in {} instructions, with {} revisits. Unreachable: {}.",
+            self.visited,
+            self.instructions.len(),
+            self.revisits,
+            unreachable
+        )
+    }
+}
+
+/// Plans a route over every free position reachable from `start`.
+///
+/// The route sweeps the Tabletop row by row, alternating direction on each row
+/// (boustrophedon). Whenever the next position in the sweep isn't right next to
+/// the robot (e.g. because an obstacle is in the way), the robot detours along
+/// the shortest path to the nearest position it hasn't visited yet.
+///
+/// Only Tabletops that can be gone over position by position, with at most
+/// `PLAN_SIZE_LIMIT` positions, can be swept (see `Tabletop::grid_size`).
+/// Planning is aborted once it has taken more than `step_budget` steps.
+pub fn plan(
+    tabletop: &Tabletop,
+    start: Position,
+    orientation: Orientation,
+    step_budget: u32,
+) -> Result<CoveragePlan, String> {
+    let (width, height) = tabletop.grid_size()?;
+    if i64::from(width) * i64::from(height) > PLAN_SIZE_LIMIT {
+        return Err(format!(
+            "Tabletop is too large to sweep; at most {} positions are supported.",
+            PLAN_SIZE_LIMIT
+        ));
+    }
+
+    let is_free = |position: &Position| tabletop.request_place(position).is_ok();
+    let mut budget = step_budget;
+
+    let reachable = shortest_paths(start, &is_free, |_| false, &mut budget)?.1;
+    let unreachable = (0..height)
+        .flat_map(|y| {
+            (0..width).map(move |x| Position {
+                x: x.into(),
+                y: y.into(),
+            })
+        })
+        .filter(|position| is_free(position) && !reachable.contains(position))
+        .collect::<Vec<Position>>();
+
+    // :: Where a position falls in the sweep: row by row, going east on
+    //    even rows and west on odd ones.
+    let width = Coordinate::from(width);
+    let sweep_order = |position: &Position| {
+        let column = match position.y % 2 {
+            0 => position.x,
+            _ => width - 1 - position.x,
+        };
+
+        (position.y, column)
+    };
+
+    // :: ---
+
+    let mut visited = HashSet::new();
+    visited.insert(start);
+
+    let mut route = vec![];
+    let mut revisits = 0;
+    let mut current = start;
+
+    while visited.len() < reachable.len() {
+        let (targets, _) = shortest_paths(
+            current,
+            &is_free,
+            |position| !visited.contains(position),
+            &mut budget,
+        )?;
+
+        let target = targets
+            .into_iter()
+            .min_by_key(|(position, _)| sweep_order(position));
+
+        let path = match target {
+            Some((_, path)) => path,
+            None => break,
+        };
+
+        for position in path {
+            if !visited.insert(position) {
+                revisits += 1;
+            }
+
+            route.push(position);
+            current = position;
+        }
+    }
+
+    Ok(CoveragePlan {
+        instructions: to_instructions(start, orientation, &route),
+        visited: visited.len(),
+        revisits,
+        unreachable,
+        planning_steps: step_budget - budget,
+    })
+}
+
+/// The paths to the targets a search found, and every position it reached.
+type Search = (Vec<(Position, Vec<Position>)>, HashSet<Position>);
+
+/// Breadth-first search from `start`, over free positions.
+///
+/// Returns the paths (excluding `start`) to the nearest positions satisfying
+/// `is_target`, all of which are the same distance away; along with every
+/// position the search reached. Each position searched takes a step out of
+/// `budget`, and the search fails once there are none left.
+fn shortest_paths<F, T>(
+    start: Position,
+    is_free: &F,
+    is_target: T,
+    budget: &mut u32,
+) -> Result<Search, String>
+where
+    F: Fn(&Position) -> bool,
+    T: Fn(&Position) -> bool,
+{
+    let mut previous = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut frontier = VecDeque::new();
+    let mut targets = vec![];
+
+    seen.insert(start);
+    frontier.push_back((start, 0));
+
+    let mut target_distance = None;
+    while let Some((position, distance)) = frontier.pop_front() {
+        if matches!(target_distance, Some(target_distance) if distance > target_distance) {
+            break;
+        }
+
+        if *budget == 0 {
+            return Err(
+                "Step limit was exceeded while planning the sweep; aborting evaluation."
+                    .to_string(),
+            );
+        }
+
+        *budget -= 1;
+
+        if position != start && is_target(&position) {
+            target_distance = Some(distance);
+            targets.push(position);
+            continue;
+        }
+
+        for direction in DIRECTIONS.iter() {
+            if let Some(next) = position.neighbour(*direction) {
+                if is_free(&next) && seen.insert(next) {
+                    previous.insert(next, position);
+                    frontier.push_back((next, distance + 1));
+                }
+            }
+        }
+    }
+
+    let paths = targets
+        .into_iter()
+        .map(|target| {
+            let mut path = vec![target];
+            while let Some(&step) = previous.get(path.last().unwrap()) {
+                if step == start {
+                    break;
+                }
+
+                path.push(step);
+            }
+
+            path.reverse();
+            (target, path)
+        })
+        .collect();
+
+    Ok((paths, seen))
+}
+
+/// Translates a route (a list of adjacent positions) into primitive instructions.
+fn to_instructions(
+    start: Position,
+    orientation: Orientation,
+    route: &[Position],
+) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut facing = orientation;
+    let mut current = start;
+
+    for &next in route {
+        let direction = DIRECTIONS
+            .iter()
+            .copied()
+            .find(|direction| current.neighbour(*direction) == Some(next))
+            .expect("Route positions should be adjacent.");
+
+        if direction == facing.turned_left() {
+            instructions.push(Instruction::Left);
+        } else if direction == facing.turned_right() {
+            instructions.push(Instruction::Right);
+        } else if direction == facing.reversed() {
+            instructions.push(Instruction::Right);
+            instructions.push(Instruction::Right);
+        }
+
+        instructions.push(Instruction::Move);
+        facing = direction;
+        current = next;
+    }
+
+    instructions
+}
+
+/// The most positions a Tabletop may have to be swept; past that, even finding
+/// out which positions are reachable takes too long (and too much memory).
+pub const PLAN_SIZE_LIMIT: i64 = 1 << 16;
+
+const DIRECTIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Follows a plan's instructions, returning every position stood on.
+    fn trace(plan: &CoveragePlan, start: Position, orientation: Orientation) -> Vec<Position> {
+        let mut positions = vec![start];
+        let mut facing = orientation;
+
+        for instruction in plan.instructions.iter() {
+            match instruction {
+                Instruction::Move => {
+                    positions.push(positions.last().unwrap().neighbour(facing).unwrap());
+                }
+
+                Instruction::Left => facing = facing.turned_left(),
+                Instruction::Right => facing = facing.turned_right(),
+                _ => panic!("Plans should only contain primitive instructions."),
+            }
+        }
+
+        positions
+    }
+
+    #[test]
+    fn empty_tabletops_are_swept_without_revisits() {
+        let tabletop = Tabletop::new(4, 3).unwrap();
+        let start = Position { x: 0, y: 0 };
+        let plan = plan(&tabletop, start, Orientation::North, u32::MAX).unwrap();
+
+        assert_eq!(plan.visited, 12);
+        assert_eq!(plan.revisits, 0);
+        assert!(plan.unreachable.is_empty());
+
+        let positions = trace(&plan, start, Orientation::North);
+        assert_eq!(positions.len(), 12);
+        assert_eq!(positions[3], Position { x: 3, y: 0 });
+        assert_eq!(positions[4], Position { x: 3, y: 1 });
+        assert_eq!(positions[11], Position { x: 3, y: 2 });
+    }
+
+    #[test]
+    fn obstacles_are_detoured_around() {
+        let mut tabletop = Tabletop::new(5, 5).unwrap();
+        for &(x, y) in [(1, 1), (2, 1), (3, 1), (2, 3)].iter() {
+            tabletop.add_obstacle(x, y).unwrap();
+        }
+
+        let start = Position { x: 2, y: 2 };
+        let plan = plan(&tabletop, start, Orientation::East, u32::MAX).unwrap();
+        let positions = trace(&plan, start, Orientation::East);
+
+        assert_eq!(plan.visited, 21);
+        assert!(plan.unreachable.is_empty());
+
+        for position in positions.iter() {
+            assert!(tabletop.request_place(position).is_ok());
+        }
+
+        let distinct = positions.iter().collect::<HashSet<&Position>>();
+        assert_eq!(distinct.len(), 21);
+        assert_eq!(positions.len() - 1, 20 + plan.revisits);
+    }
+
+    #[test]
+    fn unreachable_positions_are_reported() {
+        let mut tabletop = Tabletop::new(3, 3).unwrap();
+        tabletop.add_obstacle(1, 2).unwrap();
+        tabletop.add_obstacle(2, 1).unwrap();
+
+        let plan = plan(
+            &tabletop,
+            Position { x: 0, y: 0 },
+            Orientation::North,
+            u32::MAX,
+        )
+        .unwrap();
+
+        assert_eq!(plan.visited, 6);
+        assert_eq!(plan.unreachable, vec![Position { x: 2, y: 2 }]);
+        assert!(plan.summary().ends_with("Unreachable: (2, 2)."));
+    }
+
+    #[test]
+    fn planning_is_limited() {
+        let tabletop = Tabletop::new(10, 10).unwrap();
+        let start = Position { x: 0, y: 0 };
+
+        let planned = plan(&tabletop, start, Orientation::North, u32::MAX).unwrap();
+        assert!(planned.planning_steps >= 100);
+        assert_eq!(
+            plan(&tabletop, start, Orientation::North, planned.planning_steps),
+            Ok(planned.clone())
+        );
+        assert!(plan(
+            &tabletop,
+            start,
+            Orientation::North,
+            planned.planning_steps - 1
+        )
+        .is_err());
+
+        let tabletop = Tabletop::new(257, 256).unwrap();
+        assert!(plan(&tabletop, start, Orientation::North, u32::MAX).is_err());
+    }
+}
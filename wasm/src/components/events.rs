@@ -0,0 +1,162 @@
+use js_sys::{Function, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use super::common::*;
+
+// :: ---
+
+/// A change in (or a notable non-change to) the state of a Robot and its Tabletop.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The robot was (re-)placed on the Tabletop.
+    Placed {
+        position: Position,
+        orientation: Orientation,
+    },
+
+    /// The robot moved, whether by a single unit or several.
+    Moved {
+        from: Position,
+        to: Position,
+    },
+
+    Turned {
+        from: Orientation,
+        to: Orientation,
+    },
+
+    /// The robot could not move, because something was in the way.
+    MoveBlocked {
+        reason: String,
+    },
+
+    /// The robot could not move, because it would have fallen off the Tabletop at `at`.
+    FallPrevented {
+        at: Position,
+    },
+
+    ObstacleAdded {
+        position: Position,
+    },
+
+    /// A command was rejected (e.g. it was malformed, or could not be carried out).
+    ///
+    /// Scripts that can't be parsed are rejected as a whole before anything is
+    /// run, with `command` being the statement at fault; they are only reported
+    /// when they were meant to be run, though, not when just parsed or compiled.
+    CommandIgnored {
+        command: String,
+        reason: String,
+    },
+}
+
+/// Something that wants to be told about the `Event`s a Robot emits.
+///
+/// Any `FnMut(&Event)` closure is a `Subscriber`.
+pub trait Subscriber {
+    fn notify(&mut self, event: &Event);
+}
+
+impl<F> Subscriber for F
+where
+    F: FnMut(&Event),
+{
+    fn notify(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+impl Event {
+    /// The name of this kind of event, e.g. `Moved`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Placed { .. } => "Placed",
+            Event::Moved { .. } => "Moved",
+            Event::Turned { .. } => "Turned",
+            Event::MoveBlocked { .. } => "MoveBlocked",
+            Event::FallPrevented { .. } => "FallPrevented",
+            Event::ObstacleAdded { .. } => "ObstacleAdded",
+            Event::CommandIgnored { .. } => "CommandIgnored",
+        }
+    }
+
+    /// Converts this event into a plain JS object, e.g.
+    /// `{ type: "Moved", from: { x: 0, y: 0 }, to: { x: 0, y: 1 } }`.
+    pub fn to_js(&self) -> JsValue {
+        let object = Object::new();
+        let mut fields: Vec<(&str, JsValue)> = vec![("type", self.name().into())];
+
+        match self {
+            Event::Placed {
+                position,
+                orientation,
+            } => {
+                fields.push(("position", position_to_js(*position)));
+                fields.push(("orientation", orientation.as_str().into()));
+            }
+
+            Event::Moved { from, to } => {
+                fields.push(("from", position_to_js(*from)));
+                fields.push(("to", position_to_js(*to)));
+            }
+
+            Event::Turned { from, to } => {
+                fields.push(("from", from.as_str().into()));
+                fields.push(("to", to.as_str().into()));
+            }
+
+            Event::MoveBlocked { reason } => fields.push(("reason", reason.as_str().into())),
+            Event::FallPrevented { at } => fields.push(("at", position_to_js(*at))),
+            Event::ObstacleAdded { position } => {
+                fields.push(("position", position_to_js(*position)))
+            }
+
+            Event::CommandIgnored { command, reason } => {
+                fields.push(("command", command.as_str().into()));
+                fields.push(("reason", reason.as_str().into()));
+            }
+        }
+
+        set_fields(&object, fields);
+        object.into()
+    }
+}
+
+/// A JS callback subscribed to a Robot via `Robot::subscribe`, which is handed
+/// every event as a plain object (see `Event::to_js`).
+pub struct JsSubscriber {
+    callback: Function,
+}
+
+impl JsSubscriber {
+    pub fn new(callback: Function) -> Self {
+        JsSubscriber { callback }
+    }
+}
+
+impl Subscriber for JsSubscriber {
+    fn notify(&mut self, event: &Event) {
+        // :: A subscriber that throws has no bearing on the robot itself,
+        //    so there's nothing sensible to do with the error here.
+        let _ = self.callback.call1(&JsValue::NULL, &event.to_js());
+    }
+}
+
+fn position_to_js(position: Position) -> JsValue {
+    let object = Object::new();
+    set_fields(
+        &object,
+        vec![
+            ("x", (position.x as f64).into()),
+            ("y", (position.y as f64).into()),
+        ],
+    );
+
+    object.into()
+}
+
+fn set_fields(object: &Object, fields: Vec<(&str, JsValue)>) {
+    for (name, value) in fields {
+        let _ = Reflect::set(object, &JsValue::from_str(name), &value);
+    }
+}
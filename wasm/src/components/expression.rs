@@ -0,0 +1,346 @@
+/// How many terms (literals, variables, negations and parenthesized
+/// sub-expressions) a single expression may have.
+pub const EXPRESSION_SIZE_LIMIT: usize = 128;
+
+/// An integer-valued argument to an instruction, which may either be given
+/// outright or be computed when the instruction runs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(i64),
+
+    /// A named value (e.g. a procedure parameter, or a `LET` binding), written as `$NAME`.
+    Variable(String),
+
+    Negate(Box<Expr>),
+    Binary {
+        operator: Operator,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl Expr {
+    /// Translates a plaintext expression (e.g. `3`, `$N`, `($X + 1) * 2`) to an `Expr`.
+    ///
+    /// Expressions support integer literals, variables, `+`, `-`, `*`, `/`, `%`
+    /// (with the usual precedence), unary minus, and parentheses.
+    ///
+    /// Expressions are limited to `EXPRESSION_SIZE_LIMIT` terms, so that ones
+    /// nested deeply enough to overflow the stack (when parsed, computed or
+    /// dropped) are rejected instead.
+    pub fn parse(plaintext: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            text: plaintext,
+            offset: 0,
+            terms: 0,
+        };
+
+        let expr = parser.parse_sum()?;
+        parser.skip_whitespace();
+
+        match parser.offset == plaintext.len() {
+            true => Ok(expr),
+            false => Err(format!("Expression {} is invalid.", plaintext.trim())),
+        }
+    }
+
+    /// Computes the value of this expression, using `lookup` to resolve any variables.
+    ///
+    /// Arithmetic is checked, so overflows and divisions by zero are reported as errors.
+    pub fn evaluate<F>(&self, lookup: F) -> Result<i64, String>
+    where
+        F: Fn(&str) -> Option<i64>,
+    {
+        self.evaluate_with(&lookup)
+    }
+
+    fn evaluate_with<F>(&self, lookup: &F) -> Result<i64, String>
+    where
+        F: Fn(&str) -> Option<i64>,
+    {
+        match self {
+            Expr::Literal(value) => Ok(*value),
+            Expr::Variable(name) => {
+                lookup(name).ok_or_else(|| format!("Variable ${} is not defined.", name))
+            }
+
+            Expr::Negate(operand) => operand
+                .evaluate_with(lookup)?
+                .checked_neg()
+                .ok_or_else(|| "Arithmetic overflow while evaluating expression.".to_string()),
+
+            Expr::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let left = left.evaluate_with(lookup)?;
+                let right = right.evaluate_with(lookup)?;
+
+                if right == 0 && matches!(operator, Operator::Divide | Operator::Remainder) {
+                    return Err("Division by zero while evaluating expression.".to_string());
+                }
+
+                let result = match operator {
+                    Operator::Add => left.checked_add(right),
+                    Operator::Subtract => left.checked_sub(right),
+                    Operator::Multiply => left.checked_mul(right),
+                    Operator::Divide => left.checked_div(right),
+                    Operator::Remainder => left.checked_rem(right),
+                };
+
+                result.ok_or_else(|| "Arithmetic overflow while evaluating expression.".to_string())
+            }
+        }
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Self {
+        Expr::Literal(value)
+    }
+}
+
+/// Is `text` usable as a name (e.g. for variables and procedures)?
+///
+/// Names start with a letter or an underscore, followed by any number of
+/// letters, digits, or underscores.
+pub fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+
+    match characters.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
+        }
+
+        _ => false,
+    }
+}
+
+// :: ---
+
+/// A simple recursive descent parser over the text of an expression.
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+    terms: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// `sum := product (('+' | '-') product)*`
+    fn parse_sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_product()?;
+
+        loop {
+            let operator = match self.peek() {
+                Some('+') => Operator::Add,
+                Some('-') => Operator::Subtract,
+                _ => return Ok(expr),
+            };
+
+            self.offset += 1;
+            expr = Expr::Binary {
+                operator,
+                left: Box::new(expr),
+                right: Box::new(self.parse_product()?),
+            };
+        }
+    }
+
+    /// `product := factor (('*' | '/' | '%') factor)*`
+    fn parse_product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_factor()?;
+
+        loop {
+            let operator = match self.peek() {
+                Some('*') => Operator::Multiply,
+                Some('/') => Operator::Divide,
+                Some('%') => Operator::Remainder,
+                _ => return Ok(expr),
+            };
+
+            self.offset += 1;
+            expr = Expr::Binary {
+                operator,
+                left: Box::new(expr),
+                right: Box::new(self.parse_factor()?),
+            };
+        }
+    }
+
+    /// `factor := '-' factor | '(' sum ')' | integer | '$' name`
+    fn parse_factor(&mut self) -> Result<Expr, String> {
+        self.terms += 1;
+        if self.terms > EXPRESSION_SIZE_LIMIT {
+            return Err(format!(
+                "Expression is too long; expressions may have at most {} terms.",
+                EXPRESSION_SIZE_LIMIT
+            ));
+        }
+
+        match self.peek() {
+            Some('-') => {
+                self.offset += 1;
+
+                // :: Fold negated literals right away, so that e.g. `-1` is
+                //    still just a literal.
+                match self.parse_factor()? {
+                    Expr::Literal(value) => Ok(Expr::Literal(-value)),
+                    operand => Ok(Expr::Negate(Box::new(operand))),
+                }
+            }
+
+            Some('(') => {
+                self.offset += 1;
+                let expr = self.parse_sum()?;
+
+                match self.peek() {
+                    Some(')') => {
+                        self.offset += 1;
+                        Ok(expr)
+                    }
+
+                    _ => Err(format!("Expression {} is missing a `)`.", self.text.trim())),
+                }
+            }
+
+            Some('$') => {
+                self.offset += 1;
+                let name = self
+                    .take_while(|character| character.is_ascii_alphanumeric() || character == '_');
+
+                match is_identifier(name) {
+                    true => Ok(Expr::Variable(name.to_uppercase())),
+                    false => Err(format!("Variable name ${} is invalid.", name)),
+                }
+            }
+
+            Some(character) if character.is_ascii_digit() => {
+                let digits = self.take_while(|character| character.is_ascii_digit());
+
+                digits
+                    .parse::<i64>()
+                    .map(Expr::Literal)
+                    .map_err(|_| format!("Value {} is not a valid integer.", digits))
+            }
+
+            _ => Err(format!("Expression {} is invalid.", self.text.trim())),
+        }
+    }
+
+    /// The next non-whitespace character, if any (which is not consumed).
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.offset..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let remaining = &self.text[self.offset..];
+        self.offset += remaining.len() - remaining.trim_start().len();
+    }
+
+    fn take_while<P>(&mut self, predicate: P) -> &'a str
+    where
+        P: Fn(char) -> bool,
+    {
+        let remaining = &self.text[self.offset..];
+        let length = remaining
+            .find(|character| !predicate(character))
+            .unwrap_or(remaining.len());
+
+        self.offset += length;
+        &remaining[..length]
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(plaintext: &str) -> Result<i64, String> {
+        let lookup = |name: &str| match name {
+            "X" => Some(3),
+            "Y" => Some(-2),
+            _ => None,
+        };
+
+        Expr::parse(plaintext)?.evaluate(lookup)
+    }
+
+    #[test]
+    fn literals_are_parsed_correctly() {
+        assert_eq!(Expr::parse("3"), Ok(Expr::Literal(3)));
+        assert_eq!(Expr::parse(" -12 "), Ok(Expr::Literal(-12)));
+    }
+
+    #[test]
+    fn variables_are_parsed_correctly() {
+        assert_eq!(Expr::parse("$N"), Ok(Expr::Variable("N".to_string())));
+        assert_eq!(
+            Expr::parse("$side_length"),
+            Ok(Expr::Variable("SIDE_LENGTH".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("three").is_err());
+        assert!(Expr::parse("$").is_err());
+        assert!(Expr::parse("$1ST").is_err());
+        assert!(Expr::parse("N").is_err());
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("(1 + 2").is_err());
+        assert!(Expr::parse("1 2").is_err());
+        assert!(Expr::parse("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn arithmetic_follows_the_usual_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+        assert_eq!(evaluate("(1 + 2) * 3"), Ok(9));
+        assert_eq!(evaluate("10 - 4 - 3"), Ok(3));
+        assert_eq!(evaluate("7 / 2"), Ok(3));
+        assert_eq!(evaluate("7 % 4"), Ok(3));
+        assert_eq!(evaluate("-(2 + 3)"), Ok(-5));
+        assert_eq!(evaluate("$x+1"), Ok(4));
+        assert_eq!(evaluate("$X * $Y"), Ok(-6));
+        assert_eq!(evaluate("--$y"), Ok(-2));
+        assert_eq!(evaluate("99999999999 * 2"), Ok(199_999_999_998));
+    }
+
+    #[test]
+    fn invalid_arithmetic_is_reported() {
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("1 % ($x - 3)").is_err());
+        assert!(evaluate("9223372036854775807 + 1").is_err());
+        assert!(evaluate("-9223372036854775807 - 2").is_err());
+        assert!(evaluate("$Z").is_err());
+    }
+
+    #[test]
+    fn overly_long_expressions_are_rejected() {
+        let depth = EXPRESSION_SIZE_LIMIT * 100;
+        let nested = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let negated = format!("{}1", "-".repeat(depth));
+        let chained = vec!["1"; depth].join(" + ");
+
+        assert!(Expr::parse(&nested).is_err());
+        assert!(Expr::parse(&negated).is_err());
+        assert!(Expr::parse(&chained).is_err());
+
+        let nested = format!("{}1{}", "(".repeat(50), ")".repeat(50));
+        assert_eq!(evaluate(&nested), Ok(1));
+    }
+}
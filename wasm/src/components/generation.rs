@@ -0,0 +1,565 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::common::*;
+
+// :: ---
+
+/// A small, seedable pseudo-random number generator (SplitMix64).
+///
+/// We roll our own rather than pull in a crate, so that a given seed produces
+/// exactly the same layout everywhere (native and `wasm32` builds alike), and
+/// keeps doing so across dependency upgrades.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound` (which must be non-zero).
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// A number in `low..=high`.
+    pub fn between(&mut self, low: i32, high: i32) -> i32 {
+        low + self.below((high - low + 1) as usize) as i32
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            items.swap(index, self.below(index + 1));
+        }
+    }
+}
+
+/// The most cells a generated board may have, so that generating one takes
+/// neither unbounded time nor unbounded memory (e.g. on the wasm main thread).
+pub const GRID_SIZE_LIMIT: usize = 1 << 18;
+
+/// How many cells `Grid::stays_connected_without` searches, at most, before
+/// giving up.
+const SEARCH_LIMIT: usize = 1024;
+
+/// Which cells of a (width x height) board are blocked.
+pub struct Grid {
+    width: Coordinate,
+    height: Coordinate,
+    blocked: Vec<bool>,
+}
+
+impl Grid {
+    /// A board with every cell blocked (or free); or an error, if it would have
+    /// more than `GRID_SIZE_LIMIT` cells.
+    pub fn new(width: i32, height: i32, blocked: bool) -> Result<Self, String> {
+        let cells = (width.max(0) as usize)
+            .checked_mul(height.max(0) as usize)
+            .filter(|cells| *cells <= GRID_SIZE_LIMIT)
+            .ok_or_else(|| {
+                format!(
+                    "Tabletop is too large to generate a layout for; at most {} positions are supported.",
+                    GRID_SIZE_LIMIT
+                )
+            })?;
+
+        Ok(Grid {
+            width: width.into(),
+            height: height.into(),
+            blocked: vec![blocked; cells],
+        })
+    }
+
+    pub fn is_blocked(&self, position: Position) -> bool {
+        self.blocked[self.index(position)]
+    }
+
+    pub fn set_blocked(&mut self, position: Position, blocked: bool) {
+        let index = self.index(position);
+        self.blocked[index] = blocked;
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        position.x >= 0 && position.y >= 0 && position.x < self.width && position.y < self.height
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> {
+        let (width, height) = (self.width, self.height);
+        (0..height).flat_map(move |y| (0..width).map(move |x| Position { x, y }))
+    }
+
+    pub fn obstacles(&self) -> Vec<Position> {
+        self.positions()
+            .filter(|position| self.is_blocked(*position))
+            .collect()
+    }
+
+    /// Can every free cell be reached from every other free cell?
+    pub fn is_connected(&self) -> bool {
+        let free_cells = self.blocked.iter().filter(|blocked| !**blocked).count();
+
+        match self
+            .positions()
+            .find(|position| !self.is_blocked(*position))
+        {
+            Some(start) => self.reachable_from(start) == free_cells,
+            None => true,
+        }
+    }
+
+    /// Would the free space stay connected with `cell` (which is free) blocked,
+    /// given that it is connected as it is?
+    ///
+    /// It does, as long as the free cells next to `cell` can still reach each
+    /// other. That is usually settled by the 8 cells around `cell` alone; only
+    /// otherwise is the board searched, and only until they have all been found.
+    ///
+    /// Searches that go past `SEARCH_LIMIT` cells are given up on, with `cell`
+    /// taken to be needed after all; so the answer is only ever wrong in the
+    /// safe direction (and never on boards smaller than that).
+    pub fn stays_connected_without(&self, cell: Position) -> bool {
+        // :: Clockwise from north; orthogonal neighbours are at even indices.
+        const RING: [(Coordinate, Coordinate); 8] = [
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, -1),
+            (0, -1),
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+        ];
+
+        let mut ring = [false; RING.len()];
+        for (free, (dx, dy)) in ring.iter_mut().zip(RING.iter()) {
+            let position = Position {
+                x: cell.x + dx,
+                y: cell.y + dy,
+            };
+
+            *free = self.contains(position) && !self.is_blocked(position);
+        }
+
+        // :: Consecutive cells of the ring are next to each other, so each run of
+        //    free ones is connected; count the runs holding a free neighbour.
+        let mut groups = 0;
+        for start in 0..RING.len() {
+            if !ring[start] || ring[(start + RING.len() - 1) % RING.len()] {
+                continue;
+            }
+
+            let run = (start..start + RING.len())
+                .map(|index| index % RING.len())
+                .take_while(|index| ring[*index]);
+
+            if run.clone().any(|index| index % 2 == 0) {
+                groups += 1;
+            }
+        }
+
+        if groups <= 1 {
+            return true;
+        }
+
+        // :: ---
+
+        // :: Otherwise, search from each neighbour in turn, a cell at a time,
+        //    noting whose search reaches which cell. Searches that run into
+        //    each other are merged; and a search that runs out of cells first
+        //    has been cut off. Either way, the searches only go as far as the
+        //    smaller side of any cut.
+        let mut searched_by = HashMap::new();
+        let mut queues: [VecDeque<Position>; DIRECTIONS.len()] = Default::default();
+        let mut merged_into = [0, 1, 2, 3];
+        let mut searches = 0;
+
+        let neighbours = DIRECTIONS
+            .iter()
+            .filter_map(|orientation| cell.neighbour(*orientation))
+            .filter(|next| self.contains(*next) && !self.is_blocked(*next));
+
+        for neighbour in neighbours {
+            searched_by.insert(neighbour, searches);
+            queues[searches].push_back(neighbour);
+            searches += 1;
+        }
+
+        let root = |merged_into: &[usize], mut search: usize| {
+            while merged_into[search] != search {
+                search = merged_into[search];
+            }
+            search
+        };
+
+        while searched_by.len() <= SEARCH_LIMIT {
+            for (search, queue) in queues.iter_mut().enumerate() {
+                let position = match queue.pop_front() {
+                    Some(position) => position,
+                    None => continue,
+                };
+
+                for orientation in DIRECTIONS.iter() {
+                    let next = match position.neighbour(*orientation) {
+                        Some(next) if next != cell && self.contains(next) => next,
+                        _ => continue,
+                    };
+
+                    if self.is_blocked(next) {
+                        continue;
+                    }
+
+                    match searched_by.get(&next) {
+                        Some(&other) => {
+                            let (a, b) = (root(&merged_into, search), root(&merged_into, other));
+                            merged_into[a] = b;
+                        }
+
+                        None => {
+                            searched_by.insert(next, search);
+                            queue.push_back(next);
+                        }
+                    }
+                }
+            }
+
+            let mut roots = (0..searches).filter(|search| root(&merged_into, *search) == *search);
+
+            if roots.clone().count() == 1 {
+                return true;
+            }
+
+            let cut_off = roots.any(|group| {
+                (0..searches)
+                    .filter(|search| root(&merged_into, *search) == group)
+                    .all(|search| queues[search].is_empty())
+            });
+
+            if cut_off {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// How many free cells can be reached from `start` (inclusive).
+    fn reachable_from(&self, start: Position) -> usize {
+        let mut seen = vec![false; self.blocked.len()];
+        let mut queue = VecDeque::new();
+
+        seen[self.index(start)] = true;
+        queue.push_back(start);
+
+        let mut count = 0;
+        while let Some(position) = queue.pop_front() {
+            count += 1;
+
+            for orientation in DIRECTIONS.iter() {
+                if let Some(next) = position.neighbour(*orientation) {
+                    if self.contains(next) && !self.is_blocked(next) && !seen[self.index(next)] {
+                        seen[self.index(next)] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        count
+    }
+
+    fn index(&self, position: Position) -> usize {
+        (position.y * self.width + position.x) as usize
+    }
+}
+
+const DIRECTIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+// :: ---
+
+/// Scatters obstacles over roughly `density` (0 to 1) of the board, without ever
+/// cutting the free space in two.
+///
+/// Cells are considered in a random order, and each one is only blocked if the
+/// rest of the free space stays connected; so on very dense settings, fewer
+/// obstacles than asked for may end up being placed.
+pub fn random_obstacles(width: i32, height: i32, density: f64, seed: u64) -> Result<Grid, String> {
+    let mut rng = Rng::new(seed);
+    let mut grid = Grid::new(width, height, false)?;
+
+    let mut cells = grid.positions().collect::<Vec<Position>>();
+    rng.shuffle(&mut cells);
+
+    // :: At least one cell is always left free.
+    let target = ((cells.len() as f64 * density) as usize).min(cells.len() - 1);
+    let mut placed = 0;
+
+    for cell in cells {
+        if placed == target {
+            break;
+        }
+
+        if grid.stays_connected_without(cell) {
+            grid.set_blocked(cell, true);
+            placed += 1;
+        }
+    }
+
+    Ok(grid)
+}
+
+/// Carves a perfect maze (i.e. one with exactly one path between any two free
+/// cells) by recursive backtracking.
+///
+/// Free cells sit on even coordinates, with the walls between them on odd ones;
+/// on boards with an even width (or height), the last column (or row) is all wall.
+pub fn maze(width: i32, height: i32, seed: u64) -> Result<Grid, String> {
+    let mut rng = Rng::new(seed);
+    let mut grid = Grid::new(width, height, true)?;
+
+    let start = Position { x: 0, y: 0 };
+    grid.set_blocked(start, false);
+
+    // :: An explicit stack, rather than actual recursion, so that large mazes
+    //    don't overflow the (rather small, on wasm) call stack.
+    let mut stack = vec![start];
+    while let Some(&current) = stack.last() {
+        let mut unvisited = DIRECTIONS
+            .iter()
+            .filter_map(|orientation| {
+                let wall = current.neighbour(*orientation)?;
+                Some((wall, wall.neighbour(*orientation)?))
+            })
+            .filter(|(_, cell)| grid.contains(*cell) && grid.is_blocked(*cell))
+            .collect::<Vec<(Position, Position)>>();
+
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let (wall, cell) = unvisited.swap_remove(rng.below(unvisited.len()));
+        grid.set_blocked(wall, false);
+        grid.set_blocked(cell, false);
+        stack.push(cell);
+    }
+
+    Ok(grid)
+}
+
+/// Lays out a handful of rectangular rooms, joined up in a chain by L-shaped
+/// corridors; everything else is blocked.
+pub fn rooms(width: i32, height: i32, seed: u64) -> Result<Grid, String> {
+    let mut rng = Rng::new(seed);
+    let mut grid = Grid::new(width, height, true)?;
+
+    let max_width = (width / 3).max(1);
+    let max_height = (height / 3).max(1);
+    let room_count = (grid.blocked.len() / 40).clamp(1, 12) as i32;
+
+    // :: Rooms are (x, y, width, height); overlapping placements are retried
+    //    a limited number of times before giving up on them.
+    let mut placed: Vec<(i32, i32, i32, i32)> = vec![];
+    for _ in 0..room_count * 10 {
+        if placed.len() as i32 == room_count {
+            break;
+        }
+
+        let room_width = rng.between(2.min(max_width), max_width);
+        let room_height = rng.between(2.min(max_height), max_height);
+        let x = rng.between(0, width - room_width);
+        let y = rng.between(0, height - room_height);
+
+        let overlaps = placed
+            .iter()
+            .any(|&(other_x, other_y, other_width, other_height)| {
+                x <= other_x + other_width
+                    && other_x <= x + room_width
+                    && y <= other_y + other_height
+                    && other_y <= y + room_height
+            });
+
+        if !overlaps || placed.is_empty() {
+            placed.push((x, y, room_width, room_height));
+        }
+    }
+
+    for &(x, y, room_width, room_height) in placed.iter() {
+        for room_y in y..y + room_height {
+            for room_x in x..x + room_width {
+                grid.set_blocked(
+                    Position {
+                        x: room_x.into(),
+                        y: room_y.into(),
+                    },
+                    false,
+                );
+            }
+        }
+    }
+
+    // :: Join every room to the one placed before it, going either
+    //    horizontally-then-vertically, or the other way around.
+    let centre = |&(x, y, room_width, room_height): &(i32, i32, i32, i32)| Position {
+        x: (x + room_width / 2).into(),
+        y: (y + room_height / 2).into(),
+    };
+
+    for pair in placed.windows(2) {
+        let (from, to) = (centre(&pair[0]), centre(&pair[1]));
+        let corner = match rng.below(2) {
+            0 => Position { x: to.x, y: from.y },
+            _ => Position { x: from.x, y: to.y },
+        };
+
+        carve_line(&mut grid, from, corner);
+        carve_line(&mut grid, corner, to);
+    }
+
+    Ok(grid)
+}
+
+/// Frees every cell on the straight (horizontal or vertical) line between `from` and `to`.
+fn carve_line(grid: &mut Grid, from: Position, to: Position) {
+    let (dx, dy) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+    let mut position = from;
+
+    grid.set_blocked(position, false);
+    while position != to {
+        position = Position {
+            x: position.x + dx,
+            y: position.y + dy,
+        };
+
+        grid.set_blocked(position, false);
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_deterministic_for_a_seed() {
+        let (mut a, mut b, mut c) = (Rng::new(42), Rng::new(42), Rng::new(43));
+        let sequence = (0..8).map(|_| a.next_u64()).collect::<Vec<u64>>();
+
+        assert_eq!(sequence, (0..8).map(|_| b.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(sequence, (0..8).map(|_| c.next_u64()).collect::<Vec<u64>>());
+
+        // :: Pinned, so that any change to the generator (which would silently
+        //    change every generated layout) is caught.
+        assert_eq!(Rng::new(0).next_u64(), 0xE220_A839_7B1D_CDAF);
+    }
+
+    #[test]
+    fn random_obstacles_keep_free_space_connected() {
+        for seed in 0..20 {
+            let grid = random_obstacles(12, 9, 0.3, seed).unwrap();
+
+            assert!(grid.is_connected());
+            assert_eq!(grid.obstacles().len(), (12.0 * 9.0 * 0.3) as usize);
+        }
+
+        // :: Even when asked for more than can fit.
+        let grid = random_obstacles(6, 6, 0.95, 7).unwrap();
+        assert!(grid.is_connected());
+        assert!(grid.obstacles().len() < 36);
+    }
+
+    #[test]
+    fn mazes_are_perfect() {
+        for seed in 0..10 {
+            let grid = maze(11, 9, seed).unwrap();
+            let free_cells = grid
+                .positions()
+                .filter(|position| !grid.is_blocked(*position))
+                .collect::<Vec<Position>>();
+
+            // :: A connected graph with exactly (nodes - 1) edges is a tree,
+            //    i.e. there's exactly one path between any two cells.
+            let passages = free_cells
+                .iter()
+                .flat_map(|position| {
+                    [Orientation::North, Orientation::East]
+                        .iter()
+                        .map(move |orientation| position.neighbour(*orientation))
+                        .collect::<Option<Vec<Position>>>()
+                        .unwrap()
+                })
+                .filter(|next| grid.contains(*next) && !grid.is_blocked(*next))
+                .count();
+
+            assert!(grid.is_connected());
+            assert_eq!(passages, free_cells.len() - 1);
+            assert_eq!(free_cells.len(), 6 * 5 + (6 * 5 - 1));
+        }
+    }
+
+    #[test]
+    fn rooms_are_connected() {
+        for seed in 0..20 {
+            let grid = rooms(24, 16, seed).unwrap();
+
+            assert!(grid.is_connected());
+            assert!(grid.obstacles().len() < 24 * 16);
+        }
+
+        assert!(rooms(1, 1, 0).unwrap().is_connected());
+    }
+
+    #[test]
+    fn blocking_cells_keeps_free_space_connected() {
+        // :: . # .
+        //    . x .    Blocking x would cut the left column off from the right.
+        //    . # .
+        let mut grid = Grid::new(3, 3, false).unwrap();
+        grid.set_blocked(Position { x: 1, y: 2 }, true);
+
+        assert!(grid.stays_connected_without(Position { x: 1, y: 1 }));
+        assert!(grid.stays_connected_without(Position { x: 0, y: 0 }));
+
+        grid.set_blocked(Position { x: 1, y: 0 }, true);
+        assert!(!grid.stays_connected_without(Position { x: 1, y: 1 }));
+
+        // :: Here, the cells either side of (2, 2) can only reach each other
+        //    the long way round, along the top (or bottom) row.
+        let mut grid = Grid::new(5, 5, false).unwrap();
+        for x in 1..4 {
+            grid.set_blocked(Position { x, y: 3 }, true);
+            grid.set_blocked(Position { x, y: 1 }, true);
+        }
+
+        assert!(grid.stays_connected_without(Position { x: 2, y: 2 }));
+        grid.set_blocked(Position { x: 4, y: 4 }, true);
+        grid.set_blocked(Position { x: 4, y: 0 }, true);
+        assert!(!grid.stays_connected_without(Position { x: 2, y: 2 }));
+    }
+
+    #[test]
+    fn oversized_grids_are_rejected() {
+        assert!(Grid::new(65536, 65536, false).is_err());
+        assert!(Grid::new(i32::MAX, i32::MAX, true).is_err());
+        assert!(maze(65536, 65536, 0).is_err());
+        assert!(rooms(65536, 65536, 0).is_err());
+        assert!(random_obstacles(65536, 65536, 0.5, 0).is_err());
+        assert!(Grid::new(513, 512, false).is_err());
+        assert!(Grid::new(512, 512, false).is_ok());
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+// :: ---
+
+/// A minimal encoder for animated GIFs, with a fixed palette of 8 colours.
+///
+/// We roll our own rather than pull in a crate, for the same reasons as with
+/// `generation::Rng`: the format is small and stable, and this keeps the module
+/// free of dependencies on both native and `wasm32` builds.
+///
+/// Frames are encoded as soon as they are added, so only the encoded output
+/// (rather than every frame) is held in memory.
+pub struct GifEncoder {
+    bytes: Vec<u8>,
+    width: u16,
+    height: u16,
+    delay: u16,
+}
+
+/// An RGB colour.
+pub type Colour = [u8; 3];
+
+/// Every pixel is an index into the palette, so it must be below this.
+pub const PALETTE_SIZE: usize = 8;
+
+/// The number of bits needed to index into the palette.
+const PALETTE_BITS: u8 = 3;
+
+impl GifEncoder {
+    /// Starts an animation of (width x height) pixel frames, each shown for
+    /// `delay` hundredths of a second, that loops forever.
+    pub fn new(width: u16, height: u16, palette: &[Colour; PALETTE_SIZE], delay: u16) -> Self {
+        let mut bytes = b"GIF89a".to_vec();
+
+        // :: Logical screen descriptor: a global colour table of 2^3 entries,
+        //    with the first colour as the background.
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&[0xF0 | (PALETTE_BITS - 1), 0, 0]);
+
+        for colour in palette.iter() {
+            bytes.extend_from_slice(colour);
+        }
+
+        // :: The (de facto standard) extension that makes the animation loop.
+        bytes.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        bytes.extend_from_slice(b"NETSCAPE2.0");
+        bytes.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+        GifEncoder {
+            bytes,
+            width,
+            height,
+            delay,
+        }
+    }
+
+    /// Appends a frame, given as palette indices, row by row from the top.
+    pub fn add_frame(&mut self, pixels: &[u8]) {
+        debug_assert_eq!(pixels.len(), self.width as usize * self.height as usize);
+
+        // :: Graphic control extension: the frame's delay, and leaving the
+        //    frame in place when moving on to the next one.
+        self.bytes.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04]);
+        self.bytes.extend_from_slice(&self.delay.to_le_bytes());
+        self.bytes.extend_from_slice(&[0x00, 0x00]);
+
+        // :: Image descriptor: the frame covers the whole screen.
+        self.bytes.extend_from_slice(&[0x2C, 0, 0, 0, 0]);
+        self.bytes.extend_from_slice(&self.width.to_le_bytes());
+        self.bytes.extend_from_slice(&self.height.to_le_bytes());
+        self.bytes.push(0x00);
+
+        // :: Image data, in sub-blocks of at most 255 bytes.
+        self.bytes.push(PALETTE_BITS);
+        for block in compress(pixels, PALETTE_BITS).chunks(255) {
+            self.bytes.push(block.len() as u8);
+            self.bytes.extend_from_slice(block);
+        }
+
+        self.bytes.push(0x00);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0x3B);
+        self.bytes
+    }
+}
+
+// :: ---
+
+/// The largest code LZW (as used by GIF) is allowed to emit.
+const MAX_CODE: u16 = 4095;
+
+/// Compresses palette indices using GIF's variant of LZW.
+fn compress(pixels: &[u8], minimum_code_size: u8) -> Vec<u8> {
+    let clear_code = 1u16 << minimum_code_size;
+    let end_code = clear_code + 1;
+
+    let mut output = BitWriter::default();
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = minimum_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    output.write(clear_code, code_size);
+
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(&pixel) => pixel as u16,
+        None => {
+            output.write(end_code, code_size);
+            return output.finish();
+        }
+    };
+
+    for &pixel in pixels {
+        if let Some(&code) = dictionary.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        output.write(prefix, code_size);
+
+        // :: Once the dictionary is full, start over rather than keep using it;
+        //    otherwise, the decoder widens its codes as soon as they no longer
+        //    fit in the current code size, and so must we.
+        match next_code <= MAX_CODE {
+            true => {
+                dictionary.insert((prefix, pixel), next_code);
+                next_code += 1;
+
+                if next_code - 1 == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+
+            false => {
+                output.write(clear_code, code_size);
+                dictionary.clear();
+                code_size = minimum_code_size + 1;
+                next_code = end_code + 1;
+            }
+        }
+
+        prefix = pixel as u16;
+    }
+
+    output.write(prefix, code_size);
+    output.write(end_code, code_size);
+    output.finish()
+}
+
+/// Packs variable-width codes into bytes, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    length: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.length;
+        self.length += size;
+
+        while self.length >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.length -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.length > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A straightforward GIF-flavoured LZW decoder, to check the encoder against.
+    fn decompress(bytes: &[u8], minimum_code_size: u8) -> Vec<u8> {
+        let clear_code = 1u16 << minimum_code_size;
+        let end_code = clear_code + 1;
+
+        let mut dictionary: Vec<Vec<u8>> = vec![];
+        let mut code_size = minimum_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = vec![];
+
+        let (mut buffer, mut length, mut bytes) = (0u32, 0u8, bytes.iter());
+        loop {
+            while length < code_size {
+                buffer |= (*bytes.next().expect("Data ended early.") as u32) << length;
+                length += 8;
+            }
+
+            let code = (buffer & ((1 << code_size) - 1)) as u16;
+            buffer >>= code_size;
+            length -= code_size;
+
+            if code == clear_code {
+                dictionary = (0..clear_code).map(|index| vec![index as u8]).collect();
+                dictionary.push(vec![]);
+                dictionary.push(vec![]);
+                code_size = minimum_code_size + 1;
+                previous = None;
+                continue;
+            }
+
+            if code == end_code {
+                return output;
+            }
+
+            let entry = match (dictionary.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                }
+
+                (None, None) => panic!("Code {} is not in the dictionary.", code),
+            };
+
+            if let Some(mut previous) = previous {
+                if dictionary.len() <= MAX_CODE as usize {
+                    previous.push(entry[0]);
+                    dictionary.push(previous);
+                }
+            }
+
+            if dictionary.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn pixels_survive_compression() {
+        let cases = vec![
+            vec![],
+            vec![5],
+            vec![0; 1000],
+            (0..10_000).map(|index| (index % 7) as u8).collect(),
+            // :: Enough variety to fill the dictionary (and clear it) several times.
+            (0..60_000u32)
+                .map(|index| (index.wrapping_mul(2_654_435_761) >> 29) as u8)
+                .collect::<Vec<u8>>(),
+        ];
+
+        for pixels in cases {
+            assert_eq!(decompress(&compress(&pixels, 3), 3), pixels);
+        }
+    }
+
+    #[test]
+    fn animations_are_framed_correctly() {
+        let palette = [[0, 0, 0]; PALETTE_SIZE];
+        let mut encoder = GifEncoder::new(3, 2, &palette, 50);
+        encoder.add_frame(&[0, 1, 2, 3, 4, 5]);
+        encoder.add_frame(&[7; 6]);
+
+        let bytes = encoder.finish();
+
+        assert!(bytes.starts_with(b"GIF89a\x03\x00\x02\x00\xF2"));
+        assert_eq!(bytes.last(), Some(&0x3B));
+        assert_eq!(
+            bytes
+                .windows(4)
+                .filter(|window| window == &[0x21, 0xF9, 0x04, 0x04])
+                .count(),
+            2
+        );
+    }
+}
@@ -1,100 +1,376 @@
-use std::iter::Inspect;
-
+use super::command::{self, Arguments, CommandRegistry};
 use super::common::*;
+use super::expression::{is_identifier, Expr};
+use super::script;
 
 // :: ---
 
-#[derive(Debug, PartialEq)]
+/// Words that have a meaning of their own in the command language, and so
+/// cannot be used to name procedures.
+pub const KEYWORDS: &[&str] = &[
+    "PLACE", "OBSTACLE", "LEFT", "RIGHT", "MOVE", "REPORT", "REPEAT", "IF", "THEN", "ELSE",
+    "WHILE", "END", "DEFINE", "CALL", "LET", "BACK", "STRAFE", "UTURN", "FACE", "CHARGER", "SWEEP",
+    "EXPECT",
+];
+
+/// Variables that reflect the live state of a Robot and its Tabletop,
+/// and so cannot be assigned to.
+pub const BUILT_IN_VARIABLES: &[&str] = &["ROBOT_X", "ROBOT_Y", "WIDTH", "HEIGHT"];
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Instruction {
     Place {
-        x: i32,
-        y: i32,
+        x: Expr,
+        y: Expr,
         orientation: Orientation,
     },
 
     Obstacle {
-        x: i32,
-        y: i32,
+        x: Expr,
+        y: Expr,
+    },
+
+    /// Adds a charging station to the Tabletop.
+    Charger {
+        x: Expr,
+        y: Expr,
     },
 
     Left,
     Right,
     Move,
     Report,
+    /// Reports the Robot's running statistics (see `Stats`).
+    ReportStats,
+    /// Reports the Robot's status, along with its remaining charge.
+    ReportCharge,
+    /// Reports the Robot's status as a JSON object (see `ReportFormat::Json`).
+    ReportJson,
+    /// Reports the Robot's status as a CSV record (see `ReportFormat::Csv`).
+    ReportCsv,
+    /// Reports every position (and orientation) the Robot has been in, in order.
+    ReportTrail,
+    /// Draws the Tabletop, with the Robot and the positions it has visited.
+    ReportMap,
+
+    /// Moves back one unit, against the direction the robot is facing (without turning).
+    Back,
+    /// Moves one unit to the robot's left (without turning).
+    StrafeLeft,
+    /// Moves one unit to the robot's right (without turning).
+    StrafeRight,
+    /// Turns the robot around, to face the opposite direction.
+    UTurn,
+    /// Turns the robot to face the provided orientation.
+    Face(Orientation),
+
+    /// Covers every free position reachable on the Tabletop (see `coverage::plan`),
+    /// or if `dry_run` is set, just reports the route that would be taken.
+    Sweep {
+        dry_run: bool,
+    },
+
+    /// Checks that the Robot (or its Tabletop) is in the expected state, and is
+    /// rejected (see `Robot::expectation_failed`) if it is not.
+    Expect(Expectation),
+
+    /// Moves forward `steps` units, handling obstructions according to `policy`
+    /// (or to the Robot's own move policy, if not given).
+    MoveBy {
+        steps: Expr,
+        policy: Option<MovePolicy>,
+    },
+
+    /// Runs the instructions in `body`, in order, `times` times over.
+    Repeat {
+        times: Expr,
+        body: Vec<Instruction>,
+    },
+
+    /// Runs `then_branch` if `condition` holds, and `else_branch` otherwise.
+    If {
+        condition: Condition,
+        then_branch: Vec<Instruction>,
+        else_branch: Vec<Instruction>,
+    },
+
+    /// Runs the instructions in `body` for as long as `condition` holds.
+    While {
+        condition: Condition,
+        body: Vec<Instruction>,
+    },
+
+    /// Registers a named procedure, which can later be run via `Call`.
+    Define {
+        name: String,
+        params: Vec<String>,
+        body: Vec<Instruction>,
+    },
+
+    /// Runs a previously defined procedure, binding `args` to its parameters.
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
+
+    /// Binds the result of `value` to the variable `name`.
+    Let {
+        name: String,
+        value: Expr,
+    },
+
+    /// A command registered at runtime (see `CommandRegistry`), along with
+    /// the (uppercased) words that followed its keyword.
+    Custom {
+        keyword: String,
+        args: Vec<String>,
+    },
+}
+
+/// A predicate on the state of a Robot and its Tabletop, as used by `IF` and `WHILE`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// Can the robot move forward one unit?
+    FrontClear,
+    /// Could the robot move one unit to its left (without turning)?
+    LeftClear,
+    /// Could the robot move one unit to its right (without turning)?
+    RightClear,
+
+    At {
+        x: Expr,
+        y: Expr,
+    },
+    Facing(Orientation),
+    Placed,
+
+    Not(Box<Condition>),
+}
+
+/// What an `EXPECT` instruction asserts about a Robot and its Tabletop.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expectation {
+    /// `EXPECT x,y,F`: the robot is at (x, y), facing F.
+    At {
+        x: Expr,
+        y: Expr,
+        orientation: Orientation,
+    },
+
+    /// `EXPECT NOT_PLACED`
+    NotPlaced,
+    /// `EXPECT BLOCKED`: the robot is placed, but cannot move forward one unit.
+    Blocked,
+    /// `EXPECT OBSTACLE x,y`
+    Obstacle { x: Expr, y: Expr },
 }
 
 impl Instruction {
     /// Translates a plaintext command to an `Instruction`.
     pub fn parse(plaintext: &str) -> Result<Self, String> {
-        let normalized_text = plaintext.to_uppercase();
-        let words = normalized_text.split_whitespace().collect::<Vec<&str>>();
-
-        // :: This pattern validates any required arguments given the command,
-        //    but just drops all other unexpected additional arguments.
-        //    Will need to change approach if commands are invalidated if
-        //    provided too many arguments.
-        match words[0] {
-            "PLACE" if words.len() >= 2 => {
-                let args_fragment = words[1..].join("");
-                let args = args_fragment
-                    .split(',')
-                    .map(|fragment| fragment.trim())
-                    .collect::<Vec<&str>>();
-
-                if args.len() < 3 {
-                    return Err(format!(
-                        "Arguments for command {} were incomplete.",
-                        words[0]
-                    ));
-                }
+        command::with_built_in_commands(|commands| Instruction::parse_with(plaintext, commands))
+    }
 
-                // :: ---
+    /// Translates a plaintext command to an `Instruction`, recognizing the
+    /// commands in `commands` (rather than just the built-in ones).
+    ///
+    /// Keywords are matched case-insensitively against the text as written, so
+    /// that (other than for what the instruction itself holds, e.g. a procedure
+    /// name) parsing a command does not allocate.
+    pub fn parse_with(plaintext: &str, commands: &CommandRegistry) -> Result<Self, String> {
+        // :: Commands validate any required arguments themselves; the built-in
+        //    ones (mostly) just drop any unexpected additional arguments.
+        let (keyword, args) = match Arguments::new(plaintext).split_first() {
+            Some(split) => split,
+            None => return Err("Expected a command, but found none.".to_string()),
+        };
+
+        if let Some((keyword, command)) = commands.find(keyword) {
+            return command.parse(keyword, args);
+        }
+
+        // :: Block constructs span several statements, so these are handed off
+        //    to the script parser, which expects the block to be wrapped in
+        //    braces when given as a single line (e.g. `REPEAT 4 { MOVE; LEFT }`).
+        let constructs = ["REPEAT", "IF", "WHILE", "DEFINE"];
+        if constructs
+            .iter()
+            .any(|construct| construct.eq_ignore_ascii_case(keyword))
+        {
+            return script::parse_single(plaintext, commands);
+        }
 
-                let rx = args[0].parse::<i32>();
-                let ry = args[1].parse::<i32>();
-                let rorientation = Orientation::parse(args[2]);
+        match args.split_first() {
+            Some((name, args)) if keyword.eq_ignore_ascii_case("CALL") => parse_call(name, args),
+            Some(_) if keyword.eq_ignore_ascii_case("LET") => parse_let(args),
 
-                // :: If x, y, and orientation all parsed correctly
-                if let (Ok(x), Ok(y), Ok(orientation)) = (rx, ry, rorientation) {
-                    Ok(Instruction::Place { x, y, orientation })
-                } else {
-                    Err(format!("Arguments for command {} were invalid.", words[0]))
-                }
+            // :: Any other name is taken to be a call to a user-defined procedure;
+            //    whether such a procedure actually exists is only known when the
+            //    instruction is run.
+            _ if is_identifier(keyword) && !is_keyword(keyword) => parse_call(keyword, args),
+
+            _ => Err(format!(
+                "Command {} was not recognized or is malformed.",
+                keyword.to_uppercase()
+            )),
+        }
+    }
+
+    /// The keyword of the command (or construct) that this instruction came from.
+    pub fn keyword(&self) -> &str {
+        match self {
+            Instruction::Place { .. } => "PLACE",
+            Instruction::Obstacle { .. } => "OBSTACLE",
+            Instruction::Charger { .. } => "CHARGER",
+            Instruction::Left => "LEFT",
+            Instruction::Right => "RIGHT",
+            Instruction::Move | Instruction::MoveBy { .. } => "MOVE",
+            Instruction::Report
+            | Instruction::ReportStats
+            | Instruction::ReportCharge
+            | Instruction::ReportJson
+            | Instruction::ReportCsv
+            | Instruction::ReportTrail
+            | Instruction::ReportMap => "REPORT",
+            Instruction::Back => "BACK",
+            Instruction::StrafeLeft | Instruction::StrafeRight => "STRAFE",
+            Instruction::UTurn => "UTURN",
+            Instruction::Face(_) => "FACE",
+            Instruction::Sweep { .. } => "SWEEP",
+            Instruction::Expect(_) => "EXPECT",
+            Instruction::Repeat { .. } => "REPEAT",
+            Instruction::If { .. } => "IF",
+            Instruction::While { .. } => "WHILE",
+            Instruction::Define { .. } => "DEFINE",
+            Instruction::Call { .. } => "CALL",
+            Instruction::Let { .. } => "LET",
+            Instruction::Custom { keyword, .. } => keyword,
+        }
+    }
+}
+
+/// Is `word` (in any case) one of the `KEYWORDS`?
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS
+        .iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+fn parse_call(name: &str, args: Arguments) -> Result<Instruction, String> {
+    let name = match is_identifier(name) && !is_keyword(name) {
+        true => name.to_ascii_uppercase(),
+        false => {
+            return Err(format!(
+                "Procedure name {} is invalid.",
+                name.to_uppercase()
+            ))
+        }
+    };
+
+    let args = match args.is_empty() {
+        true => vec![],
+        false => args
+            .split(',')
+            .map(Expr::parse)
+            .collect::<Result<Vec<Expr>, String>>()
+            .map_err(|_| format!("Arguments for procedure {} were invalid.", name))?,
+    };
+
+    Ok(Instruction::Call { name, args })
+}
+
+fn parse_let(args: Arguments) -> Result<Instruction, String> {
+    let (name, value) = match args.as_str().split_once('=') {
+        Some((name, value)) => (name.trim(), value.trim()),
+        None => return Err("Arguments for command LET were incomplete.".to_string()),
+    };
+
+    let name = name.strip_prefix('$').unwrap_or(name).to_uppercase();
+    check_variable_name(&name)?;
+
+    Ok(Instruction::Let {
+        name,
+        value: Expr::parse(value)?,
+    })
+}
+
+/// Checks that `LET` may assign to `name`, which (as variable names always are)
+/// should already be uppercased.
+pub fn check_variable_name(name: &str) -> Result<(), String> {
+    if !is_identifier(name) || name != name.to_uppercase() {
+        return Err(format!("Variable name {} is invalid.", name));
+    }
+
+    if BUILT_IN_VARIABLES.contains(&name) {
+        return Err(format!("Variable ${} is read-only.", name));
+    }
+
+    Ok(())
+}
+
+impl Condition {
+    /// Translates a plaintext predicate (e.g. `FRONT_CLEAR`, `AT 3,3`) to a `Condition`.
+    pub fn parse(plaintext: &str) -> Result<Self, String> {
+        let (word, args) = match Arguments::new(plaintext).split_first() {
+            Some(split) => split,
+            None => return Err("Expected a condition, but found none.".to_string()),
+        };
+
+        let predicates = [
+            ("FRONT_CLEAR", Condition::FrontClear),
+            ("LEFT_CLEAR", Condition::LeftClear),
+            ("RIGHT_CLEAR", Condition::RightClear),
+            ("PLACED", Condition::Placed),
+        ];
+
+        for (name, condition) in predicates {
+            if name.eq_ignore_ascii_case(word) {
+                return Ok(condition);
             }
+        }
 
-            "OBSTACLE" if words.len() >= 2 => {
-                let args_fragment = words[1..].join("");
-                let args = args_fragment
-                    .split(',')
-                    .map(|fragment| fragment.trim())
-                    .collect::<Vec<&str>>();
-
-                if args.len() < 2 {
-                    return Err(format!(
-                        "Arguments for command {} were incomplete.",
-                        words[0]
-                    ));
+        // :: Double negatives cancel out, so that however many `NOT`s
+        //    there are, the condition is only ever wrapped once.
+        if word.eq_ignore_ascii_case("NOT") {
+            let mut count = 1;
+            let mut negated = args;
+            while let Some((word, rest)) = negated.split_first() {
+                if !word.eq_ignore_ascii_case("NOT") {
+                    break;
                 }
 
-                let rx = args[0].parse::<i32>();
-                let ry = args[1].parse::<i32>();
+                count += 1;
+                negated = rest;
+            }
 
-                // :: If x and y are parsed correctly
-                if let (Ok(x), Ok(y)) = (rx, ry) {
-                    Ok(Instruction::Obstacle { x, y })
-                } else {
-                    Err(format!("Arguments for command {} were invalid.", words[0]))
-                }
+            let negated = Condition::parse(negated.as_str())?;
+            return match count % 2 {
+                0 => Ok(negated),
+                _ => Ok(Condition::Not(Box::new(negated))),
+            };
+        }
+
+        match args.first() {
+            Some(orientation) if word.eq_ignore_ascii_case("FACING") => {
+                Ok(Condition::Facing(Orientation::parse(orientation)?))
             }
 
-            "MOVE" => Ok(Instruction::Move),
-            "LEFT" => Ok(Instruction::Left),
-            "RIGHT" => Ok(Instruction::Right),
-            "REPORT" => Ok(Instruction::Report),
+            Some(_) if word.eq_ignore_ascii_case("AT") => {
+                let mut args = args.split(',');
+                let (x, y) = match (args.next(), args.next()) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => return Err("Arguments for condition AT were incomplete.".to_string()),
+                };
+
+                match (Expr::parse(x), Expr::parse(y)) {
+                    (Ok(x), Ok(y)) => Ok(Condition::At { x, y }),
+                    _ => Err("Arguments for condition AT were invalid.".to_string()),
+                }
+            }
 
             _ => Err(format!(
-                "Command {} was not recognized or is malformed.",
-                words[0]
+                "Condition {} was not recognized or is malformed.",
+                word.to_uppercase()
             )),
         }
     }
@@ -113,13 +389,53 @@ mod tests {
         assert_eq!(result.unwrap(), instruction);
     }
 
+    /// Found by fuzzing (`fuzz/fuzz_targets/parse_instruction.rs`).
+    #[test]
+    fn empty_input_is_rejected_rather_than_panicking() {
+        assert!(Instruction::parse("").is_err());
+        assert!(Instruction::parse(" \t ").is_err());
+    }
+
     #[test]
     fn move_instruction_is_parsed_correctly() {
         expect_conversion("MOVE", Instruction::Move);
         expect_conversion("move", Instruction::Move);
-        expect_conversion("move 1 2 3", Instruction::Move);
-        expect_conversion("MOVE 1,3,5,7", Instruction::Move);
-        expect_conversion("move right left", Instruction::Move);
+        expect_conversion("  move  ", Instruction::Move);
+    }
+
+    #[test]
+    fn move_instruction_accepts_a_number_of_steps() {
+        expect_conversion(
+            "MOVE 3",
+            Instruction::MoveBy {
+                steps: Expr::Literal(3),
+                policy: None,
+            },
+        );
+
+        expect_conversion(
+            "move $n + 1 as-far-as-possible",
+            Instruction::MoveBy {
+                steps: Expr::parse("$N + 1").unwrap(),
+                policy: Some(MovePolicy::AsFarAsPossible),
+            },
+        );
+
+        expect_conversion(
+            "MOVE 2 ALL_OR_NOTHING",
+            Instruction::MoveBy {
+                steps: Expr::Literal(2),
+                policy: Some(MovePolicy::AllOrNothing),
+            },
+        );
+    }
+
+    #[test]
+    fn move_instruction_rejects_unexpected_arguments() {
+        assert!(Instruction::parse("move 1 2 3").is_err());
+        assert!(Instruction::parse("MOVE 1,3,5,7").is_err());
+        assert!(Instruction::parse("move right left").is_err());
+        assert!(Instruction::parse("MOVE AS_FAR_AS_POSSIBLE").is_err());
     }
 
     #[test]
@@ -144,9 +460,56 @@ mod tests {
     fn report_instruction_is_parsed_correctly() {
         expect_conversion("REPORT", Instruction::Report);
         expect_conversion("report", Instruction::Report);
-        expect_conversion("REPORT 1, 10, 2", Instruction::Report);
-        expect_conversion("report 1 10 10", Instruction::Report);
-        expect_conversion("report move place", Instruction::Report);
+        expect_conversion("REPORT STATS", Instruction::ReportStats);
+        expect_conversion("report charge", Instruction::ReportCharge);
+        expect_conversion("REPORT TRAIL", Instruction::ReportTrail);
+        expect_conversion("REPORT JSON", Instruction::ReportJson);
+        expect_conversion("report csv", Instruction::ReportCsv);
+        expect_conversion("report map", Instruction::ReportMap);
+    }
+
+    #[test]
+    fn report_instruction_rejects_unknown_formats() {
+        for plaintext in [
+            "REPORT 1, 10, 2",
+            "report 1 10 10",
+            "report move place",
+            "REPORT JOSN",
+            "REPORT {x}:{y}",
+            "REPORT foo",
+            "report stats now",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Instruction::parse(plaintext),
+                Err("Arguments for command REPORT were invalid.".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn sweep_instruction_is_parsed_correctly() {
+        expect_conversion("SWEEP", Instruction::Sweep { dry_run: false });
+        expect_conversion("sweep plan", Instruction::Sweep { dry_run: true });
+        assert!(Instruction::parse("SWEEP NOW").is_err());
+    }
+
+    #[test]
+    fn reverse_strafe_and_facing_instructions_are_parsed_correctly() {
+        expect_conversion("BACK", Instruction::Back);
+        expect_conversion("uturn", Instruction::UTurn);
+        expect_conversion("STRAFE LEFT", Instruction::StrafeLeft);
+        expect_conversion("strafe right", Instruction::StrafeRight);
+        expect_conversion("FACE south", Instruction::Face(Orientation::South));
+    }
+
+    #[test]
+    fn strafe_and_face_instructions_require_a_direction() {
+        assert!(Instruction::parse("STRAFE").is_err());
+        assert!(Instruction::parse("STRAFE UP").is_err());
+        assert!(Instruction::parse("FACE").is_err());
+        assert!(Instruction::parse("FACE LEFT").is_err());
     }
 
     #[test]
@@ -154,8 +517,8 @@ mod tests {
         expect_conversion(
             "PLACE 0,0,NORTH",
             Instruction::Place {
-                x: 0,
-                y: 0,
+                x: Expr::Literal(0),
+                y: Expr::Literal(0),
                 orientation: Orientation::North,
             },
         );
@@ -163,8 +526,8 @@ mod tests {
         expect_conversion(
             "place 2,10,EAST",
             Instruction::Place {
-                x: 2,
-                y: 10,
+                x: Expr::Literal(2),
+                y: Expr::Literal(10),
                 orientation: Orientation::East,
             },
         );
@@ -188,8 +551,20 @@ mod tests {
 
     #[test]
     fn obstacle_instruction_is_parsed_correctly() {
-        expect_conversion("OBSTACLE 2,2", Instruction::Obstacle { x: 2, y: 2 });
-        expect_conversion("obstacle 1,3", Instruction::Obstacle { x: 1, y: 3 });
+        expect_conversion(
+            "OBSTACLE 2,2",
+            Instruction::Obstacle {
+                x: Expr::Literal(2),
+                y: Expr::Literal(2),
+            },
+        );
+        expect_conversion(
+            "obstacle 1,3",
+            Instruction::Obstacle {
+                x: Expr::Literal(1),
+                y: Expr::Literal(3),
+            },
+        );
     }
 
     #[test]
@@ -208,6 +583,211 @@ mod tests {
         assert!(Instruction::parse("OBSTACLE 2,3").is_ok());
     }
 
+    #[test]
+    fn repeat_instruction_is_parsed_correctly() {
+        expect_conversion(
+            "REPEAT 4 { MOVE; LEFT }",
+            Instruction::Repeat {
+                times: Expr::Literal(4),
+                body: vec![Instruction::Move, Instruction::Left],
+            },
+        );
+
+        expect_conversion(
+            "repeat 2 { repeat 2 { move } }",
+            Instruction::Repeat {
+                times: Expr::Literal(2),
+                body: vec![Instruction::Repeat {
+                    times: Expr::Literal(2),
+                    body: vec![Instruction::Move],
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn repeat_instruction_requires_a_block() {
+        assert!(Instruction::parse("REPEAT").is_err());
+        assert!(Instruction::parse("REPEAT 4").is_err());
+        assert!(Instruction::parse("REPEAT 4 { MOVE } MOVE").is_err());
+    }
+
+    #[test]
+    fn conditional_instructions_are_parsed_correctly() {
+        expect_conversion(
+            "IF FRONT_CLEAR THEN MOVE ELSE RIGHT",
+            Instruction::If {
+                condition: Condition::FrontClear,
+                then_branch: vec![Instruction::Move],
+                else_branch: vec![Instruction::Right],
+            },
+        );
+
+        expect_conversion(
+            "while front_clear { move }",
+            Instruction::While {
+                condition: Condition::FrontClear,
+                body: vec![Instruction::Move],
+            },
+        );
+    }
+
+    #[test]
+    fn expect_instructions_are_parsed_correctly() {
+        expect_conversion(
+            "EXPECT 1,2,NORTH",
+            Instruction::Expect(Expectation::At {
+                x: Expr::Literal(1),
+                y: Expr::Literal(2),
+                orientation: Orientation::North,
+            }),
+        );
+
+        expect_conversion(
+            "expect not_placed",
+            Instruction::Expect(Expectation::NotPlaced),
+        );
+        expect_conversion("EXPECT BLOCKED", Instruction::Expect(Expectation::Blocked));
+        expect_conversion(
+            "EXPECT OBSTACLE 3, 4",
+            Instruction::Expect(Expectation::Obstacle {
+                x: Expr::Literal(3),
+                y: Expr::Literal(4),
+            }),
+        );
+
+        assert!(Instruction::parse("EXPECT").is_err());
+        assert!(Instruction::parse("EXPECT 1,2").is_err());
+        assert!(Instruction::parse("EXPECT OBSTACLE").is_err());
+        assert!(Instruction::parse("EXPECT BLOCKED NOW").is_err());
+        assert!(Instruction::parse("EXPECT 1,2,UP").is_err());
+    }
+
+    #[test]
+    fn conditions_are_parsed_correctly() {
+        assert_eq!(Condition::parse("FRONT_CLEAR"), Ok(Condition::FrontClear));
+        assert_eq!(Condition::parse("left_clear"), Ok(Condition::LeftClear));
+        assert_eq!(Condition::parse("RIGHT_CLEAR"), Ok(Condition::RightClear));
+        assert_eq!(Condition::parse("PLACED"), Ok(Condition::Placed));
+        assert_eq!(
+            Condition::parse("AT 3, 3"),
+            Ok(Condition::At {
+                x: Expr::Literal(3),
+                y: Expr::Literal(3)
+            })
+        );
+        assert_eq!(
+            Condition::parse("FACING west"),
+            Ok(Condition::Facing(Orientation::West))
+        );
+        assert_eq!(
+            Condition::parse("NOT FRONT_CLEAR"),
+            Ok(Condition::Not(Box::new(Condition::FrontClear)))
+        );
+        assert_eq!(Condition::parse("NOT NOT PLACED"), Ok(Condition::Placed));
+    }
+
+    #[test]
+    fn long_chains_of_negations_are_folded() {
+        let odd = format!("{}FRONT_CLEAR", "NOT ".repeat(100_001));
+        let even = format!("{}FRONT_CLEAR", "NOT ".repeat(100_000));
+
+        assert_eq!(
+            Condition::parse(&odd),
+            Ok(Condition::Not(Box::new(Condition::FrontClear)))
+        );
+        assert_eq!(Condition::parse(&even), Ok(Condition::FrontClear));
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        assert!(Condition::parse("").is_err());
+        assert!(Condition::parse("NOT").is_err());
+        assert!(Condition::parse("AT").is_err());
+        assert!(Condition::parse("AT 3").is_err());
+        assert!(Condition::parse("AT 3 3").is_err());
+        assert!(Condition::parse("FACING UP").is_err());
+        assert!(Condition::parse("SUNNY").is_err());
+    }
+
+    #[test]
+    fn procedure_calls_are_parsed_correctly() {
+        expect_conversion(
+            "DODGE_LEFT",
+            Instruction::Call {
+                name: "DODGE_LEFT".to_string(),
+                args: vec![],
+            },
+        );
+
+        expect_conversion(
+            "call zigzag 3, $n",
+            Instruction::Call {
+                name: "ZIGZAG".to_string(),
+                args: vec![Expr::Literal(3), Expr::Variable("N".to_string())],
+            },
+        );
+    }
+
+    #[test]
+    fn procedure_calls_require_a_valid_name() {
+        assert!(Instruction::parse("CALL").is_err());
+        assert!(Instruction::parse("CALL MOVE").is_err());
+        assert!(Instruction::parse("CALL 3RD").is_err());
+        assert!(Instruction::parse("DODGE-LEFT").is_err());
+        assert!(Instruction::parse("DODGE three").is_err());
+    }
+
+    #[test]
+    fn coordinates_accept_expressions() {
+        expect_conversion(
+            "PLACE $x+1, $Y, NORTH",
+            Instruction::Place {
+                x: Expr::parse("$X+1").unwrap(),
+                y: Expr::Variable("Y".to_string()),
+                orientation: Orientation::North,
+            },
+        );
+
+        expect_conversion(
+            "OBSTACLE $WIDTH - 1, ($HEIGHT - 1) / 2",
+            Instruction::Obstacle {
+                x: Expr::parse("$WIDTH-1").unwrap(),
+                y: Expr::parse("($HEIGHT-1)/2").unwrap(),
+            },
+        );
+
+        assert!(Instruction::parse("PLACE $x+,0,NORTH").is_err());
+    }
+
+    #[test]
+    fn let_instruction_is_parsed_correctly() {
+        expect_conversion(
+            "LET x = 2 * 3",
+            Instruction::Let {
+                name: "X".to_string(),
+                value: Expr::parse("2*3").unwrap(),
+            },
+        );
+
+        expect_conversion(
+            "let $y = $ROBOT_Y + 1",
+            Instruction::Let {
+                name: "Y".to_string(),
+                value: Expr::parse("$ROBOT_Y+1").unwrap(),
+            },
+        );
+    }
+
+    #[test]
+    fn let_instruction_requires_a_valid_assignment() {
+        assert!(Instruction::parse("LET").is_err());
+        assert!(Instruction::parse("LET x").is_err());
+        assert!(Instruction::parse("LET x =").is_err());
+        assert!(Instruction::parse("LET 2x = 1").is_err());
+        assert!(Instruction::parse("LET $WIDTH = 10").is_err());
+    }
+
     #[test]
     fn whitespace_around_arguments_are_trimmed() {
         assert!(Instruction::parse("PLACE 5   , 10     , SOUTH ").is_ok());
@@ -215,4 +795,36 @@ mod tests {
         assert!(Instruction::parse("         MOVE      ").is_ok());
         assert!(Instruction::parse("               REPORT").is_ok());
     }
+
+    #[test]
+    fn keywords_are_matched_in_any_case() {
+        expect_conversion("Report Json", Instruction::ReportJson);
+        expect_conversion("sTrAfE lEfT", Instruction::StrafeLeft);
+        expect_conversion(
+            "Move 2 All-Or-Nothing",
+            Instruction::MoveBy {
+                steps: Expr::Literal(2),
+                policy: Some(MovePolicy::AllOrNothing),
+            },
+        );
+
+        assert_eq!(
+            Condition::parse("not Facing North"),
+            Ok(Condition::Not(Box::new(Condition::Facing(
+                Orientation::North
+            ))))
+        );
+    }
+
+    #[test]
+    fn rejections_name_the_command_in_uppercase() {
+        assert_eq!(
+            Instruction::parse("1up"),
+            Err("Command 1UP was not recognized or is malformed.".to_string())
+        );
+        assert_eq!(
+            Instruction::parse("place 1,2"),
+            Err("Arguments for command PLACE were incomplete.".to_string())
+        );
+    }
 }
@@ -1,4 +1,19 @@
+pub mod builtins;
+pub mod command;
 pub mod common;
+pub mod coverage;
+pub mod events;
+pub mod expression;
+pub mod generation;
+pub mod gif;
 pub mod instruction;
+pub mod program;
+pub mod render;
+pub mod replay;
+pub mod report;
 pub mod robot;
+pub mod script;
+pub mod stats;
+pub mod stream;
 pub mod tabletop;
+pub mod trail;
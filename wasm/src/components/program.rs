@@ -0,0 +1,1054 @@
+#![allow(clippy::unused_unit)]
+
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use super::command::{self, CommandRegistry};
+use super::common::*;
+use super::expression::{Expr, Operator, EXPRESSION_SIZE_LIMIT};
+use super::instruction::{self, Condition, Expectation, Instruction};
+use super::script;
+
+// :: ---
+
+/// What every encoded program starts with.
+const MAGIC: &[u8; 4] = b"TRSP";
+
+/// The version of the binary format; programs encoded by other versions are rejected.
+const FORMAT_VERSION: u8 = 1;
+
+/// A script compiled ahead of time, so that it can be run (against any Robot, on
+/// any Tabletop) over and over again without being parsed again every time.
+///
+/// Programs are flat lists of operations: blocks are laid out in line, and the
+/// loops and branches around them are turned into jumps, whose targets are all
+/// worked out when compiling. Procedure bodies are laid out after the rest of
+/// the program, and are looked up by name when called, just as in scripts.
+///
+/// Programs can be saved as (and loaded back from) a compact binary format, see
+/// `to_bytes` and `from_bytes`.
+#[derive(Clone, Debug, PartialEq)]
+#[wasm_bindgen]
+pub struct Program {
+    code: Rc<Code>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Code {
+    pub ops: Vec<Op>,
+
+    /// What `Op::Run` runs: any instruction other than a block construct.
+    pub commands: Vec<Instruction>,
+    /// What `Op::If` and `Op::While` check.
+    pub conditions: Vec<Condition>,
+    /// How many times each `Op::Repeat` repeats its body.
+    pub counts: Vec<Expr>,
+    /// What `Op::Define` registers.
+    pub procedures: Vec<ProcedureHeader>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ProcedureHeader {
+    pub name: String,
+    pub params: Vec<String>,
+    /// Where the body of the procedure starts; it runs until an `Op::Return`.
+    pub entry: usize,
+}
+
+/// A single operation of a `Program`.
+///
+/// Every operation that stands for an instruction takes a step when it runs,
+/// as does every pass through a loop, the same way they do in scripts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Op {
+    /// Runs `commands[index]`.
+    Run(usize),
+    /// Registers `procedures[index]`.
+    Define(usize),
+    /// Just takes a step (as a `WHILE` does, before first checking its condition).
+    Step,
+
+    /// Starts a `REPEAT`, counting down from `counts[times]`.
+    Repeat {
+        times: usize,
+    },
+    /// Carries on with the next pass of the innermost `REPEAT` (taking a step),
+    /// or if there are none left, finishes it, and jumps to `exit`.
+    Next {
+        exit: usize,
+    },
+
+    /// Jumps to `otherwise` unless `conditions[condition]` holds.
+    If {
+        condition: usize,
+        otherwise: usize,
+    },
+    /// Carries on with the next pass of a `WHILE` (taking a step) if
+    /// `conditions[condition]` holds, or jumps to `exit` if it does not.
+    While {
+        condition: usize,
+        exit: usize,
+    },
+
+    Jump(usize),
+    /// Finishes the program (or the procedure being called).
+    Return,
+}
+
+#[wasm_bindgen]
+impl Program {
+    /// Translates a plaintext script (see `script::parse`) into a `Program`.
+    #[wasm_bindgen]
+    pub fn compile(script: &str) -> Result<Program, String> {
+        command::with_built_in_commands(|commands| Program::compile_with(script, commands))
+    }
+
+    /// Encodes this program in its binary format.
+    #[wasm_bindgen]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.write_code(&self.code);
+        writer.bytes
+    }
+
+    /// Decodes a program previously encoded with `to_bytes`.
+    ///
+    /// Since the bytes may come from anywhere, the program is checked to be
+    /// well-formed before it can be run.
+    #[wasm_bindgen]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, String> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let code = reader
+            .read_code()
+            .map_err(|message| format!("Program could not be loaded: {}", message))?;
+
+        code.validate()
+            .map_err(|message| format!("Program could not be loaded: {}", message))?;
+
+        Ok(Program {
+            code: Rc::new(code),
+        })
+    }
+}
+
+/// Rust-facing API.
+impl Program {
+    /// Translates a plaintext script into a `Program`, recognizing the commands
+    /// in `commands` (rather than just the built-in ones).
+    pub fn compile_with(script: &str, commands: &CommandRegistry) -> Result<Program, String> {
+        Ok(Program::from_instructions(&script::parse_with(
+            script, commands,
+        )?))
+    }
+
+    /// Compiles already translated instructions into a `Program`.
+    pub fn from_instructions(instructions: &[Instruction]) -> Program {
+        let mut compiler = Compiler::default();
+        compiler.compile_block(instructions);
+        compiler.code.ops.push(Op::Return);
+
+        // :: Procedure bodies (including those of procedures defined within other
+        //    procedures) are laid out after everything else.
+        while let Some((index, body)) = compiler.pending.pop() {
+            compiler.code.procedures[index].entry = compiler.code.ops.len();
+            compiler.compile_block(&body);
+            compiler.code.ops.push(Op::Return);
+        }
+
+        Program {
+            code: Rc::new(compiler.code),
+        }
+    }
+
+    pub(crate) fn code(&self) -> &Code {
+        &self.code
+    }
+}
+
+// :: ---
+
+#[derive(Default)]
+struct Compiler {
+    code: Code,
+    /// Procedures whose bodies are yet to be laid out.
+    pending: Vec<(usize, Vec<Instruction>)>,
+}
+
+impl Compiler {
+    fn compile_block(&mut self, instructions: &[Instruction]) {
+        for instruction in instructions {
+            self.compile(instruction);
+        }
+    }
+
+    fn compile(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Repeat { times, body } => {
+                self.code.counts.push(times.clone());
+                self.emit(Op::Repeat {
+                    times: self.code.counts.len() - 1,
+                });
+
+                let next = self.emit(Op::Next { exit: 0 });
+                self.compile_block(body);
+                self.emit(Op::Jump(next));
+
+                self.code.ops[next] = Op::Next {
+                    exit: self.code.ops.len(),
+                };
+            }
+
+            Instruction::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.condition(condition);
+                let branch = self.emit(Op::If {
+                    condition,
+                    otherwise: 0,
+                });
+
+                self.compile_block(then_branch);
+                let skip_else = match else_branch.is_empty() {
+                    true => None,
+                    false => Some(self.emit(Op::Jump(0))),
+                };
+
+                self.code.ops[branch] = Op::If {
+                    condition,
+                    otherwise: self.code.ops.len(),
+                };
+
+                if let Some(skip_else) = skip_else {
+                    self.compile_block(else_branch);
+                    self.code.ops[skip_else] = Op::Jump(self.code.ops.len());
+                }
+            }
+
+            Instruction::While { condition, body } => {
+                let condition = self.condition(condition);
+                self.emit(Op::Step);
+
+                let check = self.emit(Op::While { condition, exit: 0 });
+                self.compile_block(body);
+                self.emit(Op::Jump(check));
+
+                self.code.ops[check] = Op::While {
+                    condition,
+                    exit: self.code.ops.len(),
+                };
+            }
+
+            Instruction::Define { name, params, body } => {
+                self.code.procedures.push(ProcedureHeader {
+                    name: name.clone(),
+                    params: params.clone(),
+                    entry: 0,
+                });
+
+                let index = self.code.procedures.len() - 1;
+                self.pending.push((index, body.clone()));
+                self.emit(Op::Define(index));
+            }
+
+            _ => {
+                self.code.commands.push(instruction.clone());
+                self.emit(Op::Run(self.code.commands.len() - 1));
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Condition) -> usize {
+        self.code.conditions.push(condition.clone());
+        self.code.conditions.len() - 1
+    }
+
+    /// Appends `op`, returning where it ended up (so that it can be patched later).
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.ops.push(op);
+        self.code.ops.len() - 1
+    }
+}
+
+impl Code {
+    /// Checks that every operation refers to things that exist, and that the
+    /// program cannot loop without taking steps (so that the step limit still
+    /// applies): jumps back must land on the head of a loop they are part of.
+    fn validate(&self) -> Result<(), String> {
+        let length = self.ops.len();
+        let check = |valid: bool, at: usize| match valid {
+            true => Ok(()),
+            false => Err(format!("operation {} is malformed", at)),
+        };
+
+        for (at, op) in self.ops.iter().enumerate() {
+            match *op {
+                Op::Run(index) => check(index < self.commands.len(), at)?,
+                Op::Define(index) => check(index < self.procedures.len(), at)?,
+                Op::Step | Op::Return => (),
+                Op::Repeat { times } => check(times < self.counts.len(), at)?,
+                Op::Next { exit } => check(at < exit && exit <= length, at)?,
+                Op::If {
+                    condition,
+                    otherwise: exit,
+                }
+                | Op::While { condition, exit } => check(
+                    condition < self.conditions.len() && at < exit && exit <= length,
+                    at,
+                )?,
+
+                Op::Jump(target) if target > at => check(target <= length, at)?,
+                Op::Jump(target) => check(
+                    match self.ops[target] {
+                        Op::Next { exit } | Op::While { exit, .. } => at < exit,
+                        _ => false,
+                    },
+                    at,
+                )?,
+            }
+        }
+
+        for procedure in self.procedures.iter() {
+            if procedure.entry > length {
+                return Err(format!("procedure {} is malformed", procedure.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// :: ---
+
+/// Writes the binary format: a header, then each part of the program in turn,
+/// as tagged values whose numbers are all LEB128 varints (zigzagged, if signed).
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn write_code(&mut self, code: &Code) {
+        self.bytes.extend_from_slice(MAGIC);
+        self.bytes.push(FORMAT_VERSION);
+
+        self.write_all(&code.ops, Writer::write_op);
+        self.write_all(&code.commands, Writer::write_command);
+        self.write_all(&code.conditions, Writer::write_condition);
+        self.write_all(&code.counts, Writer::write_expr);
+        self.write_all(&code.procedures, |writer, procedure| {
+            writer.write_string(&procedure.name);
+            writer.write_all(&procedure.params, |writer, param| {
+                writer.write_string(param)
+            });
+            writer.write_number(procedure.entry);
+        });
+    }
+
+    fn write_all<T, F>(&mut self, items: &[T], write: F)
+    where
+        F: Fn(&mut Writer, &T),
+    {
+        self.write_number(items.len());
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    fn write_op(&mut self, op: &Op) {
+        match *op {
+            Op::Run(index) => self.write_tagged(0, &[index]),
+            Op::Define(index) => self.write_tagged(1, &[index]),
+            Op::Step => self.write_tagged(2, &[]),
+            Op::Repeat { times } => self.write_tagged(3, &[times]),
+            Op::Next { exit } => self.write_tagged(4, &[exit]),
+            Op::If {
+                condition,
+                otherwise,
+            } => self.write_tagged(5, &[condition, otherwise]),
+            Op::While { condition, exit } => self.write_tagged(6, &[condition, exit]),
+            Op::Jump(target) => self.write_tagged(7, &[target]),
+            Op::Return => self.write_tagged(8, &[]),
+        }
+    }
+
+    fn write_command(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Place { x, y, orientation } => {
+                self.bytes.push(0);
+                self.write_position(x, y);
+                self.write_orientation(*orientation);
+            }
+
+            Instruction::Obstacle { x, y } => {
+                self.bytes.push(1);
+                self.write_position(x, y);
+            }
+
+            Instruction::Charger { x, y } => {
+                self.bytes.push(2);
+                self.write_position(x, y);
+            }
+
+            Instruction::Left => self.bytes.push(3),
+            Instruction::Right => self.bytes.push(4),
+            Instruction::Move => self.bytes.push(5),
+            Instruction::Report => self.bytes.push(6),
+            Instruction::ReportStats => self.bytes.push(7),
+            Instruction::ReportCharge => self.bytes.push(8),
+            Instruction::ReportJson => self.bytes.push(9),
+            Instruction::ReportCsv => self.bytes.push(10),
+            Instruction::ReportTrail => self.bytes.push(11),
+            Instruction::ReportMap => self.bytes.push(12),
+            Instruction::Back => self.bytes.push(13),
+            Instruction::StrafeLeft => self.bytes.push(14),
+            Instruction::StrafeRight => self.bytes.push(15),
+            Instruction::UTurn => self.bytes.push(16),
+
+            Instruction::Face(orientation) => {
+                self.bytes.push(17);
+                self.write_orientation(*orientation);
+            }
+
+            Instruction::Sweep { dry_run } => self.bytes.push(18 + *dry_run as u8),
+
+            Instruction::Expect(Expectation::At { x, y, orientation }) => {
+                self.bytes.push(20);
+                self.write_position(x, y);
+                self.write_orientation(*orientation);
+            }
+
+            Instruction::Expect(Expectation::NotPlaced) => self.bytes.push(21),
+            Instruction::Expect(Expectation::Blocked) => self.bytes.push(22),
+            Instruction::Expect(Expectation::Obstacle { x, y }) => {
+                self.bytes.push(23);
+                self.write_position(x, y);
+            }
+
+            Instruction::MoveBy { steps, policy } => {
+                self.bytes.push(24);
+                self.write_expr(steps);
+                self.bytes.push(match policy {
+                    None => 0,
+                    Some(MovePolicy::AllOrNothing) => 1,
+                    Some(MovePolicy::AsFarAsPossible) => 2,
+                });
+            }
+
+            Instruction::Call { name, args } => {
+                self.bytes.push(25);
+                self.write_string(name);
+                self.write_all(args, Writer::write_expr);
+            }
+
+            Instruction::Let { name, value } => {
+                self.bytes.push(26);
+                self.write_string(name);
+                self.write_expr(value);
+            }
+
+            Instruction::Custom { keyword, args } => {
+                self.bytes.push(27);
+                self.write_string(keyword);
+                self.write_all(args, |writer, arg| writer.write_string(arg));
+            }
+
+            // :: Blocks are compiled into operations of their own, and so never
+            //    end up among the commands.
+            Instruction::Repeat { .. }
+            | Instruction::If { .. }
+            | Instruction::While { .. }
+            | Instruction::Define { .. } => unreachable!(),
+        }
+    }
+
+    fn write_condition(&mut self, condition: &Condition) {
+        match condition {
+            Condition::FrontClear => self.bytes.push(0),
+            Condition::LeftClear => self.bytes.push(1),
+            Condition::RightClear => self.bytes.push(2),
+            Condition::Placed => self.bytes.push(3),
+            Condition::At { x, y } => {
+                self.bytes.push(4);
+                self.write_position(x, y);
+            }
+
+            Condition::Facing(orientation) => {
+                self.bytes.push(5);
+                self.write_orientation(*orientation);
+            }
+
+            Condition::Not(negated) => {
+                self.bytes.push(6);
+                self.write_condition(negated);
+            }
+        }
+    }
+
+    fn write_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(value) => {
+                self.bytes.push(0);
+                self.write_varint(((value << 1) ^ (value >> 63)) as u64);
+            }
+
+            Expr::Variable(name) => {
+                self.bytes.push(1);
+                self.write_string(name);
+            }
+
+            Expr::Negate(operand) => {
+                self.bytes.push(2);
+                self.write_expr(operand);
+            }
+
+            Expr::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                self.bytes.push(match operator {
+                    Operator::Add => 3,
+                    Operator::Subtract => 4,
+                    Operator::Multiply => 5,
+                    Operator::Divide => 6,
+                    Operator::Remainder => 7,
+                });
+
+                self.write_expr(left);
+                self.write_expr(right);
+            }
+        }
+    }
+
+    fn write_position(&mut self, x: &Expr, y: &Expr) {
+        self.write_expr(x);
+        self.write_expr(y);
+    }
+
+    fn write_orientation(&mut self, orientation: Orientation) {
+        self.bytes.push(match orientation {
+            Orientation::North => 0,
+            Orientation::East => 1,
+            Orientation::South => 2,
+            Orientation::West => 3,
+        });
+    }
+
+    fn write_tagged(&mut self, tag: u8, operands: &[usize]) {
+        self.bytes.push(tag);
+        for operand in operands {
+            self.write_number(*operand);
+        }
+    }
+
+    fn write_string(&mut self, text: &str) {
+        self.write_number(text.len());
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    fn write_number(&mut self, number: usize) {
+        self.write_varint(number as u64);
+    }
+
+    fn write_varint(&mut self, mut number: u64) {
+        while number >= 0x80 {
+            self.bytes.push((number as u8 & 0x7F) | 0x80);
+            number >>= 7;
+        }
+
+        self.bytes.push(number as u8);
+    }
+}
+
+/// Reads back what `Writer` wrote.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_code(&mut self) -> Result<Code, String> {
+        if self.bytes.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err("it is not a program".to_string());
+        }
+
+        self.offset = MAGIC.len();
+        if self.read_byte()? != FORMAT_VERSION {
+            return Err("it was saved by an unsupported version".to_string());
+        }
+
+        let code = Code {
+            ops: self.read_all(Reader::read_op)?,
+            commands: self.read_all(Reader::read_command)?,
+            conditions: self.read_all(|reader| reader.read_condition(true))?,
+            counts: self.read_all(Reader::read_whole_expr)?,
+            procedures: self.read_all(|reader| {
+                Ok(ProcedureHeader {
+                    name: reader.read_string()?,
+                    params: reader.read_all(Reader::read_string)?,
+                    entry: reader.read_number()?,
+                })
+            })?,
+        };
+
+        match self.offset == self.bytes.len() {
+            true => Ok(code),
+            false => Err("it has unexpected bytes at the end".to_string()),
+        }
+    }
+
+    fn read_all<T, F>(&mut self, read: F) -> Result<Vec<T>, String>
+    where
+        F: Fn(&mut Reader<'a>) -> Result<T, String>,
+    {
+        // :: The count is not trusted to preallocate with, since every item takes
+        //    up at least a byte, any count past what is left is bound to fail.
+        let count = self.read_number()?;
+        let mut items = vec![];
+        for _ in 0..count {
+            items.push(read(self)?);
+        }
+
+        Ok(items)
+    }
+
+    fn read_op(&mut self) -> Result<Op, String> {
+        Ok(match self.read_byte()? {
+            0 => Op::Run(self.read_number()?),
+            1 => Op::Define(self.read_number()?),
+            2 => Op::Step,
+            3 => Op::Repeat {
+                times: self.read_number()?,
+            },
+            4 => Op::Next {
+                exit: self.read_number()?,
+            },
+            5 => Op::If {
+                condition: self.read_number()?,
+                otherwise: self.read_number()?,
+            },
+            6 => Op::While {
+                condition: self.read_number()?,
+                exit: self.read_number()?,
+            },
+            7 => Op::Jump(self.read_number()?),
+            8 => Op::Return,
+            tag => return Err(format!("operation {} is unknown", tag)),
+        })
+    }
+
+    fn read_command(&mut self) -> Result<Instruction, String> {
+        Ok(match self.read_byte()? {
+            0 => {
+                let (x, y) = self.read_position()?;
+                Instruction::Place {
+                    x,
+                    y,
+                    orientation: self.read_orientation()?,
+                }
+            }
+
+            1 => {
+                let (x, y) = self.read_position()?;
+                Instruction::Obstacle { x, y }
+            }
+
+            2 => {
+                let (x, y) = self.read_position()?;
+                Instruction::Charger { x, y }
+            }
+
+            3 => Instruction::Left,
+            4 => Instruction::Right,
+            5 => Instruction::Move,
+            6 => Instruction::Report,
+            7 => Instruction::ReportStats,
+            8 => Instruction::ReportCharge,
+            9 => Instruction::ReportJson,
+            10 => Instruction::ReportCsv,
+            11 => Instruction::ReportTrail,
+            12 => Instruction::ReportMap,
+            13 => Instruction::Back,
+            14 => Instruction::StrafeLeft,
+            15 => Instruction::StrafeRight,
+            16 => Instruction::UTurn,
+            17 => Instruction::Face(self.read_orientation()?),
+            18 => Instruction::Sweep { dry_run: false },
+            19 => Instruction::Sweep { dry_run: true },
+
+            20 => {
+                let (x, y) = self.read_position()?;
+                Instruction::Expect(Expectation::At {
+                    x,
+                    y,
+                    orientation: self.read_orientation()?,
+                })
+            }
+
+            21 => Instruction::Expect(Expectation::NotPlaced),
+            22 => Instruction::Expect(Expectation::Blocked),
+            23 => {
+                let (x, y) = self.read_position()?;
+                Instruction::Expect(Expectation::Obstacle { x, y })
+            }
+
+            24 => Instruction::MoveBy {
+                steps: self.read_whole_expr()?,
+                policy: match self.read_byte()? {
+                    0 => None,
+                    1 => Some(MovePolicy::AllOrNothing),
+                    2 => Some(MovePolicy::AsFarAsPossible),
+                    policy => return Err(format!("move policy {} is unknown", policy)),
+                },
+            },
+
+            25 => Instruction::Call {
+                name: self.read_string()?,
+                args: self.read_all(Reader::read_whole_expr)?,
+            },
+
+            26 => {
+                // :: As when parsing, so that built-in variables stay read-only.
+                let name = self.read_string()?;
+                instruction::check_variable_name(&name)?;
+
+                Instruction::Let {
+                    name,
+                    value: self.read_whole_expr()?,
+                }
+            }
+
+            27 => Instruction::Custom {
+                keyword: self.read_string()?,
+                args: self.read_all(Reader::read_string)?,
+            },
+
+            tag => return Err(format!("command {} is unknown", tag)),
+        })
+    }
+
+    /// Reads a condition; as when parsing, negations are never nested.
+    fn read_condition(&mut self, may_negate: bool) -> Result<Condition, String> {
+        Ok(match self.read_byte()? {
+            0 => Condition::FrontClear,
+            1 => Condition::LeftClear,
+            2 => Condition::RightClear,
+            3 => Condition::Placed,
+            4 => {
+                let (x, y) = self.read_position()?;
+                Condition::At { x, y }
+            }
+
+            5 => Condition::Facing(self.read_orientation()?),
+            6 if may_negate => Condition::Not(Box::new(self.read_condition(false)?)),
+            tag => return Err(format!("condition {} is unknown", tag)),
+        })
+    }
+
+    fn read_position(&mut self) -> Result<(Expr, Expr), String> {
+        Ok((self.read_whole_expr()?, self.read_whole_expr()?))
+    }
+
+    /// Reads an expression, limited in size the same way parsed ones are
+    /// (counting every part of it, rather than just its terms).
+    fn read_whole_expr(&mut self) -> Result<Expr, String> {
+        let mut budget = 2 * EXPRESSION_SIZE_LIMIT;
+        self.read_expr(&mut budget)
+    }
+
+    fn read_expr(&mut self, budget: &mut usize) -> Result<Expr, String> {
+        *budget = match budget.checked_sub(1) {
+            Some(budget) => budget,
+            None => return Err("an expression is too long".to_string()),
+        };
+
+        let operator = match self.read_byte()? {
+            0 => {
+                let zigzag = self.read_varint(64)?;
+                return Ok(Expr::Literal((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64)));
+            }
+
+            1 => return Ok(Expr::Variable(self.read_string()?)),
+            2 => return Ok(Expr::Negate(Box::new(self.read_expr(budget)?))),
+            3 => Operator::Add,
+            4 => Operator::Subtract,
+            5 => Operator::Multiply,
+            6 => Operator::Divide,
+            7 => Operator::Remainder,
+            tag => return Err(format!("expression {} is unknown", tag)),
+        };
+
+        Ok(Expr::Binary {
+            operator,
+            left: Box::new(self.read_expr(budget)?),
+            right: Box::new(self.read_expr(budget)?),
+        })
+    }
+
+    fn read_orientation(&mut self) -> Result<Orientation, String> {
+        Ok(match self.read_byte()? {
+            0 => Orientation::North,
+            1 => Orientation::East,
+            2 => Orientation::South,
+            3 => Orientation::West,
+            orientation => return Err(format!("orientation {} is unknown", orientation)),
+        })
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let length = self.read_number()?;
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| "it ends too early".to_string())?;
+
+        let text = std::str::from_utf8(&self.bytes[self.offset..end])
+            .map_err(|_| "a name is not valid text".to_string())?;
+
+        self.offset = end;
+        Ok(text.to_string())
+    }
+
+    /// Reads a varint of up to 32 bits.
+    fn read_number(&mut self) -> Result<usize, String> {
+        // :: At most 32 bits are read, so this is lossless (even on wasm).
+        Ok(self.read_varint(32)? as usize)
+    }
+
+    /// Reads a varint of up to `width` (at most 64) bits.
+    fn read_varint(&mut self, width: u32) -> Result<u64, String> {
+        let mut number: u64 = 0;
+
+        for shift in (0..width).step_by(7) {
+            let byte = self.read_byte()?;
+            let bits = (byte & 0x7F) as u64;
+            if width - shift < 7 && bits >> (width - shift) != 0 {
+                return Err("a number is out of range".to_string());
+            }
+
+            number |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(number);
+            }
+        }
+
+        Err("a number is out of range".to_string())
+    }
+
+    fn read_byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or_else(|| "it ends too early".to_string())?;
+
+        self.offset += 1;
+        Ok(byte)
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(script: &str) -> Program {
+        Program::compile(script).unwrap()
+    }
+
+    #[test]
+    fn plain_commands_are_run_in_order() {
+        let program = compile("PLACE 0,0,NORTH\nMOVE\nREPORT");
+
+        assert_eq!(
+            program.code().ops,
+            vec![Op::Run(0), Op::Run(1), Op::Run(2), Op::Return]
+        );
+        assert_eq!(program.code().commands[1], Instruction::Move);
+    }
+
+    #[test]
+    fn loops_and_branches_are_compiled_to_jumps() {
+        let program = compile("REPEAT 2 { MOVE }\nWHILE FRONT_CLEAR { MOVE }");
+        assert_eq!(
+            program.code().ops,
+            vec![
+                Op::Repeat { times: 0 },
+                Op::Next { exit: 4 },
+                Op::Run(0),
+                Op::Jump(1),
+                Op::Step,
+                Op::While {
+                    condition: 0,
+                    exit: 8
+                },
+                Op::Run(1),
+                Op::Jump(5),
+                Op::Return,
+            ]
+        );
+
+        let program = compile("IF FRONT_CLEAR THEN MOVE ELSE LEFT\nREPORT");
+        assert_eq!(
+            program.code().ops,
+            vec![
+                Op::If {
+                    condition: 0,
+                    otherwise: 3
+                },
+                Op::Run(0),
+                Op::Jump(4),
+                Op::Run(1),
+                Op::Run(2),
+                Op::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn procedure_bodies_are_laid_out_after_the_program() {
+        let program = compile("DEFINE TWICE\n  MOVE; MOVE\nEND\nTWICE");
+
+        assert_eq!(
+            program.code().ops,
+            vec![
+                Op::Define(0),
+                Op::Run(0),
+                Op::Return,
+                Op::Run(1),
+                Op::Run(2),
+                Op::Return,
+            ]
+        );
+        assert_eq!(program.code().procedures[0].name, "TWICE");
+        assert_eq!(program.code().procedures[0].entry, 3);
+    }
+
+    #[test]
+    fn invalid_scripts_are_not_compiled() {
+        assert!(Program::compile("REPEAT 2 { MOVE").is_err());
+        assert!(Program::compile("JUMP!").is_err());
+    }
+
+    #[test]
+    fn programs_survive_being_saved_and_loaded() {
+        let script = "
+            PLACE 1, 2, NORTH
+            OBSTACLE 3,3; CHARGER 0,4; FACE west; SWEEP PLAN; SWEEP
+            LEFT; RIGHT; BACK; UTURN; STRAFE LEFT; STRAFE RIGHT
+            REPORT; REPORT STATS; REPORT CHARGE; REPORT JSON; REPORT CSV; REPORT TRAIL; REPORT MAP
+            EXPECT 1,2,NORTH; EXPECT NOT_PLACED; EXPECT BLOCKED; EXPECT OBSTACLE 3,3
+            MOVE; MOVE -($N * 2 - 1) % 3 / 1; MOVE 2 AS_FAR_AS_POSSIBLE; MOVE 1 ALL_OR_NOTHING
+            LET $N = -9223372036854775807 - 1; LET $M = 9223372036854775807
+            DEFINE GO $STEPS
+              IF NOT AT $STEPS, 0 { MOVE $STEPS } ELSE { REPORT }
+              WHILE FACING EAST { LEFT }
+            END
+            REPEAT $WIDTH { GO 1; IF LEFT_CLEAR THEN STRAFE LEFT }
+            IF RIGHT_CLEAR THEN MOVE; IF PLACED THEN MOVE
+        ";
+
+        let program = compile(script);
+        let bytes = program.to_bytes();
+
+        assert!(bytes.starts_with(b"TRSP"));
+        assert_eq!(Program::from_bytes(&bytes), Ok(program));
+    }
+
+    #[test]
+    fn programs_are_compact() {
+        let bytes = compile("PLACE 0,0,NORTH\nREPEAT 4 { MOVE; LEFT }\nREPORT").to_bytes();
+        assert!(bytes.len() < 40, "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn malformed_programs_are_not_loaded() {
+        let bytes = compile("REPEAT 2 { MOVE }\nREPORT").to_bytes();
+
+        assert!(Program::from_bytes(b"").is_err());
+        assert!(Program::from_bytes(b"GIF89a").is_err());
+        assert!(Program::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut unsupported = bytes.clone();
+        unsupported[4] = FORMAT_VERSION + 1;
+        assert!(Program::from_bytes(&unsupported).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(Program::from_bytes(&trailing).is_err());
+    }
+
+    #[test]
+    fn programs_assigning_to_invalid_variables_are_not_loaded() {
+        let load = |name: &str| {
+            let mut writer = Writer::default();
+            writer.write_code(&Code {
+                ops: vec![Op::Run(0)],
+                commands: vec![Instruction::Let {
+                    name: name.to_string(),
+                    value: Expr::Literal(1),
+                }],
+                ..Code::default()
+            });
+
+            Program::from_bytes(&writer.bytes)
+        };
+
+        assert!(load("STEPS").is_ok());
+        assert_eq!(
+            load("WIDTH").err(),
+            Some("Program could not be loaded: Variable $WIDTH is read-only.".to_string())
+        );
+        assert!(load("ROBOT_X").is_err());
+        assert!(load("steps").is_err());
+        assert!(load("2X").is_err());
+        assert!(load("").is_err());
+    }
+
+    #[test]
+    fn programs_that_could_loop_without_taking_steps_are_not_loaded() {
+        let load = |ops: Vec<Op>| {
+            let mut writer = Writer::default();
+            writer.write_code(&Code {
+                ops,
+                commands: vec![Instruction::Move],
+                conditions: vec![Condition::FrontClear],
+                ..Code::default()
+            });
+
+            Program::from_bytes(&writer.bytes)
+        };
+
+        assert!(load(vec![Op::Run(0), Op::Jump(0)]).is_err());
+        assert!(load(vec![Op::Run(1)]).is_err());
+        assert!(load(vec![Op::Jump(7)]).is_err());
+
+        // :: Once a `WHILE` is done, it jumps past its body; jumping back to it
+        //    from there could go on forever.
+        let exited = vec![
+            Op::While {
+                condition: 0,
+                exit: 3,
+            },
+            Op::Run(0),
+            Op::Jump(0),
+            Op::Jump(0),
+        ];
+        assert!(load(exited).is_err());
+
+        let looped = vec![
+            Op::While {
+                condition: 0,
+                exit: 3,
+            },
+            Op::Run(0),
+            Op::Jump(0),
+            Op::Return,
+        ];
+        assert!(load(looped).is_ok());
+    }
+}
@@ -0,0 +1,447 @@
+use super::common::*;
+use super::tabletop::Tabletop;
+use super::trail::{Trail, TrailStep};
+
+// :: ---
+
+/// Draws a Tabletop as plain text, one character per position, with the
+/// highest row (`y = height - 1`) on top:
+///
+/// ```text
+/// 2 . . #
+/// 1 * * >
+/// 0 * . .
+///   0 1 2
+/// ```
+///
+/// Obstacles are drawn as `#`, charging stations as `+`, and the robot (if placed)
+/// as an arrow pointing where it faces. If a trail is given, positions the robot
+/// has visited are drawn as `*`.
+///
+/// Only Tabletops that can be gone over position by position, with at most
+/// `DRAW_SIZE_LIMIT` positions, can be drawn (see `Tabletop::grid_size`).
+pub fn ascii(
+    tabletop: &Tabletop,
+    robot: Option<(Position, Orientation)>,
+    trail: Option<&Trail>,
+) -> Result<String, String> {
+    let (width, height) = drawable_size(tabletop)?;
+    let label_width = (height - 1).to_string().len();
+
+    let heatmap = match trail {
+        Some(trail) => Some(trail.heatmap(width, height)?),
+        None => None,
+    };
+    let visited =
+        |x: i32, y: i32| matches!(&heatmap, Some(rows) if rows[y as usize][x as usize] > 0);
+
+    let mut lines = vec![];
+    for y in (0..height).rev() {
+        let cells = (0..width)
+            .map(|x| {
+                let position = Position {
+                    x: x.into(),
+                    y: y.into(),
+                };
+
+                match robot {
+                    Some((robot_position, orientation)) if robot_position == position => {
+                        arrow(orientation)
+                    }
+
+                    _ if tabletop.obstacles().contains(&position) => '#',
+                    _ if visited(x, y) => '*',
+                    _ if tabletop.is_charging_station(&position) => '+',
+                    _ => '.',
+                }
+            })
+            .map(|cell| cell.to_string())
+            .collect::<Vec<String>>();
+
+        lines.push(format!("{:>2$} {}", y, cells.join(" "), label_width));
+    }
+
+    // :: Column labels only show the last digit, so that columns stay aligned.
+    let labels = (0..width)
+        .map(|x| (x % 10).to_string())
+        .collect::<Vec<String>>();
+    lines.push(format!("{:>2$} {}", "", labels.join(" "), label_width));
+
+    Ok(lines.join("\n"))
+}
+
+/// Draws a Tabletop as a standalone SVG image, with the highest row on top and
+/// coordinate labels along the left and bottom edges.
+///
+/// Obstacles are drawn as dark squares, charging stations as green dots, and the
+/// robot (if placed) as an arrowhead pointing where it faces. If a trail is given,
+/// the path the robot took is drawn as a line; with a break wherever the robot
+/// was picked up and placed somewhere else.
+///
+/// Every element carries a `class` (e.g. `obstacle`, `robot`, `trail`), so that
+/// the drawing can be restyled with CSS when embedded in a page.
+///
+/// As with `ascii`, only Tabletops that can be gone over position by position,
+/// with at most `DRAW_SIZE_LIMIT` positions, can be drawn.
+pub fn svg(
+    tabletop: &Tabletop,
+    robot: Option<(Position, Orientation)>,
+    trail: Option<&Trail>,
+) -> Result<String, String> {
+    // :: Neither side can be longer than `DRAW_SIZE_LIMIT` positions, so every
+    //    pixel coordinate below fits an `i32`.
+    let (width, height) = drawable_size(tabletop)?;
+    let (image_width, image_height) = (
+        LABEL_MARGIN + width * CELL_SIZE + PADDING,
+        PADDING + height * CELL_SIZE + LABEL_MARGIN,
+    );
+
+    // :: Where a position's top-left corner is drawn; SVG's y axis points down.
+    //    Everything drawn is on the Tabletop, so its coordinates fit an `i32`.
+    let corner = |position: &Position| {
+        (
+            LABEL_MARGIN + position.x as i32 * CELL_SIZE,
+            PADDING + (height - 1 - position.y as i32) * CELL_SIZE,
+        )
+    };
+
+    let centre = |position: &Position| {
+        let (x, y) = corner(position);
+        (x + CELL_SIZE / 2, y + CELL_SIZE / 2)
+    };
+
+    let mut elements = vec![format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">",
+        image_width, image_height
+    )];
+
+    elements.push(format!(
+        "<rect class=\"tabletop\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+         fill=\"#fafafa\" stroke=\"#333\"/>",
+        LABEL_MARGIN,
+        PADDING,
+        width * CELL_SIZE,
+        height * CELL_SIZE
+    ));
+
+    // :: Grid lines, then coordinate labels.
+    let (left, top) = (LABEL_MARGIN, PADDING);
+    let (right, bottom) = (left + width * CELL_SIZE, top + height * CELL_SIZE);
+
+    for x in 1..width {
+        let line_x = left + x * CELL_SIZE;
+        elements.push(format!(
+            "<line class=\"grid\" x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"#ccc\"/>",
+            line_x, top, bottom
+        ));
+    }
+
+    for y in 1..height {
+        let line_y = top + y * CELL_SIZE;
+        elements.push(format!(
+            "<line class=\"grid\" x1=\"{1}\" y1=\"{0}\" x2=\"{2}\" y2=\"{0}\" stroke=\"#ccc\"/>",
+            line_y, left, right
+        ));
+    }
+
+    for x in 0..width {
+        let (label_x, _) = centre(&Position { x: x.into(), y: 0 });
+        elements.push(format!(
+            "<text class=\"label\" x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            label_x,
+            bottom + 16,
+            x
+        ));
+    }
+
+    for y in 0..height {
+        let (_, label_y) = centre(&Position { x: 0, y: y.into() });
+        elements.push(format!(
+            "<text class=\"label\" x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            left - 6,
+            label_y + 4,
+            y
+        ));
+    }
+
+    // :: ---
+
+    // :: Sorted, so that the same Tabletop is always drawn the same way.
+    let mut charging_stations = tabletop.charging_stations().iter().collect::<Vec<_>>();
+    charging_stations.sort();
+
+    for position in charging_stations {
+        let (x, y) = centre(position);
+        elements.push(format!(
+            "<circle class=\"charging-station\" cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#7cb342\"/>",
+            x,
+            y,
+            CELL_SIZE / 4
+        ));
+    }
+
+    let mut obstacles = tabletop.obstacles().iter().collect::<Vec<_>>();
+    obstacles.sort();
+
+    for position in obstacles {
+        let (x, y) = corner(position);
+        elements.push(format!(
+            "<rect class=\"obstacle\" x=\"{0}\" y=\"{1}\" width=\"{2}\" height=\"{2}\" fill=\"#555\"/>",
+            x, y, CELL_SIZE
+        ));
+    }
+
+    if let Some(trail) = trail {
+        for segment in trail_segments(trail) {
+            let points = segment
+                .iter()
+                .map(|position| {
+                    let (x, y) = centre(position);
+                    format!("{},{}", x, y)
+                })
+                .collect::<Vec<String>>();
+
+            elements.push(format!(
+                "<polyline class=\"trail\" points=\"{}\" fill=\"none\" stroke=\"#e53935\" \
+                 stroke-width=\"3\" stroke-linejoin=\"round\" stroke-opacity=\"0.6\"/>",
+                points.join(" ")
+            ));
+        }
+    }
+
+    if let Some((position, orientation)) = robot {
+        let (x, y) = centre(&position);
+        elements.push(format!(
+            "<polygon class=\"robot\" points=\"0,-14 10,12 0,6 -10,12\" fill=\"#1e88e5\" \
+             transform=\"translate({} {}) rotate({})\"/>",
+            x,
+            y,
+            heading(orientation)
+        ));
+    }
+
+    elements.push("</svg>".to_string());
+    Ok(elements.join("\n"))
+}
+
+/// The most positions a Tabletop may have to be drawn, as drawings as text
+/// take memory in proportion (and larger SVG drawings don't fit in an `i32`).
+pub const DRAW_SIZE_LIMIT: i64 = 1 << 20;
+
+/// The size (in pixels) of a single position in SVG drawings.
+const CELL_SIZE: i32 = 40;
+
+/// Room left for the coordinate labels, along the left and bottom edges.
+const LABEL_MARGIN: i32 = 28;
+
+/// Room left along the top and right edges, so that the border isn't clipped.
+const PADDING: i32 = 8;
+
+/// The size of `tabletop`, if it can be drawn; or an error saying why not.
+fn drawable_size(tabletop: &Tabletop) -> Result<(i32, i32), String> {
+    let (width, height) = tabletop.grid_size()?;
+    if i64::from(width) * i64::from(height) > DRAW_SIZE_LIMIT {
+        return Err(format!(
+            "Tabletop is too large to be drawn; at most {} positions are supported.",
+            DRAW_SIZE_LIMIT
+        ));
+    }
+
+    Ok((width, height))
+}
+
+/// Splits a trail into runs of connected positions (the corners of each run,
+/// that is), i.e. wherever the robot was placed somewhere else.
+fn trail_segments(trail: &Trail) -> Vec<Vec<Position>> {
+    let mut segments: Vec<Vec<Position>> = vec![];
+
+    for entry in trail.entries() {
+        let position = entry.position;
+        match segments.last_mut() {
+            Some(segment) if *segment.last().unwrap() == position => (),
+            Some(segment) if entry.step != TrailStep::Placed => segment.push(position),
+            _ => segments.push(vec![position]),
+        }
+    }
+
+    segments
+}
+
+/// Clockwise rotation (in degrees) from pointing up, i.e. north.
+fn heading(orientation: Orientation) -> i32 {
+    match orientation {
+        Orientation::North => 0,
+        Orientation::East => 90,
+        Orientation::South => 180,
+        Orientation::West => 270,
+    }
+}
+
+fn arrow(orientation: Orientation) -> char {
+    match orientation {
+        Orientation::North => '^',
+        Orientation::East => '>',
+        Orientation::South => 'v',
+        Orientation::West => '<',
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::events::Event;
+
+    #[test]
+    fn tabletop_is_drawn_with_robot_and_obstacles() {
+        let mut tabletop = Tabletop::new(3, 3).unwrap();
+        tabletop.add_obstacle(2, 2).unwrap();
+        tabletop.add_charging_station(0, 2).unwrap();
+
+        let robot = Some((Position { x: 2, y: 1 }, Orientation::East));
+
+        assert_eq!(
+            ascii(&tabletop, robot, None).unwrap(),
+            "2 + . #\n1 . . >\n0 . . .\n  0 1 2"
+        );
+        assert_eq!(
+            ascii(&tabletop, None, None).unwrap(),
+            "2 + . #\n1 . . .\n0 . . .\n  0 1 2"
+        );
+    }
+
+    #[test]
+    fn trail_is_overlaid_on_the_tabletop() {
+        let tabletop = Tabletop::new(3, 11).unwrap();
+        let mut trail = Trail::default();
+
+        trail.record(
+            &Event::Placed {
+                position: Position { x: 0, y: 0 },
+                orientation: Orientation::North,
+            },
+            Some(Orientation::North),
+        );
+
+        trail.record(
+            &Event::Moved {
+                from: Position { x: 0, y: 0 },
+                to: Position { x: 0, y: 1 },
+            },
+            Some(Orientation::North),
+        );
+
+        let robot = Some((Position { x: 0, y: 1 }, Orientation::North));
+        let drawing = ascii(&tabletop, robot, Some(&trail)).unwrap();
+        let lines = drawing.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "10 . . .");
+        assert_eq!(lines[9], " 1 ^ . .");
+        assert_eq!(lines[10], " 0 * . .");
+        assert_eq!(lines[11], "   0 1 2");
+    }
+
+    #[test]
+    fn tabletop_is_exported_as_svg() {
+        let mut tabletop = Tabletop::new(4, 3).unwrap();
+        tabletop.add_obstacle(1, 1).unwrap();
+        tabletop.add_obstacle(3, 2).unwrap();
+        tabletop.add_charging_station(0, 2).unwrap();
+
+        let robot = Some((Position { x: 2, y: 0 }, Orientation::East));
+        let drawing = svg(&tabletop, robot, None).unwrap();
+
+        assert!(drawing.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"196\""));
+        assert!(drawing.ends_with("</svg>"));
+        assert_eq!(drawing.matches("class=\"obstacle\"").count(), 2);
+        assert_eq!(drawing.matches("class=\"charging-station\"").count(), 1);
+        assert_eq!(drawing.matches("class=\"label\"").count(), 4 + 3);
+        assert!(!drawing.contains("class=\"trail\""));
+
+        // :: Cell (3, 2) is in the top row, and (2, 0) in the bottom one.
+        assert!(drawing.contains("<rect class=\"obstacle\" x=\"148\" y=\"8\""));
+        assert!(drawing.contains("transform=\"translate(128 108) rotate(90)\""));
+
+        assert!(!svg(&tabletop, None, None)
+            .unwrap()
+            .contains("class=\"robot\""));
+    }
+
+    #[test]
+    fn trail_is_drawn_in_connected_segments() {
+        let tabletop = Tabletop::new(3, 3).unwrap();
+        let mut trail = Trail::default();
+
+        let events = [
+            Event::Placed {
+                position: Position { x: 0, y: 0 },
+                orientation: Orientation::North,
+            },
+            Event::Moved {
+                from: Position { x: 0, y: 0 },
+                to: Position { x: 0, y: 2 },
+            },
+            Event::Turned {
+                from: Orientation::North,
+                to: Orientation::East,
+            },
+            Event::Placed {
+                position: Position { x: 2, y: 0 },
+                orientation: Orientation::North,
+            },
+        ];
+
+        for event in events.iter() {
+            trail.record(event, Some(Orientation::North));
+        }
+
+        let drawing = svg(&tabletop, None, Some(&trail)).unwrap();
+        let trails = drawing
+            .lines()
+            .filter(|line| line.contains("class=\"trail\""))
+            .collect::<Vec<&str>>();
+
+        assert_eq!(trails.len(), 2);
+        assert!(trails[0].contains("points=\"48,108 48,28\""));
+        assert!(trails[1].contains("points=\"128,108\""));
+    }
+
+    #[test]
+    fn unbounded_tabletops_are_not_drawn() {
+        let mut tabletop = Tabletop::unbounded();
+        tabletop.add_obstacle(1, 1).unwrap();
+
+        assert!(ascii(&tabletop, None, None).is_err());
+        assert!(svg(&tabletop, None, None).is_err());
+    }
+
+    #[test]
+    fn oversized_tabletops_are_not_drawn_as_text() {
+        let tabletop = Tabletop::new(100_000, 100_000).unwrap();
+        let trail = Trail::default();
+
+        assert!(ascii(&tabletop, None, None)
+            .unwrap_err()
+            .starts_with("Tabletop is too large to be drawn"));
+        assert!(ascii(&tabletop, None, Some(&trail)).is_err());
+        assert!(trail.heatmap(100_000, 100_000).is_err());
+
+        assert!(ascii(&Tabletop::new(1024, 1024).unwrap(), None, Some(&trail)).is_ok());
+    }
+
+    #[test]
+    fn oversized_tabletops_are_not_drawn_as_svg() {
+        let robot = Some((Position { x: 0, y: 0 }, Orientation::North));
+
+        let long = Tabletop::new(60_000_000, 1).unwrap();
+        assert!(svg(&long, robot, None)
+            .unwrap_err()
+            .starts_with("Tabletop is too large to be drawn"));
+
+        let wide = Tabletop::new((1 << 20) + 1, 1).unwrap();
+        assert!(svg(&wide, robot, None).is_err());
+        assert!(svg(&Tabletop::new(1 << 10, 1 << 10).unwrap(), robot, None).is_ok());
+    }
+}
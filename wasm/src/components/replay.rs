@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::common::*;
+use super::gif::{Colour, GifEncoder, PALETTE_SIZE};
+use super::robot::{Observer, Robot};
+
+// :: ---
+
+/// Records snapshots of a Robot (and its Tabletop) as frames of an animated GIF.
+///
+/// Frames are drawn like `render::svg` (minus the coordinate labels): obstacles
+/// as dark squares, charging stations as green dots, the robot as an arrowhead,
+/// and positions it has visited shaded in red.
+pub struct Replay {
+    encoder: GifEncoder,
+    cell_size: usize,
+    width: i32,
+    height: i32,
+    frames: usize,
+}
+
+/// The default size (in pixels) of a single position in a replay.
+pub const DEFAULT_CELL_SIZE: u32 = 32;
+
+/// The default time (in milliseconds) each frame is shown for.
+pub const DEFAULT_FRAME_DELAY: u32 = 500;
+
+impl Replay {
+    /// Starts a replay of `robot`'s Tabletop, drawing each position as a
+    /// (`cell_size` x `cell_size`) pixel square, and showing each frame for
+    /// `frame_delay` milliseconds.
+    pub fn new(robot: &Robot, cell_size: u32, frame_delay: u32) -> Result<Replay, String> {
+        if !(4..=64).contains(&cell_size) {
+            return Err("Cell size needs to be between 4 and 64 pixels.".to_string());
+        }
+
+        // :: GIF stores delays in hundredths of a second.
+        let delay = (frame_delay / 10).min(u16::MAX as u32) as u16;
+
+        let (width, height) = robot.tabletop().grid_size()?;
+        let image_size = |cells: i32| (cells as i64) * (cell_size as i64) + 1;
+
+        match (image_size(width), image_size(height)) {
+            (image_width, image_height)
+                if image_width <= u16::MAX as i64 && image_height <= u16::MAX as i64 =>
+            {
+                Ok(Replay {
+                    encoder: GifEncoder::new(
+                        image_width as u16,
+                        image_height as u16,
+                        &PALETTE,
+                        delay,
+                    ),
+                    cell_size: cell_size as usize,
+                    width,
+                    height,
+                    frames: 0,
+                })
+            }
+
+            _ => Err("Tabletop is too large to be drawn at this cell size.".to_string()),
+        }
+    }
+
+    /// Draws `robot` as it is right now, as the next frame; as long as its
+    /// Tabletop is the same size as the one the replay was started for.
+    pub fn capture(&mut self, robot: &Robot) -> Result<(), String> {
+        if robot.tabletop().grid_size()? != (self.width, self.height) {
+            return Err(
+                "Robot is on a Tabletop of a different size than the replay was started for."
+                    .to_string(),
+            );
+        }
+
+        let cell_size = self.cell_size;
+        let (image_width, image_height) = (
+            self.width as usize * cell_size + 1,
+            self.height as usize * cell_size + 1,
+        );
+
+        let heatmap = robot.heatmap()?;
+        let tabletop = robot.tabletop();
+        let placed = robot.position().zip(robot.orientation());
+
+        let mut pixels = Vec::with_capacity(image_width * image_height);
+        for row in 0..image_height {
+            for column in 0..image_width {
+                let (u, v) = (column % cell_size, row % cell_size);
+
+                // :: Grid lines (and the border around them).
+                if u == 0 || v == 0 {
+                    let is_border = column == 0
+                        || row == 0
+                        || column == image_width - 1
+                        || row == image_height - 1;
+
+                    pixels.push(if is_border { BORDER } else { GRID });
+                    continue;
+                }
+
+                let position = Position {
+                    x: (column / cell_size) as Coordinate,
+                    y: (self.height - 1 - (row / cell_size) as i32).into(),
+                };
+
+                let (fx, fy) = (u as f64 / cell_size as f64, v as f64 / cell_size as f64);
+
+                let inset = |f: f64| (0.3..0.7).contains(&f);
+
+                let pixel = match placed {
+                    Some((at, orientation)) if at == position && in_arrow(orientation, fx, fy) => {
+                        ROBOT
+                    }
+
+                    _ if tabletop.obstacles().contains(&position) => OBSTACLE,
+                    _ if tabletop.is_charging_station(&position) && inset(fx) && inset(fy) => {
+                        CHARGING_STATION
+                    }
+
+                    _ if heatmap[position.y as usize][position.x as usize] > 0 => TRAIL,
+                    _ => BACKGROUND,
+                };
+
+                pixels.push(pixel);
+            }
+        }
+
+        self.encoder.add_frame(&pixels);
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Finishes the replay, returning the encoded GIF.
+    pub fn finish(self) -> Vec<u8> {
+        self.encoder.finish()
+    }
+}
+
+/// Runs `script` on `robot`, one top-level instruction at a time, returning an
+/// animated GIF with a frame for the starting state and one per command run,
+/// including those run by blocks and procedures (see `Robot::run_observed`).
+///
+/// Instructions that are rejected are skipped (but the command rejected still
+/// gets a frame), so that a replay shows everything the script tried to do.
+pub fn record(
+    robot: &mut Robot,
+    script: &str,
+    cell_size: u32,
+    frame_delay: u32,
+) -> Result<Vec<u8>, String> {
+    let instructions = robot.parse_script_to_run(script)?;
+    let replay = Rc::new(RefCell::new(Replay::new(robot, cell_size, frame_delay)?));
+
+    replay.borrow_mut().capture(robot)?;
+    for (_, instruction) in instructions.iter() {
+        let _ = robot.run_observed(instruction, observer(&replay));
+    }
+
+    match Rc::try_unwrap(replay) {
+        Ok(replay) => Ok(replay.into_inner().finish()),
+        Err(_) => Err("Replay is still being recorded.".to_string()),
+    }
+}
+
+/// An observer (see `Robot::run_observed`) capturing a frame of `replay` each
+/// time it is shown the robot.
+pub fn observer(replay: &Rc<RefCell<Replay>>) -> Observer {
+    let replay = Rc::clone(replay);
+    Box::new(move |robot| replay.borrow_mut().capture(robot))
+}
+
+// :: ---
+
+const BACKGROUND: u8 = 0;
+const GRID: u8 = 1;
+const BORDER: u8 = 2;
+const OBSTACLE: u8 = 3;
+const CHARGING_STATION: u8 = 4;
+const TRAIL: u8 = 5;
+const ROBOT: u8 = 6;
+
+/// The same colours as `render::svg` uses.
+const PALETTE: [Colour; PALETTE_SIZE] = [
+    [0xFA, 0xFA, 0xFA],
+    [0xCC, 0xCC, 0xCC],
+    [0x33, 0x33, 0x33],
+    [0x55, 0x55, 0x55],
+    [0x7C, 0xB3, 0x42],
+    [0xF2, 0xA7, 0xA6],
+    [0x1E, 0x88, 0xE5],
+    [0x00, 0x00, 0x00],
+];
+
+/// Whether a point (as a fraction of the cell, from its top-left corner) falls
+/// within an arrowhead pointing towards `orientation`.
+fn in_arrow(orientation: Orientation, x: f64, y: f64) -> bool {
+    // :: Rotate the point so that the arrowhead can be tested as if pointing up.
+    let (x, y) = match orientation {
+        Orientation::North => (x, y),
+        Orientation::East => (y, 1.0 - x),
+        Orientation::South => (1.0 - x, 1.0 - y),
+        Orientation::West => (1.0 - y, x),
+    };
+
+    (0.15..=0.85).contains(&y) && (x - 0.5).abs() <= 0.3 * (y - 0.15) / 0.7
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tabletop::Tabletop;
+
+    #[test]
+    fn arrows_point_where_the_robot_faces() {
+        // :: The tip is drawn, but not the space either side of it.
+        assert!(in_arrow(Orientation::North, 0.5, 0.2));
+        assert!(!in_arrow(Orientation::North, 0.5, 0.9));
+        assert!(in_arrow(Orientation::East, 0.8, 0.5));
+        assert!(!in_arrow(Orientation::East, 0.1, 0.5));
+        assert!(in_arrow(Orientation::South, 0.5, 0.8));
+        assert!(in_arrow(Orientation::West, 0.2, 0.5));
+        assert!(!in_arrow(Orientation::West, 0.2, 0.2));
+    }
+
+    /// How many frames (each with a 25 hundredths delay) a GIF has, going by
+    /// its graphic control extensions.
+    fn frames_of(bytes: &[u8]) -> usize {
+        bytes
+            .windows(6)
+            .filter(|window| window == &[0x21, 0xF9, 0x04, 0x04, 25, 0])
+            .count()
+    }
+
+    #[test]
+    fn scripts_are_recorded_one_frame_per_instruction() {
+        let tabletop = Tabletop::new(4, 3).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        let script = "PLACE 0,0,NORTH\nMOVE\nRIGHT\nMOVE 5\nREPEAT 2 { MOVE }\nREPORT";
+        let bytes = record(&mut robot, script, 8, 250).unwrap();
+
+        assert!(bytes.starts_with(b"GIF89a\x21\x00\x19\x00"));
+
+        // :: The starting state, and seven commands (counting both of the
+        //    `REPEAT`'s); `MOVE 5` is rejected.
+        assert_eq!(frames_of(&bytes), 8);
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("2,1,EAST".to_string())));
+    }
+
+    #[test]
+    fn commands_run_by_blocks_and_procedures_get_frames_of_their_own() {
+        let tabletop = Tabletop::new(4, 12).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        let script = "DEFINE HOP { MOVE; MOVE }\nPLACE 0,0,NORTH\nREPEAT 10 { MOVE }\nHOP\nHOP";
+        let bytes = record(&mut robot, script, 4, 250).unwrap();
+
+        // :: The starting state, `PLACE`, ten `MOVE`s, then two for the first
+        //    `HOP` and one for the second, since moving over the edge is rejected
+        //    (and so stops the procedure).
+        assert_eq!(frames_of(&bytes), 1 + 1 + 10 + 2 + 1);
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("0,11,NORTH".to_string())));
+
+        // :: Each frame takes a step, so even scripts that run forever stop.
+        robot.set_step_limit(50);
+        let bytes = record(&mut robot, "WHILE NOT AT 9,9 { LEFT }", 4, 250).unwrap();
+        assert!(frames_of(&bytes) < 50);
+    }
+
+    #[test]
+    fn robots_on_other_tabletops_are_not_captured() {
+        let robot = Robot::create(&Tabletop::new(5, 5).unwrap()).unwrap();
+        let mut replay = Replay::new(&robot, 8, 100).unwrap();
+        assert!(replay.capture(&robot).is_ok());
+
+        let smaller = Robot::create(&Tabletop::new(2, 2).unwrap()).unwrap();
+        assert!(replay.capture(&smaller).is_err());
+
+        let unbounded = Robot::create(&Tabletop::unbounded()).unwrap();
+        assert!(replay.capture(&unbounded).is_err());
+        assert_eq!(replay.frames(), 1);
+    }
+
+    #[test]
+    fn replay_options_are_validated() {
+        let robot = Robot::create(&Tabletop::new(5, 5).unwrap()).unwrap();
+        assert!(Replay::new(&robot, 3, 100).is_err());
+        assert!(Replay::new(&robot, 65, 100).is_err());
+
+        let robot = Robot::create(&Tabletop::new(5000, 5).unwrap()).unwrap();
+        assert!(Replay::new(&robot, 16, 100).is_err());
+    }
+}
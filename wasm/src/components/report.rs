@@ -0,0 +1,293 @@
+use super::common::*;
+
+// :: ---
+
+/// How `REPORT` describes a Robot's status.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ReportFormat {
+    /// `x,y,ORIENTATION`, e.g. `0,1,NORTH` (the default).
+    #[default]
+    Plain,
+    /// A JSON object, e.g.
+    /// `{"id":1,"placed":true,"position":{"x":0,"y":1},"orientation":"NORTH"}`.
+    Json,
+    /// A single CSV record, with the columns in `CSV_HEADER`, e.g. `1,true,0,1,NORTH`.
+    Csv,
+    /// A user-defined template, e.g. `{x}:{y} facing {f}`.
+    Template(Template),
+}
+
+/// The columns of `REPORT CSV` records.
+pub const CSV_HEADER: &str = "id,placed,x,y,orientation";
+
+impl ReportFormat {
+    /// Parses the name of a built-in format: `plain`, `json` or `csv`.
+    pub fn parse(plaintext: &str) -> Result<ReportFormat, String> {
+        match plaintext.to_uppercase().as_str() {
+            "PLAIN" => Ok(ReportFormat::Plain),
+            "JSON" => Ok(ReportFormat::Json),
+            "CSV" => Ok(ReportFormat::Csv),
+
+            _ => Err(format!("Unexpected report format: {}", plaintext)),
+        }
+    }
+}
+
+/// A snapshot of everything a report can include.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Status {
+    pub id: u32,
+    pub position: Option<Position>,
+    pub orientation: Option<Orientation>,
+}
+
+impl Status {
+    /// Describes this status in `format`.
+    ///
+    /// The machine-readable formats (JSON and CSV) describe robots that aren't
+    /// placed too; the others need a position to report on.
+    pub fn format(&self, format: &ReportFormat) -> Result<String, String> {
+        let placed = self.position.zip(self.orientation);
+
+        match (format, placed) {
+            (ReportFormat::Json, _) => Ok(self.as_json()),
+            (ReportFormat::Csv, _) => Ok(self.as_csv()),
+            (_, None) => Err("Robot is not placed; discarding instruction.".to_string()),
+
+            (ReportFormat::Plain, Some((position, orientation))) => Ok(format!(
+                "{},{},{}",
+                position.x,
+                position.y,
+                orientation.as_str()
+            )),
+
+            (ReportFormat::Template(template), Some(_)) => Ok(template.fill(self)),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        let (position, orientation) = match self.position.zip(self.orientation) {
+            Some((position, orientation)) => (
+                format!("{{\"x\":{},\"y\":{}}}", position.x, position.y),
+                format!("\"{}\"", orientation.as_str()),
+            ),
+
+            None => ("null".to_string(), "null".to_string()),
+        };
+
+        format!(
+            "{{\"id\":{},\"placed\":{},\"position\":{},\"orientation\":{}}}",
+            self.id,
+            self.is_placed(),
+            position,
+            orientation
+        )
+    }
+
+    fn as_csv(&self) -> String {
+        let (x, y, orientation) = match self.position.zip(self.orientation) {
+            Some((position, orientation)) => (
+                position.x.to_string(),
+                position.y.to_string(),
+                orientation.as_str(),
+            ),
+
+            None => (String::new(), String::new(), ""),
+        };
+
+        format!(
+            "{},{},{},{},{}",
+            self.id,
+            self.is_placed(),
+            x,
+            y,
+            orientation
+        )
+    }
+
+    fn is_placed(&self) -> bool {
+        self.position.is_some() && self.orientation.is_some()
+    }
+}
+
+// :: ---
+
+/// A report template: plain text, with placeholders in braces.
+///
+/// The placeholders are `{x}`, `{y}`, `{f}` (or `{orientation}`), `{id}` and
+/// `{placed}`; literal braces are written as `{{` and `}}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    X,
+    Y,
+    Orientation,
+    Id,
+    Placed,
+}
+
+impl Template {
+    pub fn parse(plaintext: &str) -> Result<Template, String> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut characters = plaintext.chars().peekable();
+
+        while let Some(character) = characters.next() {
+            match character {
+                '{' if characters.peek() == Some(&'{') => {
+                    characters.next();
+                    text.push('{');
+                }
+
+                '}' if characters.peek() == Some(&'}') => {
+                    characters.next();
+                    text.push('}');
+                }
+
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for character in characters.by_ref() {
+                        if character == '}' {
+                            closed = true;
+                            break;
+                        }
+
+                        name.push(character);
+                    }
+
+                    if !closed {
+                        return Err("Report template has an unmatched {.".to_string());
+                    }
+
+                    let segment = match name.trim().to_lowercase().as_str() {
+                        "x" => Segment::X,
+                        "y" => Segment::Y,
+                        "f" | "orientation" => Segment::Orientation,
+                        "id" => Segment::Id,
+                        "placed" => Segment::Placed,
+                        _ => {
+                            return Err(format!(
+                                "Report template placeholder {{{}}} was not recognized.",
+                                name
+                            ))
+                        }
+                    };
+
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+
+                    segments.push(segment);
+                }
+
+                '}' => return Err("Report template has an unmatched }.".to_string()),
+                _ => text.push(character),
+            }
+        }
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Ok(Template { segments })
+    }
+
+    fn fill(&self, status: &Status) -> String {
+        let (position, orientation) = (status.position, status.orientation);
+
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::X => position
+                    .map(|position| position.x.to_string())
+                    .unwrap_or_default(),
+                Segment::Y => position
+                    .map(|position| position.y.to_string())
+                    .unwrap_or_default(),
+                Segment::Orientation => orientation
+                    .map(|orientation| orientation.as_str().to_string())
+                    .unwrap_or_default(),
+                Segment::Id => status.id.to_string(),
+                Segment::Placed => status.is_placed().to_string(),
+            })
+            .collect()
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placed() -> Status {
+        Status {
+            id: 7,
+            position: Some(Position { x: 0, y: 1 }),
+            orientation: Some(Orientation::North),
+        }
+    }
+
+    fn unplaced() -> Status {
+        Status {
+            id: 7,
+            position: None,
+            orientation: None,
+        }
+    }
+
+    #[test]
+    fn statuses_are_formatted_correctly() {
+        assert_eq!(
+            placed().format(&ReportFormat::Plain),
+            Ok("0,1,NORTH".to_string())
+        );
+        assert_eq!(
+            placed().format(&ReportFormat::Json),
+            Ok(
+                r#"{"id":7,"placed":true,"position":{"x":0,"y":1},"orientation":"NORTH"}"#
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            placed().format(&ReportFormat::Csv),
+            Ok("7,true,0,1,NORTH".to_string())
+        );
+    }
+
+    #[test]
+    fn unplaced_robots_are_only_described_by_machine_readable_formats() {
+        assert_eq!(
+            unplaced().format(&ReportFormat::Json),
+            Ok(r#"{"id":7,"placed":false,"position":null,"orientation":null}"#.to_string())
+        );
+        assert_eq!(
+            unplaced().format(&ReportFormat::Csv),
+            Ok("7,false,,,".to_string())
+        );
+
+        let template = ReportFormat::Template(Template::parse("{x}").unwrap());
+        assert!(unplaced().format(&ReportFormat::Plain).is_err());
+        assert!(unplaced().format(&template).is_err());
+    }
+
+    #[test]
+    fn templates_are_filled_in() {
+        let template = Template::parse("{x}:{y} facing {f} ({{robot {ID}}})").unwrap();
+        assert_eq!(
+            placed().format(&ReportFormat::Template(template)),
+            Ok("0:1 facing NORTH ({robot 7})".to_string())
+        );
+
+        assert!(Template::parse("{z}").is_err());
+        assert!(Template::parse("{x").is_err());
+        assert!(Template::parse("x}").is_err());
+    }
+}
@@ -1,13 +1,48 @@
 #![allow(clippy::unused_unit)]
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use wasm_bindgen::prelude::*;
 
+use super::builtins;
+use super::command::{Command, CommandRegistry, JsCommand};
 use super::common::*;
-use super::instruction::Instruction;
+use super::events::{Event, JsSubscriber, Subscriber};
+use super::expression::Expr;
+use super::instruction::{Condition, Instruction};
+use super::program::{Op, Program};
+use super::render;
+use super::replay;
+use super::report::{ReportFormat, Status, Template};
+use super::script;
+use super::stats::{Stats, StatsTracker};
 use super::tabletop::Tabletop;
+use super::trail::{Trail, TrailEntry};
 
 // :: ---
 
+/// The default number of steps a single evaluation is allowed to take,
+/// before it is deemed to be runaway and aborted.
+pub const DEFAULT_STEP_LIMIT: u32 = 100_000;
+
+/// How deeply procedure calls may be nested, before the recursion is deemed
+/// to be runaway and aborted.
+pub const CALL_DEPTH_LIMIT: usize = 64;
+
+/// How deeply blocks may be nested within each other while running, counting
+/// the bodies of procedures being called, before the evaluation is aborted.
+pub const NESTING_DEPTH_LIMIT: usize = 256;
+
+/// Why a robot cannot move any further, once it is at the very end of the range
+/// of a coordinate (which only an unbounded, or very large, Tabletop allows).
+const OUT_OF_RANGE: &str = "Position would be out of the range of coordinates.";
+
+/// The id the next Robot created gets.
+static NEXT_ROBOT_ID: AtomicU32 = AtomicU32::new(1);
+
 /// A Robot is a representation of a robot that can be placed on top of a Tabletop.
 #[wasm_bindgen]
 pub struct Robot {
@@ -30,6 +65,29 @@ pub struct Robot {
     tabletop: Tabletop,
     position: Option<Position>,
     orientation: Option<Orientation>,
+
+    /// Tells robots apart in reports; unique per process, unless changed.
+    id: u32,
+    report_format: ReportFormat,
+
+    step_limit: u32,
+    move_policy: MovePolicy,
+    procedures: HashMap<String, Rc<Procedure>>,
+    variables: HashMap<String, Coordinate>,
+    commands: CommandRegistry,
+    subscribers: Vec<Box<dyn Subscriber>>,
+    stats: StatsTracker,
+    trail: Trail,
+
+    /// Movement is free unless an energy model is enabled.
+    energy_model: Option<EnergyModel>,
+    charge: u32,
+
+    /// Whether the last evaluation was rejected by an `EXPECT` that did not hold.
+    expectation_failed: bool,
+
+    /// Whether the trail, and the positions counted as visited, are kept up to date.
+    recording: bool,
 }
 
 #[wasm_bindgen]
@@ -46,35 +104,302 @@ impl Robot {
             tabletop: tabletop.clone(),
             position: None,
             orientation: None,
+            id: NEXT_ROBOT_ID.fetch_add(1, Ordering::Relaxed),
+            report_format: ReportFormat::default(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            move_policy: MovePolicy::AllOrNothing,
+            procedures: HashMap::new(),
+            variables: HashMap::new(),
+            commands: CommandRegistry::default(),
+            subscribers: vec![],
+            stats: StatsTracker::default(),
+            trail: Trail::default(),
+            energy_model: None,
+            charge: 0,
+            expectation_failed: false,
+            recording: true,
         })
     }
 
-    /// Attempts to translate a provided plaintext command, and executes the respective
-    /// operation/s if a known `Instruction` could be discerned.
+    /// Attempts to translate a provided plaintext command (or several, separated
+    /// by `;`), and executes the respective operation/s if a known `Instruction`
+    /// could be discerned (see `evaluate`).
     #[wasm_bindgen]
     pub fn evaluate_command(&mut self, command: &str) -> Result<JsValue, String> {
-        let instruction_result = Instruction::parse(command);
-        if let Err(message) = instruction_result {
-            return Err(message);
+        self.evaluate(command).map(to_js_output)
+    }
+
+    /// Runs a line of commands like `evaluate_command`, returning what each of
+    /// them came to as `[{ output, error }, ...]` (with either field `null`), so
+    /// that outputs are not lost when another command on the line is rejected.
+    #[wasm_bindgen(js_name = evaluate_statements)]
+    pub fn evaluate_statements_to_js(&mut self, line: &str) -> js_sys::Array {
+        self.evaluate_statements(line)
+            .into_iter()
+            .map(|result| {
+                let (output, error) = match result {
+                    Ok(output) => (to_js_output(output), JsValue::NULL),
+                    Err(message) => (JsValue::NULL, JsValue::from_str(&message)),
+                };
+
+                let object = js_sys::Object::new();
+                let _ = js_sys::Reflect::set(&object, &JsValue::from_str("output"), &output);
+                let _ = js_sys::Reflect::set(&object, &JsValue::from_str("error"), &error);
+                JsValue::from(object)
+            })
+            .collect()
+    }
+
+    /// Translates a plaintext script (potentially spanning several lines and
+    /// containing multi-line blocks), and executes each of its instructions in order.
+    ///
+    /// Evaluation stops at the first instruction that fails.
+    #[wasm_bindgen]
+    pub fn evaluate_script(&mut self, script: &str) -> Result<JsValue, String> {
+        self.run_script(script)
+            .map(|outputs| to_js_output(join_outputs(outputs)))
+    }
+
+    /// Runs a compiled `Program` (see `Program::compile`), as `evaluate_script`
+    /// would run the script it was compiled from.
+    #[wasm_bindgen]
+    pub fn evaluate_program(&mut self, program: &Program) -> Result<JsValue, String> {
+        self.run_program(program)
+            .map(|outputs| to_js_output(join_outputs(outputs)))
+    }
+
+    /// Runs a plaintext script like `evaluate_script`, but carries on past any
+    /// `EXPECT` that does not hold, returning
+    /// `{ outputs: [...], failures: ["Line 3: Expectation failed: ..."], error }`,
+    /// where `error` is whatever stopped the script early (or `null`).
+    #[wasm_bindgen(js_name = check_script)]
+    pub fn check_script_to_js(&mut self, script: &str) -> Result<JsValue, String> {
+        let outcome = self.check_script(script)?;
+        let to_array = |lines: &[String]| {
+            lines
+                .iter()
+                .map(|line| JsValue::from_str(line))
+                .collect::<js_sys::Array>()
+        };
+
+        let object = js_sys::Object::new();
+        let fields: [(&str, JsValue); 3] = [
+            ("outputs", to_array(&outcome.outputs).into()),
+            ("failures", to_array(&outcome.failures).into()),
+            ("error", to_js_output(outcome.error)),
+        ];
+
+        for (name, value) in fields.iter() {
+            let _ = js_sys::Reflect::set(&object, &JsValue::from_str(name), value);
         }
 
-        // :: ---
-        match instruction_result.unwrap() {
-            Instruction::Place { x, y, orientation } => {
-                let position = Position { x, y };
-                self.place_on_tabletop(position, orientation)
-            }
+        Ok(object.into())
+    }
+
+    /// Sets the maximum number of steps a single evaluation may take.
+    ///
+    /// Every instruction executed counts as a step, as does every pass through
+    /// a block. This keeps a runaway script from hanging the thread it runs on.
+    #[wasm_bindgen]
+    pub fn set_step_limit(&mut self, limit: u32) {
+        self.step_limit = limit;
+    }
+
+    /// Sets how multi-step movements (e.g. `MOVE 3`) are handled when something
+    /// is in the way: either `all-or-nothing` (the default), or `as-far-as-possible`.
+    #[wasm_bindgen]
+    pub fn set_move_policy(&mut self, policy: &str) -> Result<(), String> {
+        self.move_policy = MovePolicy::parse(policy)?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_id(&mut self, id: u32) {
+        self.id = id;
+    }
+
+    /// Sets the format plain `REPORT`s use: either `plain` (the default, e.g.
+    /// `0,1,NORTH`), `json`, or `csv` (see `ReportFormat`).
+    #[wasm_bindgen]
+    pub fn set_report_format(&mut self, format: &str) -> Result<(), String> {
+        self.report_format = ReportFormat::parse(format)?;
+        Ok(())
+    }
+
+    /// Makes plain `REPORT`s fill in a template instead, e.g. `{x}:{y} facing {f}`
+    /// (see `Template` for the placeholders available).
+    #[wasm_bindgen]
+    pub fn set_report_template(&mut self, template: &str) -> Result<(), String> {
+        self.report_format = ReportFormat::Template(Template::parse(template)?);
+        Ok(())
+    }
+
+    /// Registers a JS function to handle a custom command, e.g. `ZIGZAG 3`.
+    ///
+    /// The function is called with the command arguments and a snapshot of the
+    /// robot state, and may return commands for the robot to run in turn
+    /// (see `JsCommand` for the details).
+    #[wasm_bindgen]
+    pub fn register_command(
+        &mut self,
+        keyword: &str,
+        handler: js_sys::Function,
+    ) -> Result<(), String> {
+        self.register(keyword, JsCommand::new(handler))
+    }
+
+    /// Registers a JS callback to be called with every event this Robot emits
+    /// (e.g. `{ type: "Moved", from: { x: 0, y: 0 }, to: { x: 0, y: 1 } }`).
+    #[wasm_bindgen]
+    pub fn subscribe(&mut self, callback: js_sys::Function) {
+        self.add_subscriber(JsSubscriber::new(callback));
+    }
+
+    /// A snapshot of this Robot's running statistics.
+    #[wasm_bindgen]
+    pub fn stats(&self) -> Stats {
+        self.stats.stats()
+    }
+
+    /// Clears this Robot's statistics, leaving it where it is on the Tabletop.
+    #[wasm_bindgen]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset(self.position);
+    }
+
+    /// Every position (and orientation) this Robot has been in, in order,
+    /// as an array of `{ x, y, orientation }` objects; a move shows up as just
+    /// the position it ended on (see `Trail`).
+    #[wasm_bindgen(js_name = trail)]
+    pub fn trail_to_js(&self) -> JsValue {
+        self.trail
+            .entries()
+            .iter()
+            .map(|entry| {
+                let object = js_sys::Object::new();
+                let fields: [(&str, JsValue); 3] = [
+                    ("x", (entry.position.x as f64).into()),
+                    ("y", (entry.position.y as f64).into()),
+                    ("orientation", entry.orientation.as_str().into()),
+                ];
+
+                for (name, value) in fields.iter() {
+                    let _ = js_sys::Reflect::set(&object, &JsValue::from_str(name), value);
+                }
+
+                JsValue::from(object)
+            })
+            .collect::<js_sys::Array>()
+            .into()
+    }
+
+    /// How many times this Robot has visited each position, as an array of rows
+    /// (from `y = 0` upwards) of visit counts.
+    #[wasm_bindgen(js_name = heatmap)]
+    pub fn heatmap_to_js(&self) -> Result<JsValue, String> {
+        Ok(self
+            .heatmap()?
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(JsValue::from)
+                    .collect::<js_sys::Array>()
+            })
+            .collect::<js_sys::Array>()
+            .into())
+    }
+
+    /// Forgets where this Robot has been, leaving it where it is on the Tabletop.
+    #[wasm_bindgen]
+    pub fn clear_trail(&mut self) {
+        self.trail.clear();
+    }
 
-            // :: TODO
-            Instruction::Obstacle { x, y } => self.add_obstacle_to_tabletop(Position { x, y }),
+    /// Sets whether this Robot keeps track of where it has been: its trail (and
+    /// so its heatmap), and the positions counted as `cells_visited`. It does,
+    /// unless told otherwise; without it, a Robot takes up no more memory however
+    /// long it runs (e.g. for an endless stream of commands).
+    ///
+    /// Stopping forgets what was tracked so far; the other stats are unaffected.
+    #[wasm_bindgen]
+    pub fn set_recording(&mut self, enabled: bool) {
+        self.recording = enabled;
+        self.stats.set_recording(enabled);
 
-            Instruction::Move => self.move_forward(),
-            Instruction::Left => self.turn_left(),
-            Instruction::Right => self.turn_right(),
-            Instruction::Report => self.report_status(),
+        if !enabled {
+            self.trail.clear();
         }
     }
 
+    /// Draws the Tabletop and this Robot as plain text (see `render::ascii`),
+    /// optionally marking the positions the Robot has visited.
+    #[wasm_bindgen]
+    pub fn render_ascii(&self, show_trail: bool) -> Result<String, String> {
+        let robot = self.position.zip(self.orientation);
+        let trail = match show_trail {
+            true => Some(&self.trail),
+            false => None,
+        };
+
+        render::ascii(&self.tabletop, robot, trail)
+    }
+
+    /// Draws the Tabletop and this Robot as a standalone SVG image (see
+    /// `render::svg`), optionally with the path the Robot has taken.
+    #[wasm_bindgen]
+    pub fn render_svg(&self, show_trail: bool) -> Result<String, String> {
+        let robot = self.position.zip(self.orientation);
+        let trail = match show_trail {
+            true => Some(&self.trail),
+            false => None,
+        };
+
+        render::svg(&self.tabletop, robot, trail)
+    }
+
+    /// Runs `script`, returning an animated GIF with a frame for each command
+    /// it runs (see `replay::record`).
+    #[wasm_bindgen]
+    pub fn replay_gif(
+        &mut self,
+        script: &str,
+        cell_size: u32,
+        frame_delay: u32,
+    ) -> Result<Vec<u8>, String> {
+        replay::record(self, script, cell_size, frame_delay)
+    }
+
+    /// Makes movement cost energy: each unit moved costs `move_cost`, and each
+    /// turn `turn_cost`, out of a charge of (at most) `capacity`. The robot starts
+    /// out fully charged, and is recharged by stopping on a charging station.
+    #[wasm_bindgen]
+    pub fn enable_energy(&mut self, capacity: u32, move_cost: u32, turn_cost: u32) {
+        self.energy_model = Some(EnergyModel {
+            capacity,
+            move_cost,
+            turn_cost,
+        });
+
+        self.charge = capacity;
+    }
+
+    /// Makes movement free again.
+    #[wasm_bindgen]
+    pub fn disable_energy(&mut self) {
+        self.energy_model = None;
+    }
+
+    /// The remaining charge, if the energy model is enabled.
+    #[wasm_bindgen(getter)]
+    pub fn charge(&self) -> Option<u32> {
+        self.energy_model.map(|_| self.charge)
+    }
+
     /// Places a Robot instance on top of a Tabletop at the provided position,
     /// facing the provided orientation.
     ///
@@ -87,13 +412,18 @@ impl Robot {
         &mut self,
         position: Position,
         orientation: Orientation,
-    ) -> Result<JsValue, String> {
+    ) -> Result<Option<String>, String> {
         match self.tabletop.request_place(&position) {
             Ok(_) => {
                 self.position = Some(position);
                 self.orientation = Some(orientation);
+                self.recharge_at(position);
+                self.emit(Event::Placed {
+                    position,
+                    orientation,
+                });
 
-                Ok(JsValue::NULL)
+                Ok(None)
             }
 
             Err(message) => Err(format!(
@@ -107,15 +437,20 @@ impl Robot {
     ///
     /// This will not allow placing an obstacle on the current robot position,
     /// if the robot is currently placed on the tabletop.
-    fn add_obstacle_to_tabletop(&mut self, position: Position) -> Result<JsValue, String> {
+    fn add_obstacle_to_tabletop(&mut self, position: Position) -> Result<Option<String>, String> {
         if self.is_placed() && self.position.unwrap() == position {
             return Err("Cannot place an obstacle right on top of the robot.".to_string());
         }
 
         match self.tabletop.add_obstacle(position.x, position.y) {
-            Ok(()) => Ok(JsValue::from_str(
-                format!("Obstacle placed at {}, {}.", position.x, position.y).as_str(),
-            )),
+            Ok(()) => {
+                self.emit(Event::ObstacleAdded { position });
+                Ok(Some(format!(
+                    "Obstacle placed at {}, {}.",
+                    position.x, position.y
+                )))
+            }
+
             Err(message) => Err(format!(
                 "Could not place obstacle at that position: {}",
                 message
@@ -124,102 +459,315 @@ impl Robot {
     }
 
     /// Moves the robot forward 1 unit, in the direction it is currently oriented in.
-    ///
-    /// This function takes advantage of the fact that a movement in the context of
-    /// this problem is the same as (re-)placing the robot in the arrival position,
-    /// except that it should not be possible to do so if the robot has not been
-    /// yet placed prior.
-    fn move_forward(&mut self) -> Result<JsValue, String> {
+    fn move_forward(&mut self) -> Result<Option<String>, String> {
         if !self.is_placed() {
             return Err("Robot is not placed; discarding instruction".to_string());
         }
 
-        // :: ---
+        self.move_towards(self.orientation.unwrap())
+    }
+
+    /// Moves the robot back 1 unit, against the direction it is oriented in (without turning).
+    fn move_back(&mut self) -> Result<Option<String>, String> {
+        if !self.is_placed() {
+            return Err("Robot is not placed; discarding instruction.".to_string());
+        }
 
-        let mut target_position = self.position.unwrap();
-        match self.orientation.as_ref().unwrap() {
-            Orientation::North => target_position.y += 1,
-            Orientation::South => target_position.y -= 1,
-            Orientation::East => target_position.x += 1,
-            Orientation::West => target_position.x -= 1,
+        self.move_towards(self.orientation.unwrap().reversed())
+    }
+
+    /// Moves the robot sideways 1 unit, to its left or right (without turning).
+    fn strafe(&mut self, to_the_left: bool) -> Result<Option<String>, String> {
+        if !self.is_placed() {
+            return Err("Robot is not placed; discarding instruction.".to_string());
         }
 
+        let orientation = self.orientation.unwrap();
+        match to_the_left {
+            true => self.move_towards(orientation.turned_left()),
+            false => self.move_towards(orientation.turned_right()),
+        }
+    }
+
+    /// Moves the (already placed) robot 1 unit in the provided direction,
+    /// regardless of where it is facing.
+    ///
+    /// This function takes advantage of the fact that a movement in the context of
+    /// this problem is the same as (re-)placing the robot in the arrival position,
+    /// except that it should not be possible to do so if the robot has not been
+    /// yet placed prior.
+    fn move_towards(&mut self, direction: Orientation) -> Result<Option<String>, String> {
+        let move_cost = self.energy_model.map_or(0, |model| model.move_cost);
+        let target_position = match self.position.unwrap().neighbour(direction) {
+            Some(position) => position,
+            None => return Err(format!("Robot cannot be moved: {}", OUT_OF_RANGE)),
+        };
+
         let can_move = self.tabletop.request_place(&target_position);
         match can_move {
             Ok(_) => {
+                let from = self.position.unwrap();
+                self.charge = self.spend_energy(self.charge, move_cost, "move")?;
                 self.position = Some(target_position);
-                Ok(JsValue::NULL)
+                self.recharge_at(target_position);
+                self.emit(Event::Moved {
+                    from,
+                    to: target_position,
+                });
+
+                Ok(None)
             }
 
-            Err(message) => Err(format!("Robot cannot be moved: {}", message)),
+            Err(message) => {
+                self.emit_obstruction(target_position, &message);
+                Err(format!("Robot cannot be moved: {}", message))
+            }
         }
     }
 
-    /// Re-orients the Robot by turning it to the left.
-    fn turn_left(&mut self) -> Result<JsValue, String> {
+    /// Moves the robot forward `steps` units, in the direction it is currently oriented in.
+    ///
+    /// Each unit is checked the same way a single `MOVE` is. If something is in the way,
+    /// `policy` decides whether the robot stays put (and the movement is rejected),
+    /// or stops at the last free position. Either way, the output states how many
+    /// units the robot actually moved.
+    ///
+    /// Each unit moved past the first takes a step of its own, as separate `MOVE`s
+    /// would; but what is in the way is found without going over every unit.
+    fn move_by(
+        &mut self,
+        steps: Coordinate,
+        policy: MovePolicy,
+        evaluation: &mut Evaluation,
+    ) -> Result<Option<String>, String> {
         if !self.is_placed() {
             return Err("Robot is not placed; discarding instruction.".to_string());
         }
 
+        if steps < 0 {
+            return Err("Robot cannot be moved a negative number of units.".to_string());
+        }
+
         // :: ---
 
-        let new_orientation = match self.orientation.as_ref().unwrap() {
-            Orientation::North => Orientation::West,
-            Orientation::West => Orientation::South,
-            Orientation::South => Orientation::East,
-            Orientation::East => Orientation::North,
+        let orientation = self.orientation.unwrap();
+        let from = self.position.unwrap();
+        let clear = self.tabletop.clear_distance(from, orientation, steps);
+        let (moved, charge) = self.energy_reach(from, orientation, clear);
+
+        if moved < steps {
+            // :: Running out of energy is handled the same way an obstruction
+            //    is; the obstruction comes first, if both are on the same unit.
+            let message = match moved == clear {
+                true => match from.moved_by(orientation, clear + 1) {
+                    Some(position) => {
+                        let message = self.tabletop.request_place(&position).unwrap_err();
+                        self.emit_obstruction(position, &message);
+                        message
+                    }
+
+                    // :: Running past the range of a coordinate is handled the same way
+                    //    an obstruction is, except that there is nothing to report it at.
+                    None => OUT_OF_RANGE.to_string(),
+                },
+
+                false => out_of_energy("move"),
+            };
+
+            if policy == MovePolicy::AllOrNothing {
+                return Err(format!(
+                    "Robot cannot be moved {} units: {}",
+                    steps, message
+                ));
+            }
+        }
+
+        if moved > 1 {
+            self.take_steps(u32::try_from(moved - 1).unwrap_or(u32::MAX), evaluation)?;
+        }
+
+        self.charge = charge;
+
+        if moved > 0 {
+            let target_position = from.moved_by(orientation, moved).unwrap();
+            self.position = Some(target_position);
+            self.emit(Event::Moved {
+                from,
+                to: target_position,
+            });
+        }
+
+        Ok(Some(format!("Moved {} of {} units.", moved, steps)))
+    }
+
+    /// How far (up to `clear` units) the robot has the energy to move from `from`
+    /// towards `direction`, and the charge it would have left; being refilled by
+    /// any charging station it passes along the way.
+    fn energy_reach(
+        &self,
+        from: Position,
+        direction: Orientation,
+        clear: Coordinate,
+    ) -> (Coordinate, u32) {
+        let model = match self.energy_model {
+            Some(model) => model,
+            None => return (clear, self.charge),
         };
 
-        self.orientation = Some(new_orientation);
+        let mut stations = self
+            .tabletop
+            .charging_stations()
+            .iter()
+            .filter_map(|station| from.units_towards(*station, direction))
+            .filter(|units| (1..=clear).contains(units))
+            .collect::<Vec<Coordinate>>();
+
+        stations.sort_unstable();
+
+        let cost = Coordinate::from(model.move_cost);
+        let (mut moved, mut charge) = (0, self.charge);
+
+        for stop in stations.iter().copied().chain(Some(clear)) {
+            let affordable = match cost {
+                0 => Coordinate::MAX,
+                cost => Coordinate::from(charge) / cost,
+            };
+
+            let units = stop - moved;
+            if units > affordable {
+                return (moved + affordable, charge - (affordable * cost) as u32);
+            }
+
+            moved = stop;
+            charge = match stations.binary_search(&stop) {
+                Ok(_) => model.capacity,
+                Err(_) => charge - (units * cost) as u32,
+            };
+        }
+
+        (moved, charge)
+    }
+
+    /// Re-orients the Robot by turning it to the left.
+    fn turn_left(&mut self) -> Result<Option<String>, String> {
+        if !self.is_placed() {
+            return Err("Robot is not placed; discarding instruction.".to_string());
+        }
+
+        // :: ---
 
-        Ok(JsValue::NULL)
+        self.reorient(self.orientation.unwrap().turned_left())?;
+
+        Ok(None)
     }
 
     /// Re-orients the Robot by turning it to the right.
-    fn turn_right(&mut self) -> Result<JsValue, String> {
+    fn turn_right(&mut self) -> Result<Option<String>, String> {
         if !self.is_placed() {
             return Err("Robot is not placed; discarding instruction.".to_string());
         }
 
         // :: ---
 
-        let new_orientation = match self.orientation.as_ref().unwrap() {
-            Orientation::North => Orientation::East,
-            Orientation::East => Orientation::South,
-            Orientation::South => Orientation::West,
-            Orientation::West => Orientation::North,
-        };
+        self.reorient(self.orientation.unwrap().turned_right())?;
+
+        Ok(None)
+    }
+
+    /// Re-orients the Robot by turning it around.
+    fn turn_around(&mut self) -> Result<Option<String>, String> {
+        if !self.is_placed() {
+            return Err("Robot is not placed; discarding instruction.".to_string());
+        }
 
-        self.orientation = Some(new_orientation);
+        // :: ---
+
+        self.reorient(self.orientation.unwrap().reversed())?;
 
-        Ok(JsValue::NULL)
+        Ok(None)
     }
 
-    fn report_status(&self) -> Result<JsValue, String> {
+    /// Re-orients the Robot to face the provided orientation.
+    fn face(&mut self, orientation: Orientation) -> Result<Option<String>, String> {
         if !self.is_placed() {
             return Err("Robot is not placed; discarding instruction.".to_string());
         }
 
         // :: ---
 
-        let current_position = self.position.unwrap();
+        self.reorient(orientation)?;
 
-        // :: Unfortunately, `wasm_bindgen` does not yet support enums that have values
-        //    associated to each variant aside from integers. We have to perform
-        //    conversion ourselves in this manner.
-        let plaintext_orientation = match self.orientation.unwrap() {
-            Orientation::North => "NORTH",
-            Orientation::East => "EAST",
-            Orientation::South => "SOUTH",
-            Orientation::West => "WEST",
-        };
+        Ok(None)
+    }
 
-        let report_message = format!(
-            "{},{},{}",
-            current_position.x, current_position.y, plaintext_orientation
-        );
+    /// Turns the (already placed) robot to face the provided orientation.
+    fn reorient(&mut self, orientation: Orientation) -> Result<(), String> {
+        let turn_cost = self.energy_model.map_or(0, |model| model.turn_cost);
+        self.charge = self.spend_energy(self.charge, turn_cost, "turn")?;
+
+        let from = self.orientation.unwrap();
+        self.orientation = Some(orientation);
+        self.emit(Event::Turned {
+            from,
+            to: orientation,
+        });
+
+        Ok(())
+    }
+
+    /// Deducts `cost` from `charge` (if the energy model is enabled), returning
+    /// what is left. The robot refuses to `action` if there isn't enough charge.
+    fn spend_energy(&self, charge: u32, cost: u32, action: &str) -> Result<u32, String> {
+        if self.energy_model.is_none() {
+            return Ok(charge);
+        }
+
+        charge
+            .checked_sub(cost)
+            .ok_or_else(|| out_of_energy(action))
+    }
+
+    /// Refills the robot's charge, if `position` is a charging station.
+    fn recharge_at(&mut self, position: Position) {
+        if let (true, Some(model)) = (
+            self.tabletop.is_charging_station(&position),
+            self.energy_model,
+        ) {
+            self.charge = model.capacity;
+        }
+    }
+
+    /// Registers a charging station on the tabletop.
+    fn add_charging_station_to_tabletop(
+        &mut self,
+        position: Position,
+    ) -> Result<Option<String>, String> {
+        match self.tabletop.add_charging_station(position.x, position.y) {
+            Ok(()) => {
+                if self.position == Some(position) {
+                    self.recharge_at(position);
+                }
+
+                Ok(Some(format!(
+                    "Charging station placed at {}, {}.",
+                    position.x, position.y
+                )))
+            }
+
+            Err(message) => Err(format!(
+                "Could not place charging station at that position: {}",
+                message
+            )),
+        }
+    }
+
+    /// Reports this Robot's status, in its chosen report format.
+    fn report_status(&self) -> Result<Option<String>, String> {
+        self.report_as(&self.report_format)
+    }
 
-        Ok(JsValue::from_str(&report_message))
+    fn report_as(&self, format: &ReportFormat) -> Result<Option<String>, String> {
+        self.status().format(format).map(Some)
     }
 
     /// Has this Robot successfully been placed on a Tabletop?
@@ -232,264 +780,2205 @@ impl Robot {
     }
 }
 
-// :: ---
+/// Rust-facing API; these return plain Rust types, rather than `JsValue`s
+/// (which are only usable when running on a `wasm32` target).
+impl Robot {
+    /// Translates and executes a line of plaintext commands, separated by `;`
+    /// (e.g. `PLACE 0,0,NORTH; MOVE; REPORT`), returning any output produced.
+    ///
+    /// Each command is run as if it were on a line of its own, so that one being
+    /// rejected does not stop the others; if any are, they are reported (as
+    /// `Statement N: ...`) instead. Anything after a `#` is a comment.
+    pub fn evaluate(&mut self, line: &str) -> Result<Option<String>, String> {
+        let mut results = self.evaluate_statements(line);
+        if results.len() == 1 {
+            return results.remove(0);
+        }
 
-#[cfg(test)]
-mod tests {
-    use crate::components::tabletop;
+        let mut outputs = vec![];
+        let mut rejections = vec![];
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(output) => outputs.extend(output),
+                Err(message) => rejections.push(format!("Statement {}: {}", index + 1, message)),
+            }
+        }
 
-    use super::*;
+        match rejections.is_empty() {
+            true => Ok(join_outputs(outputs)),
+            false => Err(rejections.join("\n")),
+        }
+    }
 
-    #[test]
-    fn robot_can_be_created() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
-        assert!(Robot::create(&tabletop).is_ok());
+    /// Like `evaluate`, but with what each command on the line came to, in order.
+    pub fn evaluate_statements(&mut self, line: &str) -> Vec<Result<Option<String>, String>> {
+        script::split_commands(line)
+            .into_iter()
+            .map(|command| self.evaluate_statement(command))
+            .collect()
     }
 
-    #[test]
-    fn robot_has_empty_position_and_orientation_on_creation() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
-        let robot = Robot::create(&tabletop).unwrap();
+    fn evaluate_statement(&mut self, command: &str) -> Result<Option<String>, String> {
+        let instruction = match Instruction::parse_with(command, &self.commands) {
+            Ok(instruction) => instruction,
+            Err(message) => {
+                let keyword = command.split_whitespace().next().unwrap_or_default();
+                self.emit(Event::CommandIgnored {
+                    command: keyword.to_uppercase(),
+                    reason: message.clone(),
+                });
+
+                return Err(message);
+            }
+        };
 
-        assert!(robot.position.is_none());
-        assert!(robot.orientation.is_none());
+        self.run_instruction(&instruction)
     }
 
-    #[test]
-    fn robot_is_not_placed_on_creation() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
-        let robot = Robot::create(&tabletop).unwrap();
+    /// Translates and executes a plaintext script, returning every output produced.
+    ///
+    /// The step limit applies to the script as a whole.
+    pub fn run_script(&mut self, script: &str) -> Result<Vec<String>, String> {
+        let instructions = self
+            .parse_script_to_run(script)?
+            .into_iter()
+            .map(|(_, instruction)| instruction)
+            .collect::<Vec<Instruction>>();
+
+        let mut evaluation = Evaluation::new(self.step_limit);
+        let result = self.execute_all(&instructions, &mut evaluation);
+        self.expectation_failed = evaluation.expectation_failed;
+        result?;
+
+        Ok(evaluation.outputs)
+    }
 
-        assert!(!robot.is_placed());
+    /// Runs a compiled `Program`, returning every output produced.
+    ///
+    /// The step limit applies to the program as a whole, counted the same way
+    /// as for the script it was compiled from.
+    pub fn run_program(&mut self, program: &Program) -> Result<Vec<String>, String> {
+        let mut evaluation = Evaluation::new(self.step_limit);
+        let result = self.run_code(program, 0, &mut evaluation);
+        self.expectation_failed = evaluation.expectation_failed;
+        result?;
+
+        Ok(evaluation.outputs)
     }
 
-    #[test]
-    fn robot_tabletop_reference_is_equivalent_to_source() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
-        let robot = Robot::create(&tabletop).unwrap();
+    /// Compiles a plaintext script into a `Program`, recognizing this Robot's
+    /// commands (and procedures), without running it.
+    pub fn compile(&self, script: &str) -> Result<Program, String> {
+        Ok(Program::from_instructions(&self.parse_script(script)?))
+    }
 
-        assert_eq!(tabletop, robot.tabletop);
+    /// Translates a plaintext script into `Instruction`s, recognizing this
+    /// Robot's commands (and the procedures it already knows), without running it.
+    pub fn parse_script(&self, script: &str) -> Result<Vec<Instruction>, String> {
+        Ok(self
+            .parse_numbered_script(script)?
+            .into_iter()
+            .map(|(_, instruction)| instruction)
+            .collect())
     }
 
-    #[test]
-    fn robot_is_successfully_placed_on_tabletop() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
-        let mut robot = Robot::create(&tabletop).unwrap();
+    /// Like `parse_script`, but along with the line each instruction starts on.
+    pub fn parse_numbered_script(&self, script: &str) -> Result<Vec<(usize, Instruction)>, String> {
+        script::parse_numbered_calling(script, &self.commands, &|name| {
+            self.procedures.contains_key(name)
+        })
+        .map_err(|error| error.to_string())
+    }
 
-        let result = robot.place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North);
-        assert!(result.is_ok());
-        assert!(robot.is_placed());
+    /// Like `parse_numbered_script`, for a script about to be run; if it can't
+    /// be parsed, the statement at fault is reported as ignored (see
+    /// `Event::CommandIgnored`), as it would be on its own.
+    pub fn parse_script_to_run(
+        &mut self,
+        script: &str,
+    ) -> Result<Vec<(usize, Instruction)>, String> {
+        let parsed = script::parse_numbered_calling(script, &self.commands, &|name| {
+            self.procedures.contains_key(name)
+        });
+
+        parsed.map_err(|error| {
+            let message = error.to_string();
+            self.emit(Event::CommandIgnored {
+                command: error.command,
+                reason: message.clone(),
+            });
+
+            message
+        })
+    }
 
-        assert!(robot.position.is_some());
-        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 3 });
+    /// Translates and executes a plaintext script, carrying on past any `EXPECT`
+    /// that does not hold (see `Robot::expectation_failed`), and stopping at any
+    /// other rejection.
+    ///
+    /// The step limit applies to the script as a whole.
+    pub fn check_script(&mut self, script: &str) -> Result<ScriptOutcome, String> {
+        let instructions = self.parse_script_to_run(script)?;
+
+        let mut evaluation = Evaluation::new(self.step_limit);
+        let mut outcome = ScriptOutcome::default();
+
+        for (line, instruction) in instructions.iter() {
+            evaluation.expectation_failed = false;
+            match self.execute(instruction, &mut evaluation) {
+                Ok(()) => (),
+                Err(message) if evaluation.expectation_failed => {
+                    outcome.failures.push(format!("Line {}: {}", line, message))
+                }
+
+                Err(message) => {
+                    outcome.error = Some(format!("Line {}: {}", line, message));
+                    break;
+                }
+            }
+        }
 
-        assert!(robot.orientation.is_some());
-        assert_eq!(robot.orientation.unwrap(), Orientation::North);
+        outcome.outputs = evaluation.outputs;
+        Ok(outcome)
     }
 
-    #[test]
-    fn robot_is_correctly_oriented_on_tabletop_when_placed() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
-        let mut robot = Robot::create(&tabletop).unwrap();
+    /// Executes a single (already translated) instruction, returning any output
+    /// it produced.
+    pub fn run_instruction(&mut self, instruction: &Instruction) -> Result<Option<String>, String> {
+        let mut evaluation = Evaluation::new(self.step_limit);
+        let result = self.execute(instruction, &mut evaluation);
+        self.expectation_failed = evaluation.expectation_failed;
+        result?;
 
-        assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
-            .is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+        Ok(join_outputs(evaluation.outputs))
+    }
 
-        assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::West)
-            .is_ok());
+    /// Like `run_instruction`, but showing `observer` this Robot right after
+    /// every command run along the way (e.g. each `MOVE` of `REPEAT 4 { MOVE }`),
+    /// whether or not it was carried out.
+    ///
+    /// Each time counts as a step towards the step limit, the same as running
+    /// an instruction does.
+    pub fn run_observed(
+        &mut self,
+        instruction: &Instruction,
+        observer: Observer,
+    ) -> Result<Option<String>, String> {
+        let mut evaluation = Evaluation::new(self.step_limit);
+        evaluation.observer = Some(observer);
+
+        let result = self.execute(instruction, &mut evaluation);
+        self.expectation_failed = evaluation.expectation_failed;
+        result?;
+
+        Ok(join_outputs(evaluation.outputs))
+    }
+
+    /// Whether the last evaluation (e.g. via `evaluate`, or `run_instruction`)
+    /// was rejected because an `EXPECT` did not hold, rather than because a
+    /// command could not be carried out; so that runners can report it and
+    /// carry on.
+    ///
+    /// Only the built-in `EXPECT` can fail this way, whatever other commands'
+    /// rejections say.
+    pub fn expectation_failed(&self) -> bool {
+        self.expectation_failed
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// Everything `REPORT` can describe about this Robot.
+    pub fn status(&self) -> Status {
+        Status {
+            id: self.id,
+            position: self.position,
+            orientation: self.orientation,
+        }
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.orientation
+    }
+
+    pub fn tabletop(&self) -> &Tabletop {
+        &self.tabletop
+    }
+
+    /// Registers `command` to handle `keyword`, for this Robot only.
+    pub fn register<C>(&mut self, keyword: &str, command: C) -> Result<(), String>
+    where
+        C: Command + 'static,
+    {
+        self.commands.register(keyword, command)
+    }
+
+    /// Everywhere this Robot has been, in order (see `Trail`).
+    pub fn trail(&self) -> &[TrailEntry] {
+        self.trail.entries()
+    }
+
+    /// How many times this Robot has visited each position of its Tabletop,
+    /// as rows (from `y = 0` upwards) of visit counts; for Tabletops that can be
+    /// gone over position by position (see `Tabletop::grid_size`), and that are
+    /// no larger than `trail::HEATMAP_SIZE_LIMIT`.
+    pub fn heatmap(&self) -> Result<Vec<Vec<u32>>, String> {
+        let (width, height) = self.tabletop.grid_size()?;
+        self.trail.heatmap(width, height)
+    }
+
+    /// Registers `subscriber` to be notified of every event this Robot emits.
+    pub fn add_subscriber<S>(&mut self, subscriber: S)
+    where
+        S: Subscriber + 'static,
+    {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.stats.record(&event);
+        if self.recording {
+            self.trail.record(&event, self.orientation);
+        }
+
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.notify(&event);
+        }
+    }
+
+    /// Reports why the robot could not move onto `target_position`.
+    fn emit_obstruction(&mut self, target_position: Position, message: &str) {
+        match self.tabletop.is_within_bounds(&target_position) {
+            true => self.emit(Event::MoveBlocked {
+                reason: message.to_string(),
+            }),
+            false => self.emit(Event::FallPrevented {
+                at: target_position,
+            }),
+        }
+    }
+
+    /// Executes a single `Instruction`, collecting any output produced into the evaluation.
+    ///
+    /// Blocks are executed by recursing through this same function, so nested
+    /// instructions behave exactly like top-level ones.
+    fn execute(
+        &mut self,
+        instruction: &Instruction,
+        evaluation: &mut Evaluation,
+    ) -> Result<(), String> {
+        self.take_step(evaluation)?;
+
+        let output = match instruction {
+            Instruction::Repeat { times, body } => {
+                let times = self.resolve(times, evaluation)?;
+                if times < 0 {
+                    return Err("Arguments for command REPEAT were invalid.".to_string());
+                }
+
+                for _ in 0..times {
+                    // :: An iteration counts as a step of its own, so that even
+                    //    loops with empty bodies eventually hit the step limit.
+                    self.take_step(evaluation)?;
+                    self.execute_all(body, evaluation)?;
+                }
+
+                None
+            }
+
+            Instruction::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                match self.check(condition, evaluation)? {
+                    true => self.execute_all(then_branch, evaluation)?,
+                    false => self.execute_all(else_branch, evaluation)?,
+                };
+
+                None
+            }
+
+            Instruction::While { condition, body } => {
+                while self.check(condition, evaluation)? {
+                    self.take_step(evaluation)?;
+                    self.execute_all(body, evaluation)?;
+                }
+
+                None
+            }
+
+            Instruction::Define { name, params, body } => {
+                let procedure = Procedure {
+                    params: params.clone(),
+                    body: Body::Instructions(body.clone()),
+                };
+
+                self.procedures.insert(name.clone(), Rc::new(procedure));
+                None
+            }
+
+            Instruction::Call { name, args } => {
+                self.call_procedure(name, args, evaluation)?;
+                None
+            }
+
+            // :: Bindings made inside a procedure are local to that call;
+            //    anywhere else, they persist across evaluations.
+            Instruction::Let { name, value } => {
+                let value = self.resolve(value, evaluation)?;
+                match evaluation.frames.last_mut() {
+                    Some(frame) => frame.insert(name.clone(), value),
+                    None => self.variables.insert(name.clone(), value),
+                };
+
+                None
+            }
+
+            // :: Everything else is down to the command registered for it; the
+            //    observer (if any) is shown how that went, even if it was rejected.
+            _ => {
+                let result = self.run_command(instruction, evaluation);
+                self.observe(evaluation)?;
+                result?
+            }
+        };
+
+        if let Some(output) = output {
+            evaluation.outputs.push(output);
+        }
+
+        Ok(())
+    }
+
+    /// Shows this Robot, as it is now, to the observer of `evaluation` (if it
+    /// has one); each time takes a step, as running an instruction does.
+    fn observe(&self, evaluation: &mut Evaluation) -> Result<(), String> {
+        if evaluation.observer.is_none() {
+            return Ok(());
+        }
+
+        self.take_step(evaluation)?;
+        match evaluation.observer.as_mut() {
+            Some(observer) => observer(self),
+            None => Ok(()),
+        }
+    }
+
+    /// Executes a block of `Instruction`s (e.g. the body of a loop, or of a
+    /// procedure), in order.
+    ///
+    /// Every block run is nested within those already running, and so takes up
+    /// more of the stack; past `NESTING_DEPTH_LIMIT`, the evaluation is aborted.
+    fn execute_all(
+        &mut self,
+        instructions: &[Instruction],
+        evaluation: &mut Evaluation,
+    ) -> Result<(), String> {
+        if evaluation.depth >= NESTING_DEPTH_LIMIT {
+            return Err(format!(
+                "Nesting depth limit of {} was exceeded; aborting evaluation.",
+                NESTING_DEPTH_LIMIT
+            ));
+        }
+
+        evaluation.depth += 1;
+        let result = instructions
+            .iter()
+            .try_for_each(|instruction| self.execute(instruction, evaluation));
+        evaluation.depth -= 1;
+
+        result
+    }
+
+    /// Runs the operations of a compiled `Program`, starting from `entry`, up
+    /// until an `Op::Return`.
+    ///
+    /// Each run counts as a block towards `NESTING_DEPTH_LIMIT`, as with
+    /// `execute_all`; the blocks laid out within it do not, since they are not
+    /// nested while running.
+    fn run_code(
+        &mut self,
+        program: &Program,
+        entry: usize,
+        evaluation: &mut Evaluation,
+    ) -> Result<(), String> {
+        if evaluation.depth >= NESTING_DEPTH_LIMIT {
+            return Err(format!(
+                "Nesting depth limit of {} was exceeded; aborting evaluation.",
+                NESTING_DEPTH_LIMIT
+            ));
+        }
+
+        evaluation.depth += 1;
+        let result = self.run_ops(program, entry, evaluation);
+        evaluation.depth -= 1;
+
+        result
+    }
+
+    fn run_ops(
+        &mut self,
+        program: &Program,
+        entry: usize,
+        evaluation: &mut Evaluation,
+    ) -> Result<(), String> {
+        let code = program.code();
+
+        // :: How many passes are left of each `REPEAT` in progress, innermost last.
+        let mut counters: Vec<Coordinate> = vec![];
+        let mut at = entry;
+
+        while let Some(op) = code.ops.get(at) {
+            at += 1;
+
+            match *op {
+                Op::Run(index) => self.execute(&code.commands[index], evaluation)?,
+
+                Op::Define(index) => {
+                    self.take_step(evaluation)?;
+
+                    let header = &code.procedures[index];
+                    let procedure = Procedure {
+                        params: header.params.clone(),
+                        body: Body::Compiled {
+                            program: program.clone(),
+                            entry: header.entry,
+                        },
+                    };
+
+                    self.procedures
+                        .insert(header.name.clone(), Rc::new(procedure));
+                }
+
+                Op::Step => self.take_step(evaluation)?,
+
+                Op::Repeat { times } => {
+                    self.take_step(evaluation)?;
+
+                    let times = self.resolve(&code.counts[times], evaluation)?;
+                    if times < 0 {
+                        return Err("Arguments for command REPEAT were invalid.".to_string());
+                    }
+
+                    counters.push(times);
+                }
+
+                Op::Next { exit } => match counters.last_mut() {
+                    Some(0) => {
+                        counters.pop();
+                        at = exit;
+                    }
+
+                    Some(passes) => {
+                        *passes -= 1;
+                        self.take_step(evaluation)?;
+                    }
+
+                    None => return Err("Program is malformed; aborting evaluation.".to_string()),
+                },
+
+                Op::If {
+                    condition,
+                    otherwise,
+                } => {
+                    self.take_step(evaluation)?;
+                    if !self.check(&code.conditions[condition], evaluation)? {
+                        at = otherwise;
+                    }
+                }
+
+                Op::While { condition, exit } => {
+                    match self.check(&code.conditions[condition], evaluation)? {
+                        true => self.take_step(evaluation)?,
+                        false => at = exit,
+                    }
+                }
+
+                Op::Jump(target) => at = target,
+                Op::Return => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_command(
+        &mut self,
+        instruction: &Instruction,
+        evaluation: &mut Evaluation,
+    ) -> Result<Option<String>, String> {
+        let keyword = instruction.keyword();
+        let command = match self.commands.get(keyword) {
+            Some(command) => command,
+            None => {
+                return Err(format!(
+                    "Command {} was not recognized or is malformed.",
+                    keyword
+                ))
+            }
+        };
+
+        let mut context = CommandContext {
+            robot: self,
+            evaluation,
+        };
+
+        let result = command.execute(instruction, &mut context);
+        match &result {
+            Ok(_) => self.stats.record_accepted_command(),
+            Err(message) => self.emit(Event::CommandIgnored {
+                command: keyword.to_string(),
+                reason: message.clone(),
+            }),
+        }
+
+        result
+    }
+
+    /// Runs a procedure previously registered via `DEFINE`, with `args` bound
+    /// to its parameters for the duration of the call.
+    fn call_procedure(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        evaluation: &mut Evaluation,
+    ) -> Result<(), String> {
+        // :: Unknown procedures are reported the same way unknown commands are,
+        //    since (as far as the user is concerned) that's what they are.
+        let procedure = match self.procedures.get(name) {
+            Some(procedure) => Rc::clone(procedure),
+            None => {
+                let message = format!("Command {} was not recognized or is malformed.", name);
+                self.emit(Event::CommandIgnored {
+                    command: name.to_string(),
+                    reason: message.clone(),
+                });
+
+                return Err(message);
+            }
+        };
+
+        if args.len() != procedure.params.len() {
+            return Err(format!(
+                "Procedure {} expects {} argument(s), but was given {}.",
+                name,
+                procedure.params.len(),
+                args.len()
+            ));
+        }
+
+        if evaluation.frames.len() >= CALL_DEPTH_LIMIT {
+            return Err(format!(
+                "Call depth limit of {} was exceeded in procedure {}; aborting evaluation.",
+                CALL_DEPTH_LIMIT, name
+            ));
+        }
+
+        // :: ---
+
+        let mut frame = HashMap::new();
+        for (param, arg) in procedure.params.iter().zip(args) {
+            let value = self.resolve(arg, evaluation)?;
+            frame.insert(param.clone(), value);
+        }
+
+        evaluation.frames.push(frame);
+        let result = match &procedure.body {
+            Body::Instructions(body) => self.execute_all(body, evaluation),
+            Body::Compiled { program, entry } => self.run_code(program, *entry, evaluation),
+        };
+        evaluation.frames.pop();
+
+        result
+    }
+
+    /// Computes the value of an expression.
+    ///
+    /// Variables are looked up in the innermost procedure call in progress (if any),
+    /// then among the bindings made outside of procedures, and finally among the
+    /// built-in variables that reflect the live state of this Robot and its Tabletop.
+    fn resolve(&self, expr: &Expr, evaluation: &Evaluation) -> Result<Coordinate, String> {
+        expr.evaluate(|name| {
+            let local = evaluation
+                .frames
+                .last()
+                .and_then(|frame| frame.get(name).copied());
+
+            local
+                .or_else(|| self.variables.get(name).copied())
+                .or_else(|| match name {
+                    "ROBOT_X" => self.position.map(|position| position.x),
+                    "ROBOT_Y" => self.position.map(|position| position.y),
+                    // :: Undefined on unbounded Tabletops, which have neither.
+                    "WIDTH" => self.tabletop.width(),
+                    "HEIGHT" => self.tabletop.height(),
+                    _ => None,
+                })
+        })
+    }
+
+    fn resolve_position(
+        &self,
+        x: &Expr,
+        y: &Expr,
+        evaluation: &Evaluation,
+    ) -> Result<Position, String> {
+        Ok(Position {
+            x: self.resolve(x, evaluation)?,
+            y: self.resolve(y, evaluation)?,
+        })
+    }
+
+    /// Evaluates a `Condition` against the current state of this Robot.
+    ///
+    /// Clearance is checked the same way actual movement is, via `Tabletop::request_place`.
+    fn check(&self, condition: &Condition, evaluation: &Evaluation) -> Result<bool, String> {
+        if let Condition::Placed = condition {
+            return Ok(self.is_placed());
+        }
+
+        if let Condition::Not(negated) = condition {
+            return self.check(negated, evaluation).map(|holds| !holds);
+        }
+
+        if !self.is_placed() {
+            return Err("Robot is not placed; cannot check condition.".to_string());
+        }
+
+        // :: ---
+
+        let position = self.position.unwrap();
+        let orientation = self.orientation.unwrap();
+        let is_clear = |direction: Orientation| match position.neighbour(direction) {
+            Some(next) => self.tabletop.request_place(&next).is_ok(),
+            None => false,
+        };
+
+        Ok(match condition {
+            Condition::FrontClear => is_clear(orientation),
+            Condition::LeftClear => is_clear(orientation.turned_left()),
+            Condition::RightClear => is_clear(orientation.turned_right()),
+            Condition::At { x, y } => position == self.resolve_position(x, y, evaluation)?,
+            Condition::Facing(facing) => orientation == *facing,
+            Condition::Placed | Condition::Not(_) => unreachable!(),
+        })
+    }
+
+    fn take_step(&self, evaluation: &mut Evaluation) -> Result<(), String> {
+        self.take_steps(1, evaluation)
+    }
+
+    fn take_steps(&self, steps: u32, evaluation: &mut Evaluation) -> Result<(), String> {
+        if evaluation.budget < steps {
+            return Err(format!(
+                "Step limit of {} was exceeded; aborting evaluation.",
+                self.step_limit
+            ));
+        }
+
+        evaluation.budget -= steps;
+        Ok(())
+    }
+}
+
+/// What a `Command` gets to see and do while it runs.
+///
+/// Commands can inspect the Robot and its Tabletop, and change them through
+/// the same (checked) operations the built-in commands use; they cannot poke
+/// at the Robot's state directly.
+pub struct CommandContext<'a> {
+    robot: &'a mut Robot,
+    evaluation: &'a mut Evaluation,
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn is_placed(&self) -> bool {
+        self.robot.is_placed()
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.robot.position
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.robot.orientation
+    }
+
+    pub fn tabletop(&self) -> &Tabletop {
+        &self.robot.tabletop
+    }
+
+    /// The move policy used by multi-step movements that don't specify their own.
+    pub fn move_policy(&self) -> MovePolicy {
+        self.robot.move_policy
+    }
+
+    /// Computes the value of an expression, against the variables currently in scope.
+    pub fn resolve(&self, expr: &Expr) -> Result<Coordinate, String> {
+        self.robot.resolve(expr, self.evaluation)
+    }
+
+    pub fn resolve_position(&self, x: &Expr, y: &Expr) -> Result<Position, String> {
+        self.robot.resolve_position(x, y, self.evaluation)
+    }
+
+    pub fn place(
+        &mut self,
+        position: Position,
+        orientation: Orientation,
+    ) -> Result<Option<String>, String> {
+        self.robot.place_on_tabletop(position, orientation)
+    }
+
+    pub fn add_obstacle(&mut self, position: Position) -> Result<Option<String>, String> {
+        self.robot.add_obstacle_to_tabletop(position)
+    }
+
+    pub fn move_forward(&mut self) -> Result<Option<String>, String> {
+        self.robot.move_forward()
+    }
+
+    pub fn move_back(&mut self) -> Result<Option<String>, String> {
+        self.robot.move_back()
+    }
+
+    pub fn strafe(&mut self, to_the_left: bool) -> Result<Option<String>, String> {
+        self.robot.strafe(to_the_left)
+    }
+
+    pub fn move_by(
+        &mut self,
+        steps: Coordinate,
+        policy: MovePolicy,
+    ) -> Result<Option<String>, String> {
+        self.robot.move_by(steps, policy, self.evaluation)
+    }
+
+    pub fn turn_left(&mut self) -> Result<Option<String>, String> {
+        self.robot.turn_left()
+    }
+
+    pub fn turn_right(&mut self) -> Result<Option<String>, String> {
+        self.robot.turn_right()
+    }
+
+    pub fn turn_around(&mut self) -> Result<Option<String>, String> {
+        self.robot.turn_around()
+    }
+
+    pub fn face(&mut self, orientation: Orientation) -> Result<Option<String>, String> {
+        self.robot.face(orientation)
+    }
+
+    pub fn report(&self) -> Result<Option<String>, String> {
+        self.robot.report_status()
+    }
+
+    pub fn report_as(&self, format: &ReportFormat) -> Result<Option<String>, String> {
+        self.robot.report_as(format)
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.robot.stats()
+    }
+
+    /// Lists the robot's trail as `x,y,ORIENTATION` entries, oldest first.
+    pub fn report_trail(&self) -> String {
+        let entries = self
+            .robot
+            .trail()
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{},{},{}",
+                    entry.position.x,
+                    entry.position.y,
+                    entry.orientation.as_str()
+                )
+            })
+            .collect::<Vec<String>>();
+
+        match entries.is_empty() {
+            true => "Robot has not been anywhere yet.".to_string(),
+            false => entries.join(" -> "),
+        }
+    }
+
+    pub fn render_ascii(&self, show_trail: bool) -> Result<String, String> {
+        self.robot.render_ascii(show_trail)
+    }
+
+    pub fn add_charging_station(&mut self, position: Position) -> Result<Option<String>, String> {
+        self.robot.add_charging_station_to_tabletop(position)
+    }
+
+    /// Reports the robot status as `REPORT` does, followed by its remaining charge.
+    pub fn report_charge(&self) -> Result<Option<String>, String> {
+        let charge = match self.robot.charge() {
+            Some(charge) => charge,
+            None => return Err("Robot has no energy model enabled.".to_string()),
+        };
+
+        Ok(self
+            .robot
+            .report_as(&ReportFormat::Plain)?
+            .map(|status| format!("{},CHARGE={}", status, charge)))
+    }
+
+    /// Translates and runs plaintext commands (e.g. `MOVE; LEFT`) as part of the
+    /// current evaluation, so that their outputs are collected, and their steps
+    /// counted, along with everything else.
+    pub fn run(&mut self, commands: &str) -> Result<(), String> {
+        let instructions = self.robot.parse_script(commands)?;
+        self.execute(&instructions)
+    }
+
+    /// Runs already translated instructions as part of the current evaluation.
+    pub fn execute(&mut self, instructions: &[Instruction]) -> Result<(), String> {
+        self.robot.execute_all(instructions, self.evaluation)
+    }
+
+    /// Notes that the evaluation is being rejected by an `EXPECT` that did not
+    /// hold (see `Robot::expectation_failed`).
+    pub(crate) fn fail_expectation(&mut self) {
+        self.evaluation.expectation_failed = true;
+    }
+
+    /// How many more steps may be taken before the evaluation is aborted.
+    pub fn remaining_steps(&self) -> u32 {
+        self.evaluation.budget
+    }
+
+    /// Takes `steps` steps at once, for work done outside of instructions
+    /// (e.g. planning); fails, as the step limit does, if there aren't enough.
+    pub fn take_steps(&mut self, steps: u32) -> Result<(), String> {
+        self.robot.take_steps(steps, self.evaluation)
+    }
+}
+
+/// The result of `Robot::check_script`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptOutcome {
+    /// Everything the script reported, in order.
+    pub outputs: Vec<String>,
+    /// Every `EXPECT` that did not hold, as `Line N: ...`.
+    pub failures: Vec<String>,
+    /// Whatever stopped the script before it was done, as `Line N: ...`, if anything.
+    pub error: Option<String>,
+}
+
+/// Looks at a Robot while it is running (see `Robot::run_observed`); returning
+/// an error aborts the evaluation.
+pub type Observer = Box<dyn FnMut(&Robot) -> Result<(), String>>;
+
+/// A procedure registered via `DEFINE`.
+struct Procedure {
+    params: Vec<String>,
+    body: Body,
+}
+
+enum Body {
+    Instructions(Vec<Instruction>),
+    /// Defined by a compiled `Program`, where the body starts at `entry`.
+    Compiled {
+        program: Program,
+        entry: usize,
+    },
+}
+
+/// Book-keeping for a single evaluation (i.e. one command, or one script).
+struct Evaluation {
+    /// How many more steps may be taken before the evaluation is aborted.
+    budget: u32,
+    outputs: Vec<String>,
+
+    /// Parameter (and local variable) bindings for each procedure call in progress,
+    /// innermost last.
+    frames: Vec<HashMap<String, Coordinate>>,
+
+    /// How many blocks are running within one another.
+    depth: usize,
+
+    /// Whether an `EXPECT` did not hold.
+    expectation_failed: bool,
+
+    /// Shown the Robot after every command run (see `Robot::run_observed`).
+    observer: Option<Observer>,
+}
+
+impl Evaluation {
+    fn new(budget: u32) -> Self {
+        Evaluation {
+            budget,
+            outputs: vec![],
+            frames: vec![],
+            depth: 0,
+            expectation_failed: false,
+            observer: None,
+        }
+    }
+}
+
+fn out_of_energy(action: &str) -> String {
+    format!("Robot is out of energy; cannot {}.", action)
+}
+
+fn join_outputs(outputs: Vec<String>) -> Option<String> {
+    match outputs.is_empty() {
+        true => None,
+        false => Some(outputs.join("\n")),
+    }
+}
+
+fn to_js_output(output: Option<String>) -> JsValue {
+    match output {
+        Some(message) => JsValue::from_str(&message),
+        None => JsValue::NULL,
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::components::command::Arguments;
+    use crate::components::tabletop;
+
+    use super::*;
+
+    #[test]
+    fn robot_can_be_created() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        assert!(Robot::create(&tabletop).is_ok());
+    }
+
+    #[test]
+    fn robot_has_empty_position_and_orientation_on_creation() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.position.is_none());
+        assert!(robot.orientation.is_none());
+    }
+
+    #[test]
+    fn robot_is_not_placed_on_creation() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let robot = Robot::create(&tabletop).unwrap();
+
+        assert!(!robot.is_placed());
+    }
+
+    #[test]
+    fn robot_tabletop_reference_is_equivalent_to_source() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let robot = Robot::create(&tabletop).unwrap();
+
+        assert_eq!(tabletop, robot.tabletop);
+    }
+
+    #[test]
+    fn robot_is_successfully_placed_on_tabletop() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        let result = robot.place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North);
+        assert!(result.is_ok());
+        assert!(robot.is_placed());
+
+        assert!(robot.position.is_some());
+        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 3 });
+
+        assert!(robot.orientation.is_some());
+        assert_eq!(robot.orientation.unwrap(), Orientation::North);
+    }
+
+    #[test]
+    fn robot_is_correctly_oriented_on_tabletop_when_placed() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::West)
+            .is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::West);
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::South)
+            .is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::East)
+            .is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::East);
+    }
+
+    #[test]
+    fn robot_can_be_replaced_and_reoriented() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+        assert!(robot.is_placed());
+
+        assert_eq!(robot.position.as_ref().unwrap(), &Position { x: 3, y: 3 });
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 0, y: 2 }, Orientation::South)
+            .is_ok());
+        assert!(robot.is_placed());
+
+        assert_eq!(robot.position.as_ref().unwrap(), &Position { x: 0, y: 2 });
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+    }
+
+    #[test]
+    fn robot_turns_left_correctly() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+
+        assert!(robot.turn_left().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::West);
+
+        assert!(robot.turn_left().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+
+        assert!(robot.turn_left().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::East);
+
+        assert!(robot.turn_left().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+    }
+
+    #[test]
+    fn robot_turns_right_correctly() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+
+        assert!(robot.turn_right().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::East);
+
+        assert!(robot.turn_right().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+
+        assert!(robot.turn_right().is_ok());
         assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::West);
 
-        assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::South)
-            .is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+        assert!(robot.turn_right().is_ok());
+        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+    }
+
+    #[test]
+    fn robot_cannot_turn_if_not_yet_placed() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(!robot.is_placed());
+        assert!(robot.turn_left().is_err());
+        assert!(robot.turn_right().is_err());
+    }
+
+    #[test]
+    fn robot_cannot_be_moved_if_it_has_not_been_placed_yet() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(!robot.is_placed());
+        assert!(robot.move_forward().is_err());
+    }
+
+    #[test]
+    fn robot_can_be_moved_forward_in_the_right_direction() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+
+        assert!(robot.move_forward().is_ok());
+        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 4 });
+
+        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
+        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 4 });
+
+        assert!(robot.turn_right().is_ok());
+        assert!(robot.move_forward().is_ok());
+        assert_eq!(robot.position.unwrap(), Position { x: 4, y: 4 });
+
+        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
+        assert_eq!(robot.position.unwrap(), Position { x: 4, y: 4 });
+
+        assert!(robot.turn_right().is_ok());
+        for y in (0..=3).rev() {
+            assert!(robot.move_forward().is_ok());
+            assert_eq!(robot.position.unwrap(), Position { x: 4, y });
+        }
+
+        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
+        assert_eq!(robot.position.unwrap(), Position { x: 4, y: 0 });
+
+        assert!(robot.turn_right().is_ok());
+        for x in (0..=3).rev() {
+            assert!(robot.move_forward().is_ok());
+            assert_eq!(robot.position.unwrap(), Position { x, y: 0 });
+        }
+
+        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn robot_can_be_moved_back_and_sideways_without_turning() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.move_back().is_err());
+        assert!(robot.strafe(true).is_err());
+        assert!(robot
+            .place_on_tabletop(Position { x: 2, y: 2 }, Orientation::North)
+            .is_ok());
+
+        assert!(robot.move_back().is_ok());
+        assert_eq!(robot.position.unwrap(), Position { x: 2, y: 1 });
+
+        assert!(robot.strafe(true).is_ok());
+        assert_eq!(robot.position.unwrap(), Position { x: 1, y: 1 });
+
+        assert!(robot.strafe(false).is_ok());
+        assert!(robot.strafe(false).is_ok());
+        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 1 });
+        assert_eq!(robot.orientation.unwrap(), Orientation::North);
+
+        assert!(robot.move_back().is_ok());
+        assert!(robot.move_back().is_err()); // :: Reached the edge of the tabletop.
+        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn robot_cannot_be_moved_back_or_sideways_onto_obstacles() {
+        let mut tabletop = Tabletop::new(5, 5).unwrap();
+        assert!(tabletop.add_obstacle(2, 1).is_ok());
+        assert!(tabletop.add_obstacle(1, 2).is_ok());
+
+        let mut robot = Robot::create(&tabletop).unwrap();
+        assert!(robot
+            .place_on_tabletop(Position { x: 2, y: 2 }, Orientation::North)
+            .is_ok());
+
+        assert!(robot.move_back().is_err());
+        assert!(robot.strafe(true).is_err());
+        assert_eq!(robot.position.unwrap(), Position { x: 2, y: 2 });
+    }
+
+    #[test]
+    fn robot_can_turn_around_and_face_any_orientation() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.turn_around().is_err());
+        assert!(robot.face(Orientation::East).is_err());
+        assert!(robot
+            .place_on_tabletop(Position { x: 2, y: 2 }, Orientation::North)
+            .is_ok());
+
+        assert!(robot.turn_around().is_ok());
+        assert_eq!(robot.orientation.unwrap(), Orientation::South);
+        assert!(robot.turn_around().is_ok());
+        assert_eq!(robot.orientation.unwrap(), Orientation::North);
+
+        assert!(robot.face(Orientation::West).is_ok());
+        assert_eq!(robot.orientation.unwrap(), Orientation::West);
+
+        assert!(robot.evaluate("FACE EAST").is_ok());
+        assert!(robot.evaluate("UTURN").is_ok());
+        assert!(robot.evaluate("STRAFE RIGHT").is_ok());
+        assert!(robot.evaluate("BACK").is_ok());
+        assert_eq!(robot.report_status().unwrap().unwrap(), "3,3,WEST");
+    }
+
+    #[test]
+    fn robot_can_be_moved_several_units_at_once() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        let mut evaluation = Evaluation::new(DEFAULT_STEP_LIMIT);
+
+        assert!(robot
+            .move_by(2, MovePolicy::AllOrNothing, &mut evaluation)
+            .is_err());
+        assert!(robot
+            .place_on_tabletop(Position { x: 0, y: 0 }, Orientation::North)
+            .is_ok());
+
+        assert_eq!(
+            robot
+                .move_by(3, MovePolicy::AllOrNothing, &mut evaluation)
+                .unwrap()
+                .unwrap(),
+            "Moved 3 of 3 units."
+        );
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 3 });
+
+        assert_eq!(
+            robot
+                .move_by(0, MovePolicy::AllOrNothing, &mut evaluation)
+                .unwrap()
+                .unwrap(),
+            "Moved 0 of 0 units."
+        );
+        assert!(robot
+            .move_by(-1, MovePolicy::AsFarAsPossible, &mut evaluation)
+            .is_err());
+    }
+
+    #[test]
+    fn robot_rejects_obstructed_moves_under_all_or_nothing() {
+        let mut tabletop = Tabletop::new(5, 5).unwrap();
+        assert!(tabletop.add_obstacle(0, 3).is_ok());
+
+        let mut robot = Robot::create(&tabletop).unwrap();
+        let mut evaluation = Evaluation::new(DEFAULT_STEP_LIMIT);
+        assert!(robot
+            .place_on_tabletop(Position { x: 0, y: 0 }, Orientation::North)
+            .is_ok());
+
+        assert!(robot
+            .move_by(3, MovePolicy::AllOrNothing, &mut evaluation)
+            .is_err());
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 0 });
+
+        assert!(robot.turn_right().is_ok());
+        assert!(robot
+            .move_by(5, MovePolicy::AllOrNothing, &mut evaluation)
+            .is_err());
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn robot_moves_up_to_obstructions_under_as_far_as_possible() {
+        let mut tabletop = Tabletop::new(5, 5).unwrap();
+        assert!(tabletop.add_obstacle(0, 3).is_ok());
+
+        let mut robot = Robot::create(&tabletop).unwrap();
+        let mut evaluation = Evaluation::new(DEFAULT_STEP_LIMIT);
+        assert!(robot
+            .place_on_tabletop(Position { x: 0, y: 0 }, Orientation::North)
+            .is_ok());
+
+        assert_eq!(
+            robot
+                .move_by(3, MovePolicy::AsFarAsPossible, &mut evaluation)
+                .unwrap()
+                .unwrap(),
+            "Moved 2 of 3 units."
+        );
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 2 });
+
+        assert_eq!(
+            robot
+                .move_by(1, MovePolicy::AsFarAsPossible, &mut evaluation)
+                .unwrap()
+                .unwrap(),
+            "Moved 0 of 1 units."
+        );
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn robot_applies_its_move_policy_unless_overridden() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.evaluate("PLACE 0,0,EAST").is_ok());
+        assert!(robot.evaluate("MOVE 10").is_err());
+        assert_eq!(
+            robot
+                .evaluate("MOVE 10 AS_FAR_AS_POSSIBLE")
+                .unwrap()
+                .unwrap(),
+            "Moved 4 of 10 units."
+        );
+
+        assert!(robot.set_move_policy("as-far-as-possible").is_ok());
+        assert!(robot.evaluate("LEFT").is_ok());
+        assert_eq!(
+            robot.evaluate("MOVE 10").unwrap().unwrap(),
+            "Moved 4 of 10 units."
+        );
+        assert!(robot.evaluate("MOVE 1 ALL_OR_NOTHING").is_err());
+
+        assert!(robot.set_move_policy("sideways").is_err());
+    }
+
+    #[test]
+    fn robot_cannot_report_if_it_has_not_been_placed() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let robot = Robot::create(&tabletop).unwrap();
+
+        assert!(!robot.is_placed());
+        assert!(robot.report_status().is_err());
+    }
+
+    fn robot_cannot_place_obstacle_on_top_of_it() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+
+        assert!(robot
+            .add_obstacle_to_tabletop(Position { x: 3, y: 3 })
+            .is_err());
+
+        assert!(robot
+            .add_obstacle_to_tabletop(Position { x: 1, y: 1 })
+            .is_ok());
+
+        assert!(robot
+            .add_obstacle_to_tabletop(Position { x: 4, y: 4 })
+            .is_ok());
+    }
+
+    #[test]
+    fn robot_reports_status_correctly() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot
+            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .is_ok());
+        assert_eq!(robot.report_status().unwrap().unwrap(), "3,3,NORTH");
+    }
+
+    #[test]
+    fn robot_evaluates_repeat_blocks() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.evaluate("PLACE 0,0,EAST").is_ok());
+        assert!(robot
+            .evaluate("REPEAT 2 { MOVE; LEFT; MOVE; RIGHT }")
+            .is_ok());
+        assert_eq!(robot.evaluate("REPORT").unwrap().unwrap(), "2,2,EAST");
+
+        let outputs = robot
+            .run_script("REPEAT 2\n  REPEAT 2 { RIGHT }\n  REPORT\nEND")
+            .unwrap();
+        assert_eq!(outputs, vec!["2,2,WEST", "2,2,EAST"]);
+    }
+
+    #[test]
+    fn robot_stops_repeat_blocks_at_the_first_failure() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.evaluate("PLACE 0,0,NORTH").is_ok());
+        assert!(robot.evaluate("REPEAT 10 { MOVE }").is_err());
+        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 4 });
+    }
+
+    #[test]
+    fn robot_aborts_evaluation_past_the_step_limit() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.evaluate("PLACE 0,0,NORTH").is_ok());
+        assert!(robot.evaluate("REPEAT 4000000000 { }").is_err());
+
+        robot.set_step_limit(5);
+        assert!(robot.evaluate("REPEAT 2 { LEFT }").is_ok());
+        assert!(robot.evaluate("REPEAT 3 { LEFT }").is_err());
+        assert!(robot.run_script("LEFT\nLEFT\nLEFT\nLEFT\nLEFT").is_ok());
+        assert!(robot
+            .run_script("LEFT\nLEFT\nLEFT\nLEFT\nLEFT\nLEFT")
+            .is_err());
+    }
+
+    #[test]
+    fn robot_aborts_evaluation_past_the_nesting_depth_limit() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        // :: Neither the blocks nested in the procedure, nor the calls to it, go
+        //    past their own limits here; but together, they would have
+        //    overflowed the stack.
+        let body = format!("{}DIVE{}", "REPEAT 1 { ".repeat(30), " }".repeat(30));
+        let script = format!("DEFINE DIVE\n{}\nEND\nDIVE", body);
+
+        assert_eq!(
+            robot.run_script(&script),
+            Err("Nesting depth limit of 256 was exceeded; aborting evaluation.".to_string())
+        );
+    }
+
+    fn create_observed_robot() -> (Robot, Rc<RefCell<Vec<Event>>>) {
+        let tabletop = Tabletop::new(3, 3).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let recorded = Rc::clone(&events);
+        robot.add_subscriber(move |event: &Event| recorded.borrow_mut().push(event.clone()));
+
+        (robot, events)
+    }
+
+    #[test]
+    fn robot_emits_events_for_state_changes() {
+        let (mut robot, events) = create_observed_robot();
 
         assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::East)
+            .run_script("PLACE 0,0,NORTH\nMOVE\nRIGHT\nOBSTACLE 2,2")
             .is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::East);
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                Event::Placed {
+                    position: Position { x: 0, y: 0 },
+                    orientation: Orientation::North,
+                },
+                Event::Moved {
+                    from: Position { x: 0, y: 0 },
+                    to: Position { x: 0, y: 1 },
+                },
+                Event::Turned {
+                    from: Orientation::North,
+                    to: Orientation::East,
+                },
+                Event::ObstacleAdded {
+                    position: Position { x: 2, y: 2 },
+                },
+            ]
+        );
+
+        // :: Multi-unit moves are reported as a single movement.
+        events.borrow_mut().clear();
+        assert!(robot.evaluate("MOVE 2").is_ok());
+        assert_eq!(
+            *events.borrow(),
+            vec![Event::Moved {
+                from: Position { x: 0, y: 1 },
+                to: Position { x: 2, y: 1 },
+            }]
+        );
     }
 
     #[test]
-    fn robot_can_be_replaced_and_reoriented() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
+    fn robot_emits_events_for_rejected_commands() {
+        let (mut robot, events) = create_observed_robot();
+
+        assert!(robot.evaluate("MOVE").is_err());
+        assert!(robot.evaluate("PLACE 0,0").is_err());
+        assert!(robot.run_script("PLACE 0,0,SOUTH; OBSTACLE 1,0").is_ok());
+
+        events.borrow_mut().clear();
+        assert!(robot.evaluate("MOVE").is_err());
+        assert!(robot.evaluate("STRAFE LEFT").is_err());
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            Event::FallPrevented {
+                at: Position { x: 0, y: -1 }
+            }
+        );
+        assert_eq!(events[1].name(), "CommandIgnored");
+        assert!(matches!(&events[2], Event::MoveBlocked { reason } if reason.contains("blocked")));
+        assert!(matches!(&events[3], Event::CommandIgnored { command, .. } if command == "STRAFE"));
+    }
+
+    #[test]
+    fn robot_emits_events_for_scripts_that_cannot_be_parsed() {
+        let (mut robot, events) = create_observed_robot();
+
+        let script = "PLACE 0,0,NORTH\nREPEAT 2 { MOVE; JUMP 3 }";
+        assert!(robot.run_script(script).is_err());
+        assert!(robot.check_script(script).is_err());
+        assert!(robot.compile(script).is_err());
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| matches!(
+            event,
+            Event::CommandIgnored { command, reason }
+                if command == "JUMP" && reason.starts_with("Line 2:")
+        )));
+
+        assert_eq!(robot.stats().commands_rejected, 2);
+        assert_eq!(robot.stats().commands_accepted, 0);
+    }
+
+    #[test]
+    fn robot_keeps_statistics_until_reset() {
+        let tabletop = Tabletop::new(3, 3).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
 
+        assert!(robot.evaluate("MOVE").is_err());
+        assert!(robot.evaluate("JUMP!").is_err());
         assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .run_script("PLACE 0,0,NORTH; OBSTACLE 2,1; MOVE 2; RIGHT; MOVE; LEFT")
             .is_ok());
-        assert!(robot.is_placed());
+        assert!(robot.evaluate("MOVE").is_err());
+        assert!(robot.run_script("RIGHT; MOVE; RIGHT; MOVE").is_err());
+
+        let stats = robot.stats();
+        assert_eq!(stats.commands_evaluated, 13);
+        assert_eq!(stats.commands_accepted, 9);
+        assert_eq!(stats.commands_rejected, 4);
+        assert_eq!(stats.moves, 3);
+        assert_eq!(stats.turns, 4);
+        assert_eq!(stats.blocked_moves, 1);
+        assert_eq!(stats.prevented_falls, 1);
+        assert_eq!(stats.distance_travelled, 4);
+        assert_eq!(stats.cells_visited, 5);
+
+        assert_eq!(
+            robot.evaluate("REPORT STATS"),
+            Ok(Some(
+                "commands=13 accepted=9 rejected=4 moves=3 turns=4 blocked_moves=1 \
+                 prevented_falls=1 distance=4 cells_visited=5"
+                    .to_string()
+            ))
+        );
 
-        assert_eq!(robot.position.as_ref().unwrap(), &Position { x: 3, y: 3 });
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+        robot.reset_stats();
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("2,2,SOUTH".to_string())));
+        assert_eq!(robot.stats().commands_evaluated, 1);
+        assert_eq!(robot.stats().cells_visited, 1);
+    }
+
+    #[test]
+    fn robot_spends_energy_on_movement_when_enabled() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert_eq!(robot.charge(), None);
+        robot.enable_energy(5, 2, 1);
+        assert_eq!(robot.charge(), Some(5));
 
         assert!(robot
-            .place_on_tabletop(Position { x: 0, y: 2 }, Orientation::South)
+            .run_script("PLACE 0,0,NORTH; MOVE; LEFT; RIGHT")
             .is_ok());
-        assert!(robot.is_placed());
+        assert_eq!(robot.charge(), Some(1));
 
-        assert_eq!(robot.position.as_ref().unwrap(), &Position { x: 0, y: 2 });
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+        assert_eq!(
+            robot.evaluate("MOVE"),
+            Err("Robot is out of energy; cannot move.".to_string())
+        );
+        assert!(robot.evaluate("BACK").is_err());
+        assert!(robot.evaluate("RIGHT").is_ok());
+        assert_eq!(
+            robot.evaluate("LEFT"),
+            Err("Robot is out of energy; cannot turn.".to_string())
+        );
+        assert_eq!(
+            robot.evaluate("REPORT CHARGE"),
+            Ok(Some("0,1,EAST,CHARGE=0".to_string()))
+        );
+
+        robot.disable_energy();
+        assert!(robot.evaluate("MOVE").is_ok());
+        assert!(robot.evaluate("REPORT CHARGE").is_err());
     }
 
     #[test]
-    fn robot_turns_left_correctly() {
+    fn robot_is_recharged_at_charging_stations() {
         let tabletop = Tabletop::new(5, 5).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
 
+        robot.enable_energy(3, 1, 0);
         assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .run_script("CHARGER 0,2; PLACE 0,0,NORTH; MOVE")
             .is_ok());
+        assert_eq!(robot.charge(), Some(2));
 
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+        // :: Passing through a station mid-way refills the robot.
+        assert!(robot.evaluate("MOVE 3").is_ok());
+        assert_eq!(robot.charge(), Some(1));
 
-        assert!(robot.turn_left().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::West);
+        // :: Under all-or-nothing, a move that would run out of energy is rejected outright.
+        assert!(robot.run_script("UTURN; MOVE 2").is_err());
+        assert_eq!(
+            robot.evaluate("REPORT CHARGE"),
+            Ok(Some("0,4,SOUTH,CHARGE=1".to_string()))
+        );
 
-        assert!(robot.turn_left().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+        assert_eq!(
+            robot.evaluate("MOVE 2 AS_FAR_AS_POSSIBLE"),
+            Ok(Some("Moved 1 of 2 units.".to_string()))
+        );
+        assert_eq!(robot.charge(), Some(0));
 
-        assert!(robot.turn_left().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::East);
+        assert!(robot.evaluate("CHARGER 0,3").is_ok());
+        assert_eq!(robot.charge(), Some(3));
+    }
 
-        assert!(robot.turn_left().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+    #[test]
+    fn robot_sweeps_every_reachable_position() {
+        let tabletop = Tabletop::new(3, 3).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.evaluate("SWEEP").is_err());
+        assert!(robot.run_script("PLACE 0,0,NORTH; OBSTACLE 1,1").is_ok());
+
+        assert_eq!(
+            robot.evaluate("SWEEP PLAN"),
+            Ok(Some(
+                "RIGHT; MOVE; MOVE; LEFT; MOVE; MOVE; LEFT; MOVE; MOVE; LEFT; MOVE\n\
+                 Covered 8 positions in 11 instructions, with 0 revisits. Unreachable: none."
+                    .to_string()
+            ))
+        );
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("0,0,NORTH".to_string())));
+
+        assert!(robot.evaluate("SWEEP").is_ok());
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("0,1,SOUTH".to_string())));
+        assert_eq!(robot.stats().cells_visited, 8);
     }
 
     #[test]
-    fn robot_turns_right_correctly() {
-        let tabletop = Tabletop::new(5, 5).unwrap();
+    fn robot_charges_sweep_planning_to_the_step_limit() {
+        let tabletop = Tabletop::new(20, 20).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.evaluate("PLACE 0,0,NORTH").is_ok());
+
+        // :: Planning searches each of the 400 positions at least once.
+        robot.set_step_limit(450);
+        assert_eq!(
+            robot.evaluate("SWEEP PLAN"),
+            Err(
+                "Step limit was exceeded while planning the sweep; aborting evaluation."
+                    .to_string()
+            )
+        );
+
+        robot.set_step_limit(DEFAULT_STEP_LIMIT);
+        assert!(robot.evaluate("SWEEP PLAN").is_ok());
+    }
+
+    #[test]
+    fn robot_records_its_trail_and_heatmap() {
+        let tabletop = Tabletop::new(3, 2).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
 
+        assert_eq!(
+            robot.evaluate("REPORT TRAIL"),
+            Ok(Some("Robot has not been anywhere yet.".to_string()))
+        );
+
         assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .run_script("PLACE 0,0,EAST\nMOVE 2\nUTURN\nMOVE\nMOVE 3 AS_FAR_AS_POSSIBLE")
             .is_ok());
 
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+        assert_eq!(robot.trail().len(), 5);
+        assert_eq!(robot.heatmap().unwrap(), vec![vec![2, 2, 1], vec![0, 0, 0]]);
+        assert_eq!(
+            robot.evaluate("REPORT TRAIL"),
+            Ok(Some(
+                "0,0,EAST -> 2,0,EAST -> 2,0,WEST -> 1,0,WEST -> 0,0,WEST".to_string()
+            ))
+        );
 
-        assert!(robot.turn_right().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::East);
+        assert_eq!(
+            robot.evaluate("REPORT MAP"),
+            Ok(Some("1 . . .\n0 < * *\n  0 1 2".to_string()))
+        );
 
-        assert!(robot.turn_right().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::South);
+        robot.clear_trail();
+        assert!(robot.trail().is_empty());
+        assert_eq!(robot.render_ascii(true), robot.render_ascii(false));
+    }
 
-        assert!(robot.turn_right().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::West);
+    #[test]
+    fn robot_does_not_map_oversized_tabletops() {
+        let tabletop = Tabletop::new(100_000, 100_000).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
 
-        assert!(robot.turn_right().is_ok());
-        assert_eq!(robot.orientation.as_ref().unwrap(), &Orientation::North);
+        assert!(robot.run_script("PLACE 0,0,NORTH\nMOVE").is_ok());
+        assert!(robot.heatmap().is_err());
+        assert!(robot.evaluate("REPORT MAP").is_err());
+        assert_eq!(robot.evaluate("REPORT"), Ok(Some("0,1,NORTH".to_string())));
     }
 
     #[test]
-    fn robot_cannot_turn_if_not_yet_placed() {
+    fn robot_can_stop_recording_where_it_has_been() {
+        let tabletop = Tabletop::new(3, 2).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert!(robot.run_script("PLACE 0,0,EAST\nMOVE").is_ok());
+        robot.set_recording(false);
+        assert!(robot.trail().is_empty());
+
+        assert!(robot.run_script("MOVE\nLEFT\nMOVE").is_ok());
+        assert!(robot.trail().is_empty());
+        assert_eq!(robot.heatmap().unwrap(), vec![vec![0, 0, 0], vec![0, 0, 0]]);
+        assert_eq!(robot.stats().cells_visited, 2);
+        assert_eq!(robot.stats().distance_travelled, 3);
+
+        robot.set_recording(true);
+        assert!(robot.run_script("LEFT\nMOVE").is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT TRAIL"),
+            Ok(Some("1,1,WEST".to_string()))
+        );
+    }
+
+    #[test]
+    fn robot_reports_in_the_chosen_format() {
         let tabletop = Tabletop::new(5, 5).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
+        robot.set_id(3);
 
-        assert!(!robot.is_placed());
-        assert!(robot.turn_left().is_err());
-        assert!(robot.turn_right().is_err());
+        assert_eq!(
+            robot.evaluate("REPORT CSV"),
+            Ok(Some("3,false,,,".to_string()))
+        );
+
+        assert!(robot.evaluate("PLACE 0,1,NORTH").is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT JSON"),
+            Ok(Some(
+                r#"{"id":3,"placed":true,"position":{"x":0,"y":1},"orientation":"NORTH"}"#
+                    .to_string()
+            ))
+        );
+
+        assert!(robot.set_report_format("csv").is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT"),
+            Ok(Some("3,true,0,1,NORTH".to_string()))
+        );
+
+        assert!(robot.set_report_template("{x}:{y} facing {f}").is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT"),
+            Ok(Some("0:1 facing NORTH".to_string()))
+        );
+
+        assert!(robot.set_report_format("xml").is_err());
+        assert!(robot.set_report_template("{x}:{y} facing {z}").is_err());
+        assert_eq!(
+            robot.evaluate("REPORT"),
+            Ok(Some("0:1 facing NORTH".to_string()))
+        );
     }
 
     #[test]
-    fn robot_cannot_be_moved_if_it_has_not_been_placed_yet() {
+    fn robot_checks_expectations() {
+        let tabletop = Tabletop::new(3, 3).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        assert_eq!(robot.evaluate("EXPECT NOT_PLACED"), Ok(None));
+        assert_eq!(
+            robot.evaluate("EXPECT 0,0,NORTH"),
+            Err(
+                "Expectation failed: expected robot at 0,0,NORTH, but it is not placed."
+                    .to_string()
+            )
+        );
+
+        assert!(robot.run_script("OBSTACLE 1,1\nPLACE 0,1,EAST").is_ok());
+        assert_eq!(robot.evaluate("EXPECT 0,1,EAST"), Ok(None));
+        assert_eq!(robot.evaluate("EXPECT OBSTACLE 1,1"), Ok(None));
+        assert_eq!(robot.evaluate("EXPECT BLOCKED"), Ok(None));
+        assert_eq!(
+            robot.evaluate("EXPECT NOT_PLACED"),
+            Err(
+                "Expectation failed: expected robot not to be placed, but it is at 0,1,EAST."
+                    .to_string()
+            )
+        );
+
+        assert!(robot.evaluate("LEFT").is_ok());
+        assert_eq!(
+            robot.evaluate("EXPECT BLOCKED"),
+            Err(
+                "Expectation failed: expected robot to be blocked, but 0,2 ahead of it is clear."
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            robot.evaluate("EXPECT OBSTACLE 2,2"),
+            Err("Expectation failed: expected an obstacle at 2,2, but there is none.".to_string())
+        );
+    }
+
+    #[test]
+    fn robot_reports_every_failed_expectation_in_a_script() {
         let tabletop = Tabletop::new(5, 5).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
 
-        assert!(!robot.is_placed());
-        assert!(robot.move_forward().is_err());
+        let outcome = robot
+            .check_script(
+                "PLACE 0,0,NORTH\nEXPECT 0,1,NORTH\nMOVE\nREPORT\nEXPECT BLOCKED\nMOVE 9\nREPORT",
+            )
+            .unwrap();
+
+        assert_eq!(outcome.outputs, vec!["0,1,NORTH".to_string()]);
+        assert_eq!(
+            outcome.failures,
+            vec![
+                "Line 2: Expectation failed: expected robot at 0,1,NORTH, but it is at 0,0,NORTH."
+                    .to_string(),
+                "Line 5: Expectation failed: expected robot to be blocked, but 0,2 ahead of it is clear."
+                    .to_string(),
+            ]
+        );
+
+        // :: Anything other than a failed expectation still stops the script.
+        assert_eq!(
+            outcome.error,
+            Some(
+                "Line 6: Robot cannot be moved 9 units: Position (0, 5) is out of bounds."
+                    .to_string()
+            )
+        );
     }
 
     #[test]
-    fn robot_can_be_moved_forward_in_the_right_direction() {
+    fn robot_only_counts_expect_as_failed_expectations() {
+        struct Impostor;
+
+        impl Command for Impostor {
+            fn parse(&self, keyword: &str, _args: Arguments) -> Result<Instruction, String> {
+                Ok(Instruction::Custom {
+                    keyword: keyword.to_string(),
+                    args: vec![],
+                })
+            }
+
+            fn execute(
+                &self,
+                _instruction: &Instruction,
+                _context: &mut CommandContext,
+            ) -> Result<Option<String>, String> {
+                Err("Expectation failed: expected nothing, but got this.".to_string())
+            }
+        }
+
         let tabletop = Tabletop::new(5, 5).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
+        robot.register("IMPOSTOR", Impostor).unwrap();
 
-        assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
-            .is_ok());
+        assert!(robot.evaluate("EXPECT 0,0,NORTH").is_err());
+        assert!(robot.expectation_failed());
 
-        assert!(robot.move_forward().is_ok());
-        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 4 });
+        assert!(robot.evaluate("IMPOSTOR").is_err());
+        assert!(!robot.expectation_failed());
 
-        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
-        assert_eq!(robot.position.unwrap(), Position { x: 3, y: 4 });
+        let outcome = robot
+            .check_script("EXPECT NOT_PLACED\nIMPOSTOR\nREPORT")
+            .unwrap();
+        assert!(outcome.failures.is_empty());
+        assert_eq!(
+            outcome.error,
+            Some("Line 2: Expectation failed: expected nothing, but got this.".to_string())
+        );
+    }
 
-        assert!(robot.turn_right().is_ok());
-        assert!(robot.move_forward().is_ok());
-        assert_eq!(robot.position.unwrap(), Position { x: 4, y: 4 });
+    #[test]
+    fn robots_get_distinct_ids() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let (a, b) = (
+            Robot::create(&tabletop).unwrap(),
+            Robot::create(&tabletop).unwrap(),
+        );
 
-        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
-        assert_eq!(robot.position.unwrap(), Position { x: 4, y: 4 });
+        assert_ne!(a.id(), b.id());
+    }
 
-        assert!(robot.turn_right().is_ok());
-        for y in (0..=3).rev() {
-            assert!(robot.move_forward().is_ok());
-            assert_eq!(robot.position.unwrap(), Position { x: 4, y });
-        }
+    #[test]
+    fn robot_runs_every_command_on_a_line() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
 
-        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
-        assert_eq!(robot.position.unwrap(), Position { x: 4, y: 0 });
+        assert_eq!(
+            robot.evaluate("PLACE 0,0,NORTH; MOVE; MOVE; REPORT # and then?"),
+            Ok(Some("0,2,NORTH".to_string()))
+        );
+        assert_eq!(
+            robot.evaluate("REPORT;RIGHT; REPORT"),
+            Ok(Some("0,2,NORTH\n0,2,EAST".to_string()))
+        );
+        assert_eq!(robot.evaluate("# nothing but a comment"), Ok(None));
+    }
 
-        assert!(robot.turn_right().is_ok());
-        for x in (0..=3).rev() {
-            assert!(robot.move_forward().is_ok());
-            assert_eq!(robot.position.unwrap(), Position { x, y: 0 });
+    #[test]
+    fn robot_reports_each_rejected_command_on_a_line() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+
+        // :: The rest of the line still runs, as it would on lines of its own.
+        assert_eq!(
+            robot.evaluate("MOVE; PLACE 0,0,NORTH; JUMP!; MOVE"),
+            Err(
+                "Statement 1: Robot is not placed; discarding instruction\n\
+                 Statement 3: Command JUMP! was not recognized or is malformed."
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            robot.evaluate_statements("REPORT; JUMP!"),
+            vec![
+                Ok(Some("0,1,NORTH".to_string())),
+                Err("Command JUMP! was not recognized or is malformed.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn robot_runs_programs_as_it_would_their_scripts() {
+        let script = "
+            DEFINE SQUARE $SIDE
+              REPEAT 4 { MOVE $SIDE AS_FAR_AS_POSSIBLE; RIGHT }
+            END
+            PLACE 0,0,NORTH
+            SQUARE 2
+            WHILE NOT FACING WEST { LEFT }
+            IF FRONT_CLEAR THEN MOVE ELSE REPORT
+            LET $N = $WIDTH - 1
+            PLACE $N, $N, SOUTH
+            REPEAT $N { IF FRONT_CLEAR THEN MOVE }
+            REPORT
+        ";
+
+        let program = Program::compile(script).unwrap();
+        for (width, height) in [(5, 5), (3, 7), (8, 2)].iter() {
+            let tabletop = Tabletop::new(*width, *height).unwrap();
+            let mut scripted = Robot::create(&tabletop).unwrap();
+            let mut compiled = Robot::create(&tabletop).unwrap();
+
+            // :: Programs can be run over and over again.
+            for _ in 0..3 {
+                assert_eq!(compiled.run_program(&program), scripted.run_script(script));
+                assert_eq!(compiled.position(), scripted.position());
+                assert_eq!(compiled.orientation(), scripted.orientation());
+            }
         }
+    }
 
-        assert!(robot.move_forward().is_err()); // :: Reached the edge of the tabletop.
-        assert_eq!(robot.position.unwrap(), Position { x: 0, y: 0 });
+    #[test]
+    fn robot_counts_steps_of_programs_as_it_would_their_scripts() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        let script = "PLACE 0,0,NORTH\nREPEAT 3 { LEFT }\nWHILE FRONT_CLEAR { MOVE }";
+        let program = robot.compile(script).unwrap();
+
+        // :: PLACE, REPEAT (with 3 passes, and 3 LEFTs), then WHILE (with 4 passes,
+        //    and 4 MOVEs).
+        robot.set_step_limit(17);
+        assert!(robot.run_program(&program).is_ok());
+
+        robot.set_step_limit(16);
+        assert_eq!(
+            robot.run_program(&program),
+            Err("Step limit of 16 was exceeded; aborting evaluation.".to_string())
+        );
     }
 
     #[test]
-    fn robot_cannot_report_if_it_has_not_been_placed() {
+    fn robot_rejects_scripts_calling_unknown_procedures_before_running_them() {
         let tabletop = Tabletop::new(5, 5).unwrap();
-        let robot = Robot::create(&tabletop).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
 
-        assert!(!robot.is_placed());
-        assert!(robot.report_status().is_err());
+        assert_eq!(
+            robot.run_script("PLACE 0,0,NORTH\nMOVE\nMOVEE\nREPORT"),
+            Err("Line 3: Command MOVEE was not recognized or is malformed.".to_string())
+        );
+        assert_eq!(robot.position(), None);
+
+        robot.evaluate("DEFINE MOVEE { MOVE; MOVE }").unwrap();
+        assert_eq!(
+            robot.run_script("PLACE 0,0,NORTH\nMOVE\nMOVEE\nREPORT"),
+            Ok(vec!["0,3,NORTH".to_string()])
+        );
     }
 
-    fn robot_cannot_place_obstacle_on_top_of_it() {
+    #[test]
+    fn robot_remembers_procedures_defined_by_programs() {
+        let tabletop = Tabletop::new(5, 5).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        let program =
+            Program::compile("DEFINE HOP $N\n  REPEAT $N { MOVE }\n  REPORT\nEND").unwrap();
+
+        robot.run_program(&program).unwrap();
+        robot.evaluate("PLACE 0,0,EAST").unwrap();
+
+        assert_eq!(robot.evaluate("HOP 2"), Ok(Some("2,0,EAST".to_string())));
+        assert_eq!(
+            robot.run_script("HOP 1\nHOP 1"),
+            Ok(vec!["3,0,EAST".to_string(), "4,0,EAST".to_string()])
+        );
+    }
+
+    #[test]
+    fn robot_runs_programs_that_were_saved_and_loaded() {
         let tabletop = Tabletop::new(5, 5).unwrap();
         let mut robot = Robot::create(&tabletop).unwrap();
+        let program = Program::compile("PLACE 1,1,EAST\nREPEAT 2 { MOVE }\nREPORT").unwrap();
+        let loaded = Program::from_bytes(&program.to_bytes()).unwrap();
+
+        assert_eq!(robot.run_program(&loaded), Ok(vec!["3,1,EAST".to_string()]));
+    }
+
+    #[test]
+    fn robot_roams_unbounded_tabletops_around_obstacles() {
+        let mut tabletop = Tabletop::unbounded();
+        tabletop.add_obstacle(-3, 0).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
 
         assert!(robot
-            .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
+            .evaluate("PLACE 0,0,WEST; MOVE 2; LEFT; MOVE 5")
             .is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT"),
+            Ok(Some("-2,-5,SOUTH".to_string()))
+        );
 
-        assert!(robot
-            .add_obstacle_to_tabletop(Position { x: 3, y: 3 })
-            .is_err());
+        assert!(robot.evaluate("PLACE -2,0,WEST").is_ok());
+        assert!(robot.evaluate("MOVE").is_err());
+        assert!(robot.evaluate("LET W = $WIDTH").is_err());
+    }
+
+    #[test]
+    fn robot_is_placed_past_32_bits_on_large_tabletops() {
+        let tabletop = Tabletop::large(10_000_000_000, 10_000_000_000).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
 
         assert!(robot
-            .add_obstacle_to_tabletop(Position { x: 1, y: 1 })
+            .evaluate("PLACE 9999999998,4294967296,EAST; MOVE")
             .is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT"),
+            Ok(Some("9999999999,4294967296,EAST".to_string()))
+        );
+        assert!(robot.evaluate("MOVE").is_err());
+        assert_eq!(
+            robot.evaluate("LET W = $WIDTH * 2; PLACE $W - $WIDTH - 1,0,NORTH; REPORT"),
+            Ok(Some("9999999999,0,NORTH".to_string()))
+        );
+    }
+
+    #[test]
+    fn robot_is_stopped_at_the_end_of_the_range_of_coordinates() {
+        let mut robot = Robot::create(&Tabletop::unbounded()).unwrap();
+
+        assert!(robot.evaluate("PLACE 9223372036854775806,0,EAST").is_ok());
+        assert_eq!(
+            robot.evaluate("MOVE 3"),
+            Err(
+                "Robot cannot be moved 3 units: Position would be out of the range of coordinates."
+                    .to_string()
+            )
+        );
+        assert!(robot.evaluate("MOVE").is_ok());
+        assert_eq!(
+            robot.evaluate("MOVE"),
+            Err(
+                "Robot cannot be moved: Position would be out of the range of coordinates."
+                    .to_string()
+            )
+        );
+        assert_eq!(robot.evaluate("IF FRONT_CLEAR { REPORT }"), Ok(None));
+        assert_eq!(
+            robot.position(),
+            Some(Position {
+                x: Coordinate::MAX,
+                y: 0
+            })
+        );
+    }
+
+    #[test]
+    fn robot_charges_long_moves_to_the_step_limit() {
+        let mut tabletop = Tabletop::unbounded();
+        tabletop.add_obstacle(0, 50).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        robot.set_step_limit(100);
+
+        assert!(robot.evaluate("PLACE 0,0,EAST").is_ok());
+        assert_eq!(
+            robot.evaluate("MOVE 3000000000"),
+            Err("Step limit of 100 was exceeded; aborting evaluation.".to_string())
+        );
+        assert_eq!(robot.position(), Some(Position { x: 0, y: 0 }));
+
+        assert!(robot.evaluate("LEFT").is_ok());
+        assert_eq!(
+            robot.evaluate("MOVE 3000000000 AS_FAR_AS_POSSIBLE"),
+            Ok(Some("Moved 49 of 3000000000 units.".to_string()))
+        );
+        assert_eq!(robot.stats().distance_travelled, 49);
+        assert_eq!(robot.stats().cells_visited, 50);
+    }
+
+    #[test]
+    fn robot_moves_far_without_going_over_every_position() {
+        let tabletop = Tabletop::large(10_000_000_000, 1).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        robot.set_step_limit(u32::MAX);
+        robot.set_recording(false);
 
         assert!(robot
-            .add_obstacle_to_tabletop(Position { x: 4, y: 4 })
+            .evaluate("PLACE 0,0,EAST; MOVE 3000000000; MOVE 3000000000")
             .is_ok());
+        assert_eq!(
+            robot.evaluate("REPORT"),
+            Ok(Some("6000000000,0,EAST".to_string()))
+        );
+        assert_eq!(robot.stats().distance_travelled, u32::MAX);
+        assert_eq!(robot.stats().moves, 2);
+
+        assert_eq!(
+            robot.evaluate("MOVE 9000000000 AS_FAR_AS_POSSIBLE"),
+            Ok(Some("Moved 3999999999 of 9000000000 units.".to_string()))
+        );
     }
 
-    // :: We can't reliably test `JsValue` itself because this type
-    //    doesn't really reside in the Rust runtime itself (and instead is
-    //    part of the generated JavaScript glue code during WASM compilation.)
-    //
-    //    Not ideal, but we trust that the proper `REPORT` behavior is tested
-    //    in the WASM module test suites (in `tests/web.rs`).
-    //
-    //    @see https://docs.rs/wasm-bindgen/latest/wasm_bindgen/struct.JsValue.html
-    //
-    // #[test]
-    // fn robot_reports_status_correctly() {
-    //     let tabletop = Tabletop::new(5, 5).unwrap();
-    //     let mut robot = Robot::create(&tabletop).unwrap();
-
-    //     assert!(robot
-    //         .place_on_tabletop(Position { x: 3, y: 3 }, Orientation::North)
-    //         .is_ok());
-    //     assert_eq!(robot.report_status().unwrap(), "3,3,NORTH");
-    // }
+    #[test]
+    fn robot_recharges_at_stations_passed_on_long_moves() {
+        let mut tabletop = Tabletop::new(1, 40).unwrap();
+        tabletop.add_charging_station(0, 8).unwrap();
+        tabletop.add_charging_station(0, 15).unwrap();
+        let mut robot = Robot::create(&tabletop).unwrap();
+        robot.enable_energy(10, 1, 0);
+
+        assert!(robot.evaluate("PLACE 0,0,NORTH").is_ok());
+        assert!(robot.evaluate("MOVE 30").is_err());
+        assert_eq!(robot.charge(), Some(10));
+
+        assert_eq!(
+            robot.evaluate("MOVE 30 AS_FAR_AS_POSSIBLE"),
+            Ok(Some("Moved 25 of 30 units.".to_string()))
+        );
+        assert_eq!(robot.charge(), Some(0));
+        assert!(robot.evaluate("MOVE").is_err());
+    }
 }
@@ -0,0 +1,937 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::command::{self, CommandRegistry};
+use super::expression::{is_identifier, Expr};
+use super::instruction::{Condition, Instruction, KEYWORDS};
+
+// :: ---
+
+/// A single statement in a script, tagged with the (1-based) line it came from.
+#[derive(Debug, PartialEq)]
+struct Statement<'a> {
+    line: usize,
+    text: &'a str,
+}
+
+/// How deeply blocks (and inline branches) may be nested in one another, so
+/// that scripts nested deeply enough to overflow the stack are rejected instead.
+pub const NESTING_LIMIT: usize = 32;
+
+/// The statements of a script left to parse, and how deeply nested the
+/// statement being parsed is.
+///
+/// Along the way, the procedures the script defines, and the ones it calls by
+/// name alone (rather than with `CALL`), are noted; see `check_calls`.
+struct Statements<'a> {
+    remaining: std::iter::Peekable<std::vec::IntoIter<Statement<'a>>>,
+    depth: usize,
+    defined: HashSet<String>,
+    called: Vec<(usize, String)>,
+}
+
+impl<'a> Statements<'a> {
+    fn new(script: &'a str) -> Self {
+        Statements {
+            remaining: split_statements(script).into_iter().peekable(),
+            depth: 0,
+            defined: HashSet::new(),
+            called: vec![],
+        }
+    }
+
+    fn peek(&mut self) -> Option<&Statement<'a>> {
+        self.remaining.peek()
+    }
+}
+
+impl<'a> Iterator for Statements<'a> {
+    type Item = Statement<'a>;
+
+    fn next(&mut self) -> Option<Statement<'a>> {
+        self.remaining.next()
+    }
+}
+
+/// Why a script could not be parsed.
+#[derive(Debug, PartialEq)]
+pub struct ScriptError {
+    /// The (1-based) line of the statement at fault.
+    pub line: usize,
+    /// The keyword of the statement at fault (e.g. `REPEAT`), in upper case.
+    pub command: String,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Line {}: {}", self.line, self.message)
+    }
+}
+
+/// Rejects `statement`, for the reason given in `message`.
+fn rejected(statement: &Statement, message: String) -> ScriptError {
+    ScriptError {
+        line: statement.line,
+        command: statement
+            .text
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_uppercase(),
+        message,
+    }
+}
+
+/// Translates a plaintext script (potentially spanning several lines) into
+/// a list of `Instruction`s.
+///
+/// Statements are separated by newlines or `;`. Blocks (e.g. `REPEAT`, `WHILE`,
+/// `IF`) are closed either by an `END` statement, or by wrapping the block body
+/// in braces:
+///
+/// ```text
+/// REPEAT 4 { MOVE; LEFT }
+///
+/// IF FRONT_CLEAR
+///   MOVE
+/// ELSE
+///   RIGHT
+/// END
+/// ```
+pub fn parse(script: &str) -> Result<Vec<Instruction>, String> {
+    command::with_built_in_commands(|commands| parse_with(script, commands))
+}
+
+/// Translates a plaintext script into a list of `Instruction`s, recognizing
+/// the commands in `commands` (rather than just the built-in ones).
+pub fn parse_with(script: &str, commands: &CommandRegistry) -> Result<Vec<Instruction>, String> {
+    Ok(parse_numbered_with(script, commands)?
+        .into_iter()
+        .map(|(_, instruction)| instruction)
+        .collect())
+}
+
+/// Translates a plaintext script into a list of `Instruction`s, each along with
+/// the (1-based) line it starts on, so that runners can say where it came from.
+pub fn parse_numbered_with(
+    script: &str,
+    commands: &CommandRegistry,
+) -> Result<Vec<(usize, Instruction)>, String> {
+    parse_numbered_calling(script, commands, &|_| false).map_err(|error| error.to_string())
+}
+
+/// Like `parse_numbered_with`, but with `is_defined` saying which procedures
+/// were defined beforehand (e.g. by scripts run earlier), so that the script
+/// can call them by name.
+///
+/// Names that are neither commands nor procedures (defined beforehand, or
+/// anywhere in the script) are rejected, as any other unrecognized command is;
+/// only `CALL` defers looking a procedure up until it is run.
+pub fn parse_numbered_calling(
+    script: &str,
+    commands: &CommandRegistry,
+    is_defined: &dyn Fn(&str) -> bool,
+) -> Result<Vec<(usize, Instruction)>, ScriptError> {
+    let mut statements = Statements::new(script);
+    let instructions = parse_sequence(&mut statements, commands)?;
+    check_calls(&statements, is_defined)?;
+
+    Ok(instructions)
+}
+
+/// Translates a plaintext script that is expected to contain exactly one
+/// (potentially compound) instruction, e.g. `REPEAT 4 { MOVE; LEFT }`.
+pub(crate) fn parse_single(
+    plaintext: &str,
+    commands: &CommandRegistry,
+) -> Result<Instruction, String> {
+    let mut statements = Statements::new(plaintext);
+    let mut instructions =
+        parse_sequence(&mut statements, commands).map_err(|error| error.message)?;
+
+    match instructions.len() {
+        1 => Ok(instructions.remove(0).1),
+        _ => Err("Expected a single command, but found several.".to_string()),
+    }
+}
+
+// :: ---
+
+/// Breaks down a script into its statements.
+///
+/// Opening braces are treated as plain separators, since a block header
+/// (e.g. `REPEAT 4`) already marks the start of a block; closing braces
+/// are treated as an `END` statement. Comments are dropped.
+fn split_statements(script: &str) -> Vec<Statement<'_>> {
+    let mut statements = vec![];
+
+    for (index, line_text) in script.lines().enumerate() {
+        let line = index + 1;
+        let line_text = strip_comment(line_text);
+        let mut start = 0;
+
+        for (offset, character) in line_text.char_indices() {
+            if let ';' | '{' | '}' = character {
+                push_statement(&mut statements, line, &line_text[start..offset]);
+
+                if character == '}' {
+                    push_statement(&mut statements, line, "END");
+                }
+
+                start = offset + character.len_utf8();
+            }
+        }
+
+        push_statement(&mut statements, line, &line_text[start..]);
+    }
+
+    statements
+}
+
+/// Breaks down a single line into the commands on it, separated by `;` (other
+/// than within braces, so that `REPEAT 2 { MOVE; LEFT }; REPORT` is two commands),
+/// dropping any comment.
+pub(crate) fn split_commands(line: &str) -> Vec<&str> {
+    let line = strip_comment(line);
+    let mut commands = vec![];
+    let (mut start, mut depth) = (0, 0usize);
+
+    for (offset, character) in line.char_indices() {
+        match character {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => {
+                commands.push(line[start..offset].trim());
+                start = offset + 1;
+            }
+
+            _ => (),
+        }
+    }
+
+    commands.push(line[start..].trim());
+    commands.retain(|command| !command.is_empty());
+    commands
+}
+
+/// Everything on a line before a `#`, which starts a comment running to the
+/// end of the line.
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(start) => &line[..start],
+        None => line,
+    }
+}
+
+fn push_statement<'a>(statements: &mut Vec<Statement<'a>>, line: usize, text: &'a str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        statements.push(Statement { line, text });
+    }
+}
+
+/// Parses statements until the end of the script, noting the line each
+/// (top-level) instruction starts on.
+fn parse_sequence(
+    statements: &mut Statements,
+    commands: &CommandRegistry,
+) -> Result<Vec<(usize, Instruction)>, ScriptError> {
+    let mut instructions = vec![];
+
+    while let Some(statement) = statements.next() {
+        match keyword_of(statement.text) {
+            "END" => {
+                return Err(rejected(
+                    &statement,
+                    "END does not close any open block.".to_string(),
+                ))
+            }
+
+            "ELSE" => {
+                return Err(rejected(
+                    &statement,
+                    "ELSE does not belong to any IF block.".to_string(),
+                ))
+            }
+
+            _ => {
+                let line = statement.line;
+                instructions.push((line, parse_statement(statement, statements, commands)?));
+            }
+        }
+    }
+
+    Ok(instructions)
+}
+
+/// Parses the body of a block opened by `opener`, up until the `END` that closes it.
+///
+/// If `allow_else` is set, an `ELSE` statement also closes the block, and is handed
+/// back so that the caller can carry on parsing the alternate branch.
+fn parse_block<'a>(
+    statements: &mut Statements<'a>,
+    opener: &Statement,
+    allow_else: bool,
+    commands: &CommandRegistry,
+) -> Result<(Vec<Instruction>, Option<Statement<'a>>), ScriptError> {
+    let mut instructions = vec![];
+
+    while let Some(statement) = statements.next() {
+        match keyword_of(statement.text) {
+            "END" => return Ok((instructions, None)),
+            "ELSE" if allow_else => return Ok((instructions, Some(statement))),
+
+            "ELSE" => {
+                return Err(rejected(
+                    &statement,
+                    "ELSE does not belong to any IF block.".to_string(),
+                ))
+            }
+
+            _ => instructions.push(parse_statement(statement, statements, commands)?),
+        }
+    }
+
+    Err(rejected(
+        opener,
+        format!("{} block was not closed with END.", keyword_of(opener.text)),
+    ))
+}
+
+/// Parses a single statement; block headers go on to consume the statements
+/// that make up their bodies.
+fn parse_statement(
+    statement: Statement,
+    statements: &mut Statements,
+    commands: &CommandRegistry,
+) -> Result<Instruction, ScriptError> {
+    if statements.depth >= NESTING_LIMIT {
+        return Err(rejected(
+            &statement,
+            format!("Blocks may not be nested more than {} deep.", NESTING_LIMIT),
+        ));
+    }
+
+    statements.depth += 1;
+    let instruction = parse_nested_statement(statement, statements, commands);
+    statements.depth -= 1;
+
+    instruction
+}
+
+fn parse_nested_statement(
+    statement: Statement,
+    statements: &mut Statements,
+    commands: &CommandRegistry,
+) -> Result<Instruction, ScriptError> {
+    match keyword_of(statement.text) {
+        "REPEAT" => {
+            let times = parse_repeat_count(&statement)?;
+            let (body, _) = parse_block(statements, &statement, false, commands)?;
+
+            Ok(Instruction::Repeat { times, body })
+        }
+
+        "WHILE" => {
+            let condition = parse_condition(&statement, arguments_of(statement.text))?;
+            let (body, _) = parse_block(statements, &statement, false, commands)?;
+
+            Ok(Instruction::While { condition, body })
+        }
+
+        "IF" => parse_if(statement, statements, commands),
+
+        "DEFINE" => {
+            let (name, params) = parse_definition_header(&statement, commands)?;
+            let (body, _) = parse_block(statements, &statement, false, commands)?;
+
+            statements.defined.insert(name.clone());
+            Ok(Instruction::Define { name, params, body })
+        }
+
+        _ => {
+            let instruction = Instruction::parse_with(statement.text, commands)
+                .map_err(|message| rejected(&statement, message))?;
+
+            if let Instruction::Call { name, .. } = &instruction {
+                let keyword = statement.text.split_whitespace().next().unwrap_or_default();
+                if !keyword.eq_ignore_ascii_case("CALL") {
+                    statements.called.push((statement.line, name.clone()));
+                }
+            }
+
+            Ok(instruction)
+        }
+    }
+}
+
+/// Rejects the first procedure called by name alone that is defined neither
+/// in the script nor beforehand (see `parse_numbered_calling`).
+fn check_calls(
+    statements: &Statements,
+    is_defined: &dyn Fn(&str) -> bool,
+) -> Result<(), ScriptError> {
+    match statements
+        .called
+        .iter()
+        .find(|(_, name)| !statements.defined.contains(name) && !is_defined(name))
+    {
+        Some((line, name)) => Err(ScriptError {
+            line: *line,
+            command: name.clone(),
+            message: format!("Command {} was not recognized or is malformed.", name),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Parses an `IF` construct, in any of its forms:
+///
+/// ```text
+/// IF FRONT_CLEAR THEN MOVE ELSE RIGHT
+/// IF FRONT_CLEAR { MOVE } ELSE { RIGHT }
+/// IF FRONT_CLEAR ... ELSE ... END
+/// ```
+fn parse_if(
+    statement: Statement,
+    statements: &mut Statements,
+    commands: &CommandRegistry,
+) -> Result<Instruction, ScriptError> {
+    let line = statement.line;
+    let (condition_text, inline_branch) =
+        match split_at_keyword(arguments_of(statement.text), "THEN") {
+            Some((condition_text, branch)) if !branch.is_empty() => (condition_text, Some(branch)),
+            Some((condition_text, _)) => (condition_text, None),
+            None => (arguments_of(statement.text), None),
+        };
+
+    let condition = parse_condition(&statement, condition_text)?;
+
+    let mut else_branch = None;
+    let then_branch = match inline_branch {
+        // :: `IF <condition> THEN <statement> [ELSE <statement>]`
+        Some(branch) => {
+            let (then_text, else_text) = match split_at_keyword(branch, "ELSE") {
+                Some((then_text, else_text)) => (then_text, Some(else_text)),
+                None => (branch, None),
+            };
+
+            let then_branch = vec![parse_inline(line, then_text, statements, commands)?];
+            if let Some(else_text) = else_text {
+                else_branch = Some(vec![parse_inline(line, else_text, statements, commands)?]);
+            }
+
+            then_branch
+        }
+
+        // :: `IF <condition> ... [ELSE ...] END`
+        None => {
+            let (then_branch, else_statement) =
+                parse_block(statements, &statement, true, commands)?;
+
+            if let Some(else_statement) = else_statement {
+                let mut branch = vec![];
+                if let Some(text) = non_empty(arguments_of(else_statement.text)) {
+                    branch.push(parse_inline(
+                        else_statement.line,
+                        text,
+                        statements,
+                        commands,
+                    )?);
+                }
+
+                branch.extend(parse_block(statements, &statement, false, commands)?.0);
+                else_branch = Some(branch);
+            }
+
+            then_branch
+        }
+    };
+
+    // :: An `ELSE` can also directly follow a closed `IF` block, as in
+    //    `IF FRONT_CLEAR { MOVE } ELSE { RIGHT }`.
+    let trailing_else = else_branch.is_none()
+        && matches!(statements.peek(), Some(next) if keyword_of(next.text) == "ELSE");
+
+    if trailing_else {
+        let else_statement = statements.next().unwrap();
+        else_branch = Some(match non_empty(arguments_of(else_statement.text)) {
+            Some(text) => vec![parse_inline(
+                else_statement.line,
+                text,
+                statements,
+                commands,
+            )?],
+            None => parse_block(statements, &else_statement, false, commands)?.0,
+        });
+    }
+
+    Ok(Instruction::If {
+        condition,
+        then_branch,
+        else_branch: else_branch.unwrap_or_default(),
+    })
+}
+
+/// Parses a statement embedded in the middle of another (e.g. the branches of
+/// an inline `IF`).
+fn parse_inline(
+    line: usize,
+    text: &str,
+    statements: &mut Statements,
+    commands: &CommandRegistry,
+) -> Result<Instruction, ScriptError> {
+    match non_empty(text) {
+        Some(text) => parse_statement(Statement { line, text }, statements, commands),
+        // :: Only the branches of an inline `IF` can be empty.
+        None => Err(ScriptError {
+            line,
+            command: "IF".to_string(),
+            message: "Expected a command, but found none.".to_string(),
+        }),
+    }
+}
+
+fn parse_repeat_count(statement: &Statement) -> Result<Expr, ScriptError> {
+    let count = arguments_of(statement.text);
+    let invalid = || {
+        rejected(
+            statement,
+            "Arguments for command REPEAT were invalid.".to_string(),
+        )
+    };
+
+    if count.is_empty() {
+        return Err(rejected(
+            statement,
+            "Arguments for command REPEAT were incomplete.".to_string(),
+        ));
+    }
+
+    match Expr::parse(count).map_err(|_| invalid())? {
+        Expr::Literal(times) if times < 0 => Err(invalid()),
+        times => Ok(times),
+    }
+}
+
+/// Parses a `DEFINE <name> [<param>, ...]` header into the procedure name
+/// and its parameter names. Parameters may optionally be written with a `$` prefix.
+fn parse_definition_header(
+    statement: &Statement,
+    commands: &CommandRegistry,
+) -> Result<(String, Vec<String>), ScriptError> {
+    let normalized_text = arguments_of(statement.text).to_uppercase();
+    let mut words = normalized_text
+        .split(|character: char| character == ',' || character.is_whitespace())
+        .filter(|word| !word.is_empty());
+
+    let name = match words.next() {
+        Some(name)
+            if is_identifier(name) && !KEYWORDS.contains(&name) && !commands.contains(name) =>
+        {
+            name.to_string()
+        }
+        Some(name) => {
+            return Err(rejected(
+                statement,
+                format!("Procedure name {} is invalid.", name),
+            ))
+        }
+
+        None => {
+            return Err(rejected(
+                statement,
+                "Arguments for command DEFINE were incomplete.".to_string(),
+            ))
+        }
+    };
+
+    let mut params: Vec<String> = vec![];
+    for word in words {
+        let param = word.strip_prefix('$').unwrap_or(word);
+        if !is_identifier(param) || params.iter().any(|existing| existing == param) {
+            return Err(rejected(
+                statement,
+                format!("Parameter {} of procedure {} is invalid.", word, name),
+            ));
+        }
+
+        params.push(param.to_string());
+    }
+
+    Ok((name, params))
+}
+
+fn parse_condition(statement: &Statement, text: &str) -> Result<Condition, ScriptError> {
+    Condition::parse(text).map_err(|message| rejected(statement, message))
+}
+
+// :: ---
+
+/// The words that give a script its structure.
+const CONSTRUCTS: &[&str] = &["REPEAT", "WHILE", "IF", "DEFINE", "ELSE", "END"];
+
+/// The construct a statement starts with (matched case-insensitively, and
+/// handed back uppercased), or an empty string for any other statement.
+fn keyword_of(text: &str) -> &'static str {
+    let word = text.split_whitespace().next().unwrap_or_default();
+
+    CONSTRUCTS
+        .iter()
+        .find(|construct| construct.eq_ignore_ascii_case(word))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Everything in a statement past its first word.
+fn arguments_of(text: &str) -> &str {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(index) => text[index..].trim(),
+        None => "",
+    }
+}
+
+/// Splits `text` around the first standalone occurrence of `keyword`
+/// (case-insensitive), returning the trimmed text before and after it.
+fn split_at_keyword<'a>(text: &'a str, keyword: &str) -> Option<(&'a str, &'a str)> {
+    let mut offset = 0;
+
+    for word in text.split_whitespace() {
+        let start = offset + text[offset..].find(word).unwrap();
+        offset = start + word.len();
+
+        if word.eq_ignore_ascii_case(keyword) {
+            return Some((text[..start].trim(), text[offset..].trim()));
+        }
+    }
+
+    None
+}
+
+fn non_empty(text: &str) -> Option<&str> {
+    let text = text.trim();
+    match text.is_empty() {
+        true => None,
+        false => Some(text),
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::common::Orientation;
+
+    #[test]
+    fn statements_are_split_on_newlines_and_semicolons() {
+        let instructions = parse("PLACE 0,0,NORTH\nMOVE; LEFT\n\n  REPORT  ").unwrap();
+
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Place {
+                    x: Expr::Literal(0),
+                    y: Expr::Literal(0),
+                    orientation: Orientation::North
+                },
+                Instruction::Move,
+                Instruction::Left,
+                Instruction::Report,
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+        assert_eq!(
+            parse("# Setup\nPLACE 0,0,NORTH # the corner\nMOVE; # LEFT\nREPORT #").unwrap(),
+            parse("PLACE 0,0,NORTH\nMOVE\nREPORT").unwrap()
+        );
+    }
+
+    #[test]
+    fn lines_are_split_into_commands_outside_of_braces() {
+        assert_eq!(
+            split_commands("PLACE 0,0,NORTH; MOVE;MOVE ;; REPORT # MOVE; MOVE"),
+            vec!["PLACE 0,0,NORTH", "MOVE", "MOVE", "REPORT"]
+        );
+        assert_eq!(
+            split_commands("REPEAT 2 { MOVE; IF FRONT_CLEAR { LEFT; MOVE } }; REPORT"),
+            vec!["REPEAT 2 { MOVE; IF FRONT_CLEAR { LEFT; MOVE } }", "REPORT"]
+        );
+        assert!(split_commands("  # nothing to see here; MOVE").is_empty());
+    }
+
+    #[test]
+    fn inline_repeat_block_is_parsed_correctly() {
+        assert_eq!(
+            parse("REPEAT 4 { MOVE; LEFT }").unwrap(),
+            vec![Instruction::Repeat {
+                times: Expr::Literal(4),
+                body: vec![Instruction::Move, Instruction::Left],
+            }]
+        );
+    }
+
+    #[test]
+    fn multiline_repeat_block_is_parsed_correctly() {
+        assert_eq!(
+            parse("REPEAT 2\n  MOVE\n  RIGHT\nEND\nREPORT").unwrap(),
+            vec![
+                Instruction::Repeat {
+                    times: Expr::Literal(2),
+                    body: vec![Instruction::Move, Instruction::Right],
+                },
+                Instruction::Report,
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_blocks_can_be_nested() {
+        let expected = vec![Instruction::Repeat {
+            times: Expr::Literal(2),
+            body: vec![
+                Instruction::Repeat {
+                    times: Expr::Literal(3),
+                    body: vec![Instruction::Move],
+                },
+                Instruction::Left,
+            ],
+        }];
+
+        assert_eq!(
+            parse("REPEAT 2 { REPEAT 3 { MOVE }; LEFT }").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("repeat 2\nrepeat 3\nmove\nend\nleft\nend").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn unbalanced_blocks_are_rejected() {
+        assert!(parse("REPEAT 2 { MOVE").is_err());
+        assert!(parse("REPEAT 2\nMOVE").is_err());
+        assert!(parse("MOVE }").is_err());
+        assert!(parse("MOVE\nEND").is_err());
+    }
+
+    #[test]
+    fn repeat_requires_a_valid_count() {
+        assert!(parse("REPEAT { MOVE }").is_err());
+        assert!(parse("REPEAT -1 { MOVE }").is_err());
+        assert!(parse("REPEAT many { MOVE }").is_err());
+    }
+
+    #[test]
+    fn if_blocks_are_parsed_correctly_in_every_form() {
+        let expected = vec![Instruction::If {
+            condition: Condition::FrontClear,
+            then_branch: vec![Instruction::Move],
+            else_branch: vec![Instruction::Right],
+        }];
+
+        assert_eq!(
+            parse("IF FRONT_CLEAR THEN MOVE ELSE RIGHT").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("IF FRONT_CLEAR { MOVE } ELSE { RIGHT }").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("IF FRONT_CLEAR THEN { MOVE } ELSE RIGHT").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("IF FRONT_CLEAR\n  MOVE\nELSE\n  RIGHT\nEND").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("if front_clear\nmove\nend\nelse\nright\nend").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn if_blocks_do_not_require_an_else_branch() {
+        let expected = vec![
+            Instruction::If {
+                condition: Condition::At {
+                    x: Expr::Literal(3),
+                    y: Expr::Literal(3),
+                },
+                then_branch: vec![Instruction::Left, Instruction::Left],
+                else_branch: vec![],
+            },
+            Instruction::Report,
+        ];
+
+        assert_eq!(parse("IF AT 3,3 { LEFT; LEFT }; REPORT").unwrap(), expected);
+        assert_eq!(
+            parse("IF AT 3,3\nLEFT\nLEFT\nEND\nREPORT").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn inline_branches_can_open_blocks() {
+        assert_eq!(
+            parse("IF NOT FRONT_CLEAR THEN REPEAT 2 { RIGHT } ELSE MOVE").unwrap(),
+            vec![Instruction::If {
+                condition: Condition::Not(Box::new(Condition::FrontClear)),
+                then_branch: vec![Instruction::Repeat {
+                    times: Expr::Literal(2),
+                    body: vec![Instruction::Right],
+                }],
+                else_branch: vec![Instruction::Move],
+            }]
+        );
+    }
+
+    #[test]
+    fn while_blocks_are_parsed_correctly() {
+        let expected = vec![Instruction::While {
+            condition: Condition::FrontClear,
+            body: vec![Instruction::Move],
+        }];
+
+        assert_eq!(parse("WHILE FRONT_CLEAR { MOVE }").unwrap(), expected);
+        assert_eq!(parse("WHILE FRONT_CLEAR\nMOVE\nEND").unwrap(), expected);
+    }
+
+    #[test]
+    fn malformed_conditionals_are_rejected() {
+        assert!(parse("IF THEN MOVE").is_err());
+        assert!(parse("IF FRONT_CLEAR THEN MOVE ELSE").is_err());
+        assert!(parse("IF FRONT_CLEAR { MOVE } ELSE { RIGHT").is_err());
+        assert!(parse("WHILE { MOVE }").is_err());
+        assert!(parse("ELSE MOVE").is_err());
+        assert!(parse("REPEAT 2 { MOVE; ELSE; LEFT }").is_err());
+    }
+
+    #[test]
+    fn procedure_definitions_are_parsed_correctly() {
+        let expected = vec![Instruction::Define {
+            name: "DODGE_LEFT".to_string(),
+            params: vec![],
+            body: vec![Instruction::Left, Instruction::Move, Instruction::Right],
+        }];
+
+        assert_eq!(
+            parse("DEFINE DODGE_LEFT { LEFT; MOVE; RIGHT }").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("define dodge_left\n  left\n  move\n  right\nend").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn procedure_definitions_accept_parameters() {
+        assert_eq!(
+            parse("DEFINE FORWARD $n, turns { REPEAT $n { MOVE }; REPEAT $turns { LEFT } }")
+                .unwrap(),
+            vec![Instruction::Define {
+                name: "FORWARD".to_string(),
+                params: vec!["N".to_string(), "TURNS".to_string()],
+                body: vec![
+                    Instruction::Repeat {
+                        times: Expr::Variable("N".to_string()),
+                        body: vec![Instruction::Move],
+                    },
+                    Instruction::Repeat {
+                        times: Expr::Variable("TURNS".to_string()),
+                        body: vec![Instruction::Left],
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn repeat_counts_accept_expressions() {
+        assert_eq!(
+            parse("REPEAT $WIDTH - 1 { MOVE }").unwrap(),
+            vec![Instruction::Repeat {
+                times: Expr::parse("$WIDTH - 1").unwrap(),
+                body: vec![Instruction::Move],
+            }]
+        );
+    }
+
+    #[test]
+    fn malformed_procedure_definitions_are_rejected() {
+        assert!(parse("DEFINE { MOVE }").is_err());
+        assert!(parse("DEFINE MOVE { LEFT }").is_err());
+        assert!(parse("DEFINE 2STEP { MOVE }").is_err());
+        assert!(parse("DEFINE STEP n, n { MOVE }").is_err());
+        assert!(parse("DEFINE STEP 3 { MOVE }").is_err());
+        assert!(parse("DEFINE STEP\nMOVE").is_err());
+    }
+
+    #[test]
+    fn deeply_nested_blocks_are_rejected() {
+        let nested =
+            |depth: usize| format!("{}MOVE{}", "REPEAT 1 { ".repeat(depth), " }".repeat(depth));
+
+        assert!(parse(&nested(NESTING_LIMIT - 1)).is_ok());
+        assert!(parse(&nested(NESTING_LIMIT)).is_err());
+        assert!(parse(&nested(10_000)).is_err());
+
+        let inline = format!("{}MOVE", "IF PLACED THEN ".repeat(10_000));
+        assert!(parse(&inline).is_err());
+    }
+
+    #[test]
+    fn errors_report_the_offending_line() {
+        let error = parse("PLACE 0,0,NORTH\nMOVE\nJUMP").unwrap_err();
+        assert!(error.starts_with("Line 3:"));
+    }
+
+    #[test]
+    fn procedures_called_by_name_need_to_be_defined() {
+        assert_eq!(
+            parse("PLACE 0,0,NORTH\nMOVE\nMOVEE\nREPORT"),
+            Err("Line 3: Command MOVEE was not recognized or is malformed.".to_string())
+        );
+
+        // :: Procedures may be defined after (or inside) where they are called,
+        //    defined beforehand, or called with `CALL` to be looked up when run.
+        assert!(parse("REPEAT 2 { HOP }\nDEFINE HOP { MOVE }").is_ok());
+        assert!(parse("DEFINE TWICE { DEFINE HOP { MOVE }; HOP; HOP }").is_ok());
+        assert!(parse("CALL HOP").is_ok());
+
+        let parsed = command::with_built_in_commands(|commands| {
+            parse_numbered_calling("HOP 2", commands, &|name| name == "HOP")
+        });
+        assert!(parsed.is_ok());
+    }
+
+    #[test]
+    fn instructions_are_numbered_by_the_line_they_start_on() {
+        let instructions = command::with_built_in_commands(|commands| {
+            parse_numbered_with(
+                "PLACE 0,0,NORTH\n\nREPEAT 2\n  MOVE\nEND\nLEFT; REPORT",
+                commands,
+            )
+        })
+        .unwrap();
+
+        let lines = instructions
+            .iter()
+            .map(|(line, instruction)| (*line, instruction.keyword()))
+            .collect::<Vec<(usize, &str)>>();
+
+        assert_eq!(
+            lines,
+            vec![(1, "PLACE"), (3, "REPEAT"), (6, "LEFT"), (6, "REPORT")]
+        );
+    }
+}
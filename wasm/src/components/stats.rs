@@ -0,0 +1,236 @@
+#![allow(clippy::unused_unit)]
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use super::common::*;
+use super::events::Event;
+
+// :: ---
+
+/// Running counts of what a Robot has done (and failed to do).
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[wasm_bindgen]
+pub struct Stats {
+    pub commands_evaluated: u32,
+    pub commands_accepted: u32,
+    pub commands_rejected: u32,
+
+    /// Successful movements; a multi-unit move (e.g. `MOVE 3`) counts once.
+    pub moves: u32,
+    pub turns: u32,
+    pub blocked_moves: u32,
+    pub prevented_falls: u32,
+
+    /// Total number of units moved.
+    pub distance_travelled: u32,
+    /// Number of distinct positions the robot has stood on (or passed through).
+    pub cells_visited: u32,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "commands={} accepted={} rejected={} moves={} turns={} blocked_moves={} \
+             prevented_falls={} distance={} cells_visited={}",
+            self.commands_evaluated,
+            self.commands_accepted,
+            self.commands_rejected,
+            self.moves,
+            self.turns,
+            self.blocked_moves,
+            self.prevented_falls,
+            self.distance_travelled,
+            self.cells_visited
+        )
+    }
+}
+
+/// Keeps a Robot's `Stats` up to date, mostly by following the events it emits.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StatsTracker {
+    stats: Stats,
+    visited: HashSet<Position>,
+
+    /// Whether positions go unrecorded (see `Robot::set_recording`), so that
+    /// `visited` doesn't grow; `cells_visited` stays as it was meanwhile.
+    forget_positions: bool,
+}
+
+impl StatsTracker {
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Clears all counts. The position the robot currently stands on (if any)
+    /// still counts as visited.
+    pub fn reset(&mut self, position: Option<Position>) {
+        *self = StatsTracker {
+            forget_positions: self.forget_positions,
+            ..StatsTracker::default()
+        };
+
+        if let Some(position) = position {
+            self.visit(position);
+        }
+    }
+
+    /// Starts (or stops) recording which positions have been visited. Once
+    /// stopped, what was recorded so far is forgotten.
+    pub fn set_recording(&mut self, enabled: bool) {
+        self.forget_positions = !enabled;
+        if !enabled {
+            self.visited = HashSet::new();
+        }
+    }
+
+    pub fn record_accepted_command(&mut self) {
+        self.stats.commands_evaluated = self.stats.commands_evaluated.saturating_add(1);
+        self.stats.commands_accepted = self.stats.commands_accepted.saturating_add(1);
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        match event {
+            Event::Placed { position, .. } => self.visit(*position),
+
+            Event::Moved { from, to } => {
+                self.stats.moves = self.stats.moves.saturating_add(1);
+
+                // :: Movements only ever go in a straight line, so the distance
+                //    is however far apart `from` and `to` are along it.
+                let distance = (i128::from(to.x) - i128::from(from.x)).abs()
+                    + (i128::from(to.y) - i128::from(from.y)).abs();
+
+                self.stats.distance_travelled = self
+                    .stats
+                    .distance_travelled
+                    .saturating_add(u32::try_from(distance).unwrap_or(u32::MAX));
+
+                // :: Every cell between `from` and `to` was passed through along
+                //    the way (and taken a step to get through; see `Robot::move_by`).
+                if !self.forget_positions {
+                    let (dx, dy) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+                    let mut position = *from;
+                    while position != *to {
+                        position = Position {
+                            x: position.x + dx,
+                            y: position.y + dy,
+                        };
+
+                        self.visit(position);
+                    }
+                }
+            }
+
+            Event::Turned { .. } => self.stats.turns = self.stats.turns.saturating_add(1),
+            Event::MoveBlocked { .. } => {
+                self.stats.blocked_moves = self.stats.blocked_moves.saturating_add(1)
+            }
+            Event::FallPrevented { .. } => {
+                self.stats.prevented_falls = self.stats.prevented_falls.saturating_add(1)
+            }
+            Event::ObstacleAdded { .. } => (),
+
+            Event::CommandIgnored { .. } => {
+                self.stats.commands_evaluated = self.stats.commands_evaluated.saturating_add(1);
+                self.stats.commands_rejected = self.stats.commands_rejected.saturating_add(1);
+            }
+        }
+    }
+
+    fn visit(&mut self, position: Position) {
+        if !self.forget_positions && self.visited.insert(position) {
+            self.stats.cells_visited = self.stats.cells_visited.saturating_add(1);
+        }
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_unit_moves_visit_every_cell_along_the_way() {
+        let mut tracker = StatsTracker::default();
+
+        tracker.record(&Event::Placed {
+            position: Position { x: 0, y: 0 },
+            orientation: Orientation::North,
+        });
+
+        tracker.record(&Event::Moved {
+            from: Position { x: 0, y: 0 },
+            to: Position { x: 0, y: 3 },
+        });
+
+        tracker.record(&Event::Moved {
+            from: Position { x: 0, y: 3 },
+            to: Position { x: 0, y: 1 },
+        });
+
+        let stats = tracker.stats();
+        assert_eq!(stats.moves, 2);
+        assert_eq!(stats.distance_travelled, 5);
+        assert_eq!(stats.cells_visited, 4);
+    }
+
+    #[test]
+    fn resetting_keeps_the_current_cell_visited() {
+        let mut tracker = StatsTracker::default();
+
+        tracker.record_accepted_command();
+        tracker.record(&Event::Turned {
+            from: Orientation::North,
+            to: Orientation::East,
+        });
+
+        tracker.reset(Some(Position { x: 2, y: 2 }));
+        assert_eq!(
+            tracker.stats(),
+            Stats {
+                cells_visited: 1,
+                ..Stats::default()
+            }
+        );
+
+        tracker.reset(None);
+        assert_eq!(tracker.stats(), Stats::default());
+    }
+
+    #[test]
+    fn positions_are_only_counted_while_recording() {
+        let mut tracker = StatsTracker::default();
+        let origin = Position { x: 0, y: 0 };
+
+        tracker.record(&Event::Placed {
+            position: origin,
+            orientation: Orientation::North,
+        });
+
+        tracker.set_recording(false);
+        tracker.record(&Event::Moved {
+            from: origin,
+            to: Position { x: 0, y: 2 },
+        });
+
+        assert!(tracker.visited.is_empty());
+        assert_eq!(tracker.stats().cells_visited, 1);
+        assert_eq!(tracker.stats().distance_travelled, 2);
+
+        tracker.reset(Some(origin));
+        assert_eq!(tracker.stats().cells_visited, 0);
+
+        tracker.set_recording(true);
+        tracker.record(&Event::Moved {
+            from: origin,
+            to: Position { x: 0, y: 2 },
+        });
+        assert_eq!(tracker.stats().cells_visited, 2);
+    }
+}
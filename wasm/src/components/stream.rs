@@ -0,0 +1,278 @@
+use std::io::{BufRead, Write};
+
+use super::robot::Robot;
+use super::script;
+
+// :: ---
+
+/// What a single command of a stream came to.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    /// Where the command is in the stream, counting from 1 (blank lines included).
+    pub number: usize,
+    /// Which of the commands on the line (separated by `;`) this is, counting
+    /// from 1; or `None`, if it is the only one.
+    pub statement: Option<usize>,
+    pub result: Result<Option<String>, String>,
+}
+
+impl Line {
+    /// Where the command is, as `Line N` (or `Line N, statement M`).
+    pub fn location(&self) -> String {
+        match self.statement {
+            Some(statement) => format!("Line {}, statement {}", self.number, statement),
+            None => format!("Line {}", self.number),
+        }
+    }
+
+    /// Writes whatever the command reported to `outputs`, or why it was
+    /// rejected (as `Line N: ...`) to `errors`.
+    pub fn write_to<W, E>(&self, outputs: &mut W, errors: &mut E) -> Result<(), String>
+    where
+        W: Write,
+        E: Write,
+    {
+        match &self.result {
+            Ok(Some(output)) => writeln!(outputs, "{}", output),
+            Ok(None) => Ok(()),
+            Err(message) => writeln!(errors, "{}: {}", self.location(), message),
+        }
+        .map_err(|error| format!("Could not write line {}: {}", self.number, error))
+    }
+}
+
+/// Tallies of what a stream came to, once it is done.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    /// How many commands were run (several, for lines with several on them).
+    pub commands: usize,
+    /// How many of those were rejected, including failed `EXPECT`s.
+    pub rejected: usize,
+    /// How many `EXPECT`s did not hold.
+    pub failed_expectations: usize,
+}
+
+impl Summary {
+    fn run<F>(
+        &mut self,
+        robot: &mut Robot,
+        number: usize,
+        line: &str,
+        on_line: &mut F,
+    ) -> Result<(), String>
+    where
+        F: FnMut(&Robot, Line) -> Result<(), String>,
+    {
+        let commands = script::split_commands(line);
+        let several = commands.len() > 1;
+
+        for (index, command) in commands.into_iter().enumerate() {
+            let result = robot.evaluate(command);
+
+            self.commands += 1;
+            if result.is_err() {
+                self.rejected += 1;
+                if robot.expectation_failed() {
+                    self.failed_expectations += 1;
+                }
+            }
+
+            let statement = match several {
+                true => Some(index + 1),
+                false => None,
+            };
+
+            on_line(
+                robot,
+                Line {
+                    number,
+                    statement,
+                    result,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the commands on each of `lines` (see `Robot::evaluate`) as soon as it
+/// comes up, skipping blank lines and comments, and hands what each command came
+/// to over to `on_line` (along with `robot`, as the command left it) before
+/// moving on to the next one.
+///
+/// Rejected commands are skipped, as in the webapp; `on_line` returning an
+/// error is the only thing that stops the stream early.
+///
+/// Only a single line is held at a time; but `robot` keeps track of where it has
+/// been as it goes, so for endless streams, turn that off first (see
+/// `Robot::set_recording`).
+pub fn run_lines<I, S, F>(robot: &mut Robot, lines: I, mut on_line: F) -> Result<Summary, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+    F: FnMut(&Robot, Line) -> Result<(), String>,
+{
+    let mut summary = Summary::default();
+    for (index, line) in lines.into_iter().enumerate() {
+        summary.run(robot, index + 1, line.as_ref(), &mut on_line)?;
+    }
+
+    Ok(summary)
+}
+
+/// Like `run_lines`, for the lines read from `reader` (e.g. a file, or a pipe),
+/// reusing the same buffer for all of them.
+pub fn run_reader<R, F>(robot: &mut Robot, mut reader: R, mut on_line: F) -> Result<Summary, String>
+where
+    R: BufRead,
+    F: FnMut(&Robot, Line) -> Result<(), String>,
+{
+    let mut summary = Summary::default();
+    let mut buffer = String::new();
+
+    for number in 1.. {
+        buffer.clear();
+        match reader.read_line(&mut buffer) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(error) => return Err(format!("Could not read line {}: {}", number, error)),
+        }
+
+        let line = buffer.trim_end_matches(&['\n', '\r'][..]);
+        summary.run(robot, number, line, &mut on_line)?;
+    }
+
+    Ok(summary)
+}
+
+/// Like `run_reader`, writing what every command reported to `outputs`, and
+/// every rejection to `errors` (see `Line::write_to`).
+pub fn run_reader_to<R, W, E>(
+    robot: &mut Robot,
+    reader: R,
+    outputs: &mut W,
+    errors: &mut E,
+) -> Result<Summary, String>
+where
+    R: BufRead,
+    W: Write,
+    E: Write,
+{
+    run_reader(robot, reader, |_, line| line.write_to(outputs, errors))
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Cursor, Read};
+
+    use crate::components::tabletop::Tabletop;
+
+    use super::*;
+
+    fn create_robot() -> Robot {
+        Robot::create(&Tabletop::new(5, 5).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn lines_are_run_one_at_a_time() {
+        let mut robot = create_robot();
+        let mut seen = vec![];
+
+        let summary = run_lines(
+            &mut robot,
+            vec![
+                "PLACE 0,0,NORTH",
+                "",
+                "MOVE",
+                "REPORT",
+                "JUMP",
+                "EXPECT 4,4,NORTH",
+            ],
+            |robot, line| {
+                seen.push((line.number, robot.position().map(|position| position.y)));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            seen,
+            vec![
+                (1, Some(0)),
+                (3, Some(1)),
+                (4, Some(1)),
+                (5, Some(1)),
+                (6, Some(1))
+            ]
+        );
+        assert_eq!(
+            summary,
+            Summary {
+                commands: 5,
+                rejected: 2,
+                failed_expectations: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn readers_are_run_to_writers() {
+        let mut robot = create_robot();
+        let input = Cursor::new("PLACE 1,1,EAST\r\nMOVE # East\n\n# Where to?\nLEAP; REPORT");
+        let (mut outputs, mut errors) = (vec![], vec![]);
+
+        let summary = run_reader_to(&mut robot, input, &mut outputs, &mut errors).unwrap();
+
+        assert_eq!(String::from_utf8(outputs).unwrap(), "2,1,EAST\n");
+        assert_eq!(
+            String::from_utf8(errors).unwrap(),
+            "Line 5, statement 1: Command LEAP was not recognized or is malformed.\n"
+        );
+        assert_eq!(summary.commands, 4);
+    }
+
+    #[test]
+    fn endless_streams_are_run_as_they_arrive() {
+        // :: The same command over and over, never running out.
+        struct Endless;
+        impl Read for Endless {
+            fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+                let line = b"PLACE 0,0,NORTH\n";
+                let length = line.len().min(buffer.len());
+                buffer[..length].copy_from_slice(&line[..length]);
+                Ok(length)
+            }
+        }
+
+        let mut robot = create_robot();
+        let result = run_reader(&mut robot, BufReader::new(Endless), |_, line| {
+            match line.number {
+                1000 => Err("Enough.".to_string()),
+                _ => Ok(()),
+            }
+        });
+
+        assert_eq!(result, Err("Enough.".to_string()));
+    }
+
+    #[test]
+    fn read_errors_stop_the_stream() {
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let mut robot = create_robot();
+        let result = run_reader(&mut robot, BufReader::new(Broken), |_, _| Ok(()));
+
+        assert_eq!(
+            result,
+            Err("Could not read line 1: broken pipe".to_string())
+        );
+    }
+}
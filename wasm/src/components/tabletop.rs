@@ -1,54 +1,132 @@
 #![allow(clippy::unused_unit)]
 
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
 use wasm_bindgen::prelude::*;
 
 use super::common::*;
+use super::generation::{self, Grid};
 
 // :: ---
 
+/// How far a Tabletop extends.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bounds {
+    /// From `(0, 0)` up to (but not including) `(width, height)`.
+    Sized {
+        width: Coordinate,
+        height: Coordinate,
+    },
+    /// Without any edges at all; only obstacles get in the way.
+    Unbounded,
+}
+
+/// Obstacles and charging stations are kept as sets of the (few) positions they
+/// are on, rather than as grids, so that looking them up stays fast however
+/// large the Tabletop is.
 #[derive(Clone, PartialEq, Debug)]
 #[wasm_bindgen]
 pub struct Tabletop {
-    width: i32,
-    height: i32,
+    bounds: Bounds,
 
-    obstacles: Vec<Position>,
+    obstacles: HashSet<Position>,
+    charging_stations: HashSet<Position>,
 }
 
 #[wasm_bindgen]
 impl Tabletop {
     #[wasm_bindgen(constructor)]
     pub fn new(width: i32, height: i32) -> Result<Tabletop, String> {
-        match (width, height) {
-            (width, height) if width > 0 && height > 0 => Ok(Tabletop {
-                width,
-                height,
-                obstacles: vec![],
-            }),
+        Tabletop::large(width.into(), height.into())
+    }
 
-            _ => Err("Tabletop dimensions need to be positive integers.".to_string()),
+    /// Creates a Tabletop without any edges, that robots can roam as far as
+    /// they like, around whatever obstacles are added to it.
+    pub fn unbounded() -> Tabletop {
+        Tabletop::with_bounds(Bounds::Unbounded)
+    }
+
+    /// Creates a Tabletop with obstacles scattered over roughly `density` (0 to 1)
+    /// of it, while keeping all of its free space connected.
+    ///
+    /// The same seed always produces the same layout.
+    pub fn with_random_obstacles(
+        width: i32,
+        height: i32,
+        density: f64,
+        seed: u32,
+    ) -> Result<Tabletop, String> {
+        if !(0.0..=1.0).contains(&density) {
+            return Err("Obstacle density needs to be between 0 and 1.".to_string());
         }
+
+        let tabletop = Tabletop::new(width, height)?;
+        let grid = generation::random_obstacles(width, height, density, seed.into())?;
+        Tabletop::from_grid(tabletop, grid)
     }
 
-    pub fn add_obstacle(&mut self, x: i32, y: i32) -> Result<(), String> {
-        match (x, y) {
-            (x, y) if x >= 0 && y >= 0 && x < self.width && y < self.height => {
-                self.obstacles.push(Position { x, y });
-                Ok(())
+    /// Creates a Tabletop laid out as a perfect maze, i.e. with exactly one path
+    /// between any two free positions. The same seed always produces the same maze.
+    pub fn with_maze(width: i32, height: i32, seed: u32) -> Result<Tabletop, String> {
+        let tabletop = Tabletop::new(width, height)?;
+        Tabletop::from_grid(tabletop, generation::maze(width, height, seed.into())?)
+    }
+
+    /// Creates a Tabletop laid out as rooms joined up by corridors.
+    /// The same seed always produces the same layout.
+    pub fn with_rooms(width: i32, height: i32, seed: u32) -> Result<Tabletop, String> {
+        let tabletop = Tabletop::new(width, height)?;
+        Tabletop::from_grid(tabletop, generation::rooms(width, height, seed.into())?)
+    }
+
+    /// The width of the Tabletop (as a number), or `null` if it is unbounded.
+    #[wasm_bindgen(getter = width)]
+    pub fn width_to_js(&self) -> Option<f64> {
+        self.width().map(|width| width as f64)
+    }
+
+    /// The height of the Tabletop (as a number), or `null` if it is unbounded.
+    #[wasm_bindgen(getter = height)]
+    pub fn height_to_js(&self) -> Option<f64> {
+        self.height().map(|height| height as f64)
+    }
+
+    #[wasm_bindgen(js_name = add_obstacle)]
+    pub fn add_obstacle_from_js(&mut self, x: i32, y: i32) -> Result<(), String> {
+        self.add_obstacle(x.into(), y.into())
+    }
+
+    /// Registers a charging station, which refills the energy of any robot
+    /// that stops on (or passes through) it.
+    #[wasm_bindgen(js_name = add_charging_station)]
+    pub fn add_charging_station_from_js(&mut self, x: i32, y: i32) -> Result<(), String> {
+        self.add_charging_station(x.into(), y.into())
+    }
+
+    pub fn is_charging_station(&self, position: &Position) -> bool {
+        self.charging_stations.contains(position)
+    }
+
+    pub fn is_obstacle(&self, position: &Position) -> bool {
+        self.obstacles.contains(position)
+    }
+
+    /// Is the position provided on the Tabletop at all (regardless of obstacles)?
+    pub fn is_within_bounds(&self, position: &Position) -> bool {
+        match self.bounds {
+            Bounds::Sized { width, height } => {
+                position.x >= 0 && position.y >= 0 && position.x < width && position.y < height
             }
 
-            _ => Err(format!("Position ({}, {}) is out of bounds.", x, y)),
+            Bounds::Unbounded => true,
         }
     }
 
     /// Checks if an item can be placed on the Tabletop at the position provided.
     pub fn request_place(&self, position: &Position) -> Result<(), String> {
         // :: Check if the requested position is outside of the bounds of the table.
-        if !(position.x >= 0
-            && position.y >= 0
-            && position.x < self.width
-            && position.y < self.height)
-        {
+        if !self.is_within_bounds(position) {
             Err(format!(
                 "Position ({}, {}) is out of bounds.",
                 position.x, position.y
@@ -66,6 +144,176 @@ impl Tabletop {
     }
 }
 
+/// Rust-facing API.
+impl Tabletop {
+    /// Creates a Tabletop too large for its dimensions to fit in 32 bits.
+    pub fn large(width: Coordinate, height: Coordinate) -> Result<Tabletop, String> {
+        match (width, height) {
+            (width, height) if width > 0 && height > 0 => {
+                Ok(Tabletop::with_bounds(Bounds::Sized { width, height }))
+            }
+
+            _ => Err("Tabletop dimensions need to be positive integers.".to_string()),
+        }
+    }
+
+    /// Like the JS-facing `add_obstacle`, anywhere on a Tabletop of any size.
+    pub fn add_obstacle(&mut self, x: Coordinate, y: Coordinate) -> Result<(), String> {
+        let position = Position { x, y };
+
+        match self.is_within_bounds(&position) {
+            true => {
+                self.obstacles.insert(position);
+                Ok(())
+            }
+
+            false => Err(format!("Position ({}, {}) is out of bounds.", x, y)),
+        }
+    }
+
+    /// Like the JS-facing `add_charging_station`, anywhere on a Tabletop of any size.
+    pub fn add_charging_station(&mut self, x: Coordinate, y: Coordinate) -> Result<(), String> {
+        let position = Position { x, y };
+
+        match self.is_within_bounds(&position) {
+            true => {
+                self.charging_stations.insert(position);
+                Ok(())
+            }
+
+            false => Err(format!("Position ({}, {}) is out of bounds.", x, y)),
+        }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// The width of the Tabletop, unless it is unbounded.
+    pub fn width(&self) -> Option<Coordinate> {
+        match self.bounds {
+            Bounds::Sized { width, .. } => Some(width),
+            Bounds::Unbounded => None,
+        }
+    }
+
+    /// The height of the Tabletop, unless it is unbounded.
+    pub fn height(&self) -> Option<Coordinate> {
+        match self.bounds {
+            Bounds::Sized { height, .. } => Some(height),
+            Bounds::Unbounded => None,
+        }
+    }
+
+    /// The size of the Tabletop, as long as it is small enough to be gone over
+    /// position by position (e.g. to draw it); or an error saying why not.
+    pub fn grid_size(&self) -> Result<(i32, i32), String> {
+        match self.bounds {
+            Bounds::Sized { width, height } => {
+                match (i32::try_from(width), i32::try_from(height)) {
+                    (Ok(width), Ok(height)) => Ok((width, height)),
+                    _ => Err(
+                        "Tabletop is too large to be gone over position by position.".to_string(),
+                    ),
+                }
+            }
+
+            Bounds::Unbounded => Err(
+                "Tabletop is unbounded, so cannot be gone over position by position.".to_string(),
+            ),
+        }
+    }
+
+    /// How many units (up to `limit`) something at `from` can move towards
+    /// `direction` before running into an obstacle, an edge, or the end of the
+    /// range of coordinates.
+    ///
+    /// This is worked out from the bounds and the obstacles themselves, rather
+    /// than by going over every position along the way, so long moves are cheap.
+    pub fn clear_distance(
+        &self,
+        from: Position,
+        direction: Orientation,
+        limit: Coordinate,
+    ) -> Coordinate {
+        let (low, high) = match self.bounds {
+            Bounds::Sized { width, height } => (
+                Position { x: 0, y: 0 },
+                Position {
+                    x: width - 1,
+                    y: height - 1,
+                },
+            ),
+
+            Bounds::Unbounded => (
+                Position {
+                    x: Coordinate::MIN,
+                    y: Coordinate::MIN,
+                },
+                Position {
+                    x: Coordinate::MAX,
+                    y: Coordinate::MAX,
+                },
+            ),
+        };
+
+        let edge = match direction {
+            Orientation::North => Position { y: high.y, ..from },
+            Orientation::South => Position { y: low.y, ..from },
+            Orientation::East => Position { x: high.x, ..from },
+            Orientation::West => Position { x: low.x, ..from },
+        };
+
+        // :: An edge too far away to count the units to is past `limit` anyway.
+        let reach = from
+            .units_towards(edge, direction)
+            .map_or(limit, |units| units.min(limit));
+
+        // :: For short moves past many obstacles, looking at each position along
+        //    the way is quicker than going over every obstacle.
+        let obstacle = match usize::try_from(reach) {
+            Ok(reach) if reach < self.obstacles.len() => (1..=reach as Coordinate).find(|units| {
+                matches!(
+                    from.moved_by(direction, *units),
+                    Some(position) if self.obstacles.contains(&position)
+                )
+            }),
+
+            _ => self
+                .obstacles
+                .iter()
+                .filter_map(|obstacle| from.units_towards(*obstacle, direction))
+                .filter(|units| (1..=reach).contains(units))
+                .min(),
+        };
+
+        obstacle.map_or(reach, |units| units - 1)
+    }
+
+    /// The positions of all obstacles on the Tabletop, in no particular order.
+    pub fn obstacles(&self) -> &HashSet<Position> {
+        &self.obstacles
+    }
+
+    /// The positions of all charging stations on the Tabletop, in no particular order.
+    pub fn charging_stations(&self) -> &HashSet<Position> {
+        &self.charging_stations
+    }
+
+    fn with_bounds(bounds: Bounds) -> Tabletop {
+        Tabletop {
+            bounds,
+            obstacles: HashSet::new(),
+            charging_stations: HashSet::new(),
+        }
+    }
+
+    fn from_grid(mut tabletop: Tabletop, grid: Grid) -> Result<Tabletop, String> {
+        tabletop.obstacles = grid.obstacles().into_iter().collect();
+        Ok(tabletop)
+    }
+}
+
 // :: ---
 
 #[cfg(test)]
@@ -80,8 +328,8 @@ mod tests {
         let result = Tabletop::new(width, height);
         let tabletop = result.unwrap();
 
-        assert_eq!(tabletop.width, width);
-        assert_eq!(tabletop.height, height);
+        assert_eq!(tabletop.width(), Some(width.into()));
+        assert_eq!(tabletop.height(), Some(height.into()));
     }
 
     #[test]
@@ -155,4 +403,130 @@ mod tests {
         assert!(tabletop.request_place(&Position { x: 1, y: 1 }).is_err());
         assert!(tabletop.request_place(&Position { x: 4, y: 4 }).is_err());
     }
+
+    #[test]
+    fn tabletop_generators_are_deterministic_for_a_seed() {
+        let generators: [fn(u32) -> Result<Tabletop, String>; 3] = [
+            |seed| Tabletop::with_random_obstacles(10, 8, 0.25, seed),
+            |seed| Tabletop::with_maze(10, 8, seed),
+            |seed| Tabletop::with_rooms(10, 8, seed),
+        ];
+
+        for generate in generators.iter() {
+            assert_eq!(generate(7).unwrap(), generate(7).unwrap());
+            assert_ne!(generate(7).unwrap(), generate(8).unwrap());
+        }
+    }
+
+    #[test]
+    fn tabletop_generators_validate_their_arguments() {
+        assert!(Tabletop::with_random_obstacles(5, 5, 1.5, 0).is_err());
+        assert!(Tabletop::with_random_obstacles(5, 5, -0.1, 0).is_err());
+        assert!(Tabletop::with_random_obstacles(0, 5, 0.1, 0).is_err());
+        assert!(Tabletop::with_maze(5, -5, 0).is_err());
+        assert!(Tabletop::with_rooms(0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn tabletop_can_add_charging_stations_correctly() {
+        let mut tabletop = Tabletop::new(5, 5).unwrap();
+
+        assert!(tabletop.add_charging_station(2, 3).is_ok());
+        assert!(tabletop.add_charging_station(5, 3).is_err());
+
+        assert!(tabletop.is_charging_station(&Position { x: 2, y: 3 }));
+        assert!(!tabletop.is_charging_station(&Position { x: 3, y: 2 }));
+
+        // :: Charging stations don't get in the way.
+        assert!(tabletop.request_place(&Position { x: 2, y: 3 }).is_ok());
+    }
+
+    #[test]
+    fn large_tabletops_go_past_32_bits() {
+        let width = 3 * i32::MAX as Coordinate;
+        let mut tabletop = Tabletop::large(width, 2).unwrap();
+
+        assert!(tabletop.add_obstacle(width - 1, 1).is_ok());
+        assert!(tabletop.add_obstacle(width, 1).is_err());
+
+        assert!(tabletop
+            .request_place(&Position { x: width - 2, y: 1 })
+            .is_ok());
+        assert!(tabletop
+            .request_place(&Position { x: width - 1, y: 1 })
+            .is_err());
+        assert!(tabletop.grid_size().is_err());
+
+        assert!(Tabletop::large(Coordinate::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn unbounded_tabletops_have_no_edges() {
+        let mut tabletop = Tabletop::unbounded();
+
+        assert!(tabletop
+            .add_obstacle(Coordinate::MIN, Coordinate::MAX)
+            .is_ok());
+        assert!(tabletop.add_obstacle(-4, 2).is_ok());
+
+        assert!(tabletop.is_obstacle(&Position { x: -4, y: 2 }));
+        assert!(tabletop.request_place(&Position { x: -4, y: 2 }).is_err());
+        assert!(tabletop
+            .request_place(&Position {
+                x: Coordinate::MAX,
+                y: Coordinate::MIN
+            })
+            .is_ok());
+
+        assert_eq!((tabletop.width(), tabletop.height()), (None, None));
+        assert!(tabletop.grid_size().is_err());
+    }
+
+    #[test]
+    fn clear_distances_stop_at_edges_and_obstacles() {
+        let mut tabletop = Tabletop::large(10_000_000_000, 5).unwrap();
+        tabletop.add_obstacle(7_000_000_000, 1).unwrap();
+        tabletop.add_obstacle(2, 3).unwrap();
+
+        let origin = Position { x: 0, y: 1 };
+        let distance =
+            |from: Position, direction, limit| tabletop.clear_distance(from, direction, limit);
+
+        assert_eq!(
+            distance(origin, Orientation::East, Coordinate::MAX),
+            6_999_999_999
+        );
+        assert_eq!(distance(origin, Orientation::East, 12), 12);
+        assert_eq!(distance(origin, Orientation::West, 12), 0);
+        assert_eq!(distance(origin, Orientation::North, Coordinate::MAX), 3);
+        assert_eq!(distance(Position { x: 2, y: 0 }, Orientation::North, 4), 2);
+
+        let unbounded = Tabletop::unbounded();
+        let edge = Position {
+            x: Coordinate::MAX - 2,
+            y: Coordinate::MIN,
+        };
+        assert_eq!(unbounded.clear_distance(edge, Orientation::East, 9), 2);
+        assert_eq!(
+            unbounded.clear_distance(edge, Orientation::North, Coordinate::MAX),
+            Coordinate::MAX
+        );
+    }
+
+    #[test]
+    fn js_facing_api_takes_32_bit_coordinates() {
+        let mut tabletop = Tabletop::new(5, 5).unwrap();
+
+        assert!(tabletop.add_obstacle_from_js(1, 2).is_ok());
+        assert!(tabletop.add_charging_station_from_js(3, 4).is_ok());
+        assert!(tabletop.add_obstacle_from_js(-1, 0).is_err());
+
+        assert!(tabletop.is_obstacle(&Position { x: 1, y: 2 }));
+        assert!(tabletop.is_charging_station(&Position { x: 3, y: 4 }));
+
+        let mut position = Position { x: 1 << 40, y: 0 };
+        assert_eq!(position.x_to_js(), 1099511627776.0);
+        position.set_y_from_js(-3);
+        assert_eq!(position.y, -3);
+    }
 }
@@ -0,0 +1,267 @@
+use super::common::*;
+use super::events::Event;
+
+// :: ---
+
+/// The most positions a Tabletop may have for a heatmap of it to be made, as
+/// it takes memory in proportion.
+pub const HEATMAP_SIZE_LIMIT: i64 = 1 << 20;
+
+/// A single point along a robot's trail: where it stood, where it was facing,
+/// and how it got there.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrailEntry {
+    pub position: Position,
+    pub orientation: Orientation,
+    pub step: TrailStep,
+}
+
+/// How a robot got to a point along its trail.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TrailStep {
+    Placed,
+    /// Turned on the spot.
+    Turned,
+    /// Moved in a straight line from the previous point, passing through every
+    /// position in between.
+    Moved,
+}
+
+/// Everywhere a robot has been, in order.
+///
+/// Moves are kept as single entries however long they are, so a trail only grows
+/// with the number of things the robot did; positions passed through along the
+/// way are worked out when asked for.
+#[derive(Clone, Debug, Default)]
+pub struct Trail {
+    entries: Vec<TrailEntry>,
+}
+
+impl Trail {
+    pub fn entries(&self) -> &[TrailEntry] {
+        &self.entries
+    }
+
+    /// How many times the robot has stood on (or passed through) `position`.
+    pub fn visits(&self, position: &Position) -> u32 {
+        let mut visits = 0u32;
+        self.for_each_visit(|from, to| {
+            let (low, high) = (
+                Position {
+                    x: from.x.min(to.x),
+                    y: from.y.min(to.y),
+                },
+                Position {
+                    x: from.x.max(to.x),
+                    y: from.y.max(to.y),
+                },
+            );
+
+            if (low.x..=high.x).contains(&position.x) && (low.y..=high.y).contains(&position.y) {
+                visits = visits.saturating_add(1);
+            }
+        });
+
+        visits
+    }
+
+    /// Visit counts for every position of a (width x height) Tabletop, as rows
+    /// from `y = 0` upwards; or an error, if it has more than
+    /// `HEATMAP_SIZE_LIMIT` positions.
+    pub fn heatmap(&self, width: i32, height: i32) -> Result<Vec<Vec<u32>>, String> {
+        let (width, height) = (width.max(0), height.max(0));
+        if i64::from(width) * i64::from(height) > HEATMAP_SIZE_LIMIT {
+            return Err(format!(
+                "Tabletop is too large for a heatmap; at most {} positions are supported.",
+                HEATMAP_SIZE_LIMIT
+            ));
+        }
+
+        let mut rows = vec![vec![0u32; width as usize]; height as usize];
+
+        // :: Only the part of each move that is on the Tabletop is gone over.
+        self.for_each_visit(|from, to| {
+            let (x1, x2) = (
+                from.x.min(to.x).max(0),
+                from.x.max(to.x).min(width as Coordinate - 1),
+            );
+            let (y1, y2) = (
+                from.y.min(to.y).max(0),
+                from.y.max(to.y).min(height as Coordinate - 1),
+            );
+
+            for y in y1..=y2 {
+                for x in x1..=x2 {
+                    let cell = &mut rows[y as usize][x as usize];
+                    *cell = cell.saturating_add(1);
+                }
+            }
+        });
+
+        Ok(rows)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Trail::default();
+    }
+
+    /// Follows an event a robot emitted, given the orientation the robot is
+    /// in right after it.
+    pub fn record(&mut self, event: &Event, orientation: Option<Orientation>) {
+        let orientation = match orientation {
+            Some(orientation) => orientation,
+            None => return,
+        };
+
+        let (position, step) = match event {
+            Event::Placed { position, .. } => (*position, TrailStep::Placed),
+            Event::Turned { .. } => match self.entries.last() {
+                Some(last) => (last.position, TrailStep::Turned),
+                None => return,
+            },
+
+            Event::Moved { to, .. } => (*to, TrailStep::Moved),
+            _ => return,
+        };
+
+        self.entries.push(TrailEntry {
+            position,
+            orientation,
+            step,
+        });
+    }
+
+    /// Calls `visit` with the (inclusive) ends of each straight run of positions
+    /// the robot visited: the position it was placed on, or those it moved
+    /// through (not counting the one it set off from).
+    fn for_each_visit<F>(&self, mut visit: F)
+    where
+        F: FnMut(Position, Position),
+    {
+        let mut previous: Option<Position> = None;
+
+        for entry in self.entries.iter() {
+            match (entry.step, previous) {
+                (TrailStep::Placed, _) => visit(entry.position, entry.position),
+                (TrailStep::Moved, Some(from)) if from != entry.position => {
+                    // :: The first position past `from`, towards the entry.
+                    let first = Position {
+                        x: from.x + (entry.position.x - from.x).signum(),
+                        y: from.y + (entry.position.y - from.y).signum(),
+                    };
+
+                    visit(first, entry.position);
+                }
+
+                _ => (),
+            }
+
+            previous = Some(entry.position);
+        }
+    }
+}
+
+// :: ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trail_follows_placements_moves_and_turns() {
+        let mut trail = Trail::default();
+        let origin = Position { x: 0, y: 0 };
+
+        trail.record(
+            &Event::Placed {
+                position: origin,
+                orientation: Orientation::East,
+            },
+            Some(Orientation::East),
+        );
+
+        trail.record(
+            &Event::Moved {
+                from: origin,
+                to: Position { x: 2, y: 0 },
+            },
+            Some(Orientation::East),
+        );
+
+        trail.record(
+            &Event::Turned {
+                from: Orientation::East,
+                to: Orientation::West,
+            },
+            Some(Orientation::West),
+        );
+
+        trail.record(
+            &Event::Moved {
+                from: Position { x: 2, y: 0 },
+                to: Position { x: 1, y: 0 },
+            },
+            Some(Orientation::West),
+        );
+
+        let positions = trail
+            .entries()
+            .iter()
+            .map(|entry| (entry.position.x, entry.orientation, entry.step))
+            .collect::<Vec<(Coordinate, Orientation, TrailStep)>>();
+
+        assert_eq!(
+            positions,
+            vec![
+                (0, Orientation::East, TrailStep::Placed),
+                (2, Orientation::East, TrailStep::Moved),
+                (2, Orientation::West, TrailStep::Turned),
+                (1, Orientation::West, TrailStep::Moved),
+            ]
+        );
+
+        assert_eq!(
+            trail.heatmap(3, 2).unwrap(),
+            vec![vec![1, 2, 1], vec![0, 0, 0]]
+        );
+        assert_eq!(trail.visits(&Position { x: 1, y: 0 }), 2);
+        assert_eq!(trail.visits(&Position { x: 1, y: 1 }), 0);
+    }
+
+    #[test]
+    fn long_moves_take_a_single_entry() {
+        let mut trail = Trail::default();
+        let origin = Position { x: 0, y: 0 };
+        let far = Position {
+            x: 0,
+            y: 3_000_000_000,
+        };
+
+        trail.record(
+            &Event::Placed {
+                position: origin,
+                orientation: Orientation::North,
+            },
+            Some(Orientation::North),
+        );
+
+        trail.record(
+            &Event::Moved {
+                from: origin,
+                to: far,
+            },
+            Some(Orientation::North),
+        );
+
+        assert_eq!(trail.entries().len(), 2);
+        assert_eq!(
+            trail.visits(&Position {
+                x: 0,
+                y: 2_999_999_999
+            }),
+            1
+        );
+        assert_eq!(trail.visits(&Position { x: 0, y: -1 }), 0);
+        assert_eq!(trail.heatmap(2, 2).unwrap(), vec![vec![1, 0], vec![1, 0]]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Checks that parsing the everyday commands does not allocate, run natively
+//! (no browser needed).
+//!
+//! Allocations are counted by a global allocator that wraps the system one,
+//! per thread, so that tests running alongside each other are not counted.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use rules_engine::components::instruction::Instruction;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_while<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn parsing_everyday_commands_does_not_allocate() {
+    let commands = [
+        "PLACE 1,2,NORTH",
+        "place 1, 2, north",
+        "OBSTACLE 3,3",
+        "charger 0,0",
+        "MOVE",
+        "move 3",
+        "MOVE 3 as-far-as-possible",
+        "LEFT",
+        "right",
+        "BACK",
+        "UTURN",
+        "STRAFE left",
+        "FACE WEST",
+        "REPORT",
+        "report json",
+        "SWEEP PLAN",
+        "EXPECT 1,2,NORTH",
+        "expect not_placed",
+    ];
+
+    // :: The first command parsed sets up the (shared) built-in commands.
+    Instruction::parse("MOVE").unwrap();
+
+    for command in commands.iter() {
+        let allocations = allocations_while(|| {
+            Instruction::parse(command).unwrap();
+        });
+
+        assert_eq!(allocations, 0, "Parsing {:?} allocated.", command);
+    }
+}
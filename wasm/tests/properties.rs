@@ -0,0 +1,179 @@
+//! Property-based tests, run natively (no browser needed).
+//!
+//! These generate random tabletops (with obstacles) and random command scripts,
+//! and check invariants that should hold whatever the commands are. When one
+//! fails, proptest shrinks the case down to a minimal one, e.g.
+//!
+//! ```text
+//! minimal failing input: tabletop = (1, 3, []), script = ["PLACE 0,2,NORTH"]
+//! ```
+//!
+//! For a more thorough run than the default 256 cases per property:
+//!
+//! ```sh
+//! PROPTEST_CASES=10000 cargo test --test properties
+//! ```
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use proptest::prelude::*;
+
+use rules_engine::components::common::{Orientation, Position};
+use rules_engine::components::program::Program;
+use rules_engine::components::robot::Robot;
+use rules_engine::components::tabletop::Tabletop;
+
+/// A tabletop, as (width, height, obstacles); obstacles may fall out of bounds,
+/// in which case they are left out.
+type TabletopSpec = (i32, i32, Vec<(i32, i32)>);
+
+fn tabletop() -> impl Strategy<Value = TabletopSpec> {
+    (1..8, 1..8).prop_flat_map(|(width, height)| {
+        (
+            Just(width),
+            Just(height),
+            prop::collection::vec((0..width, 0..height), 0..6),
+        )
+    })
+}
+
+fn orientation() -> impl Strategy<Value = &'static str> {
+    prop_oneof![Just("NORTH"), Just("EAST"), Just("SOUTH"), Just("WEST")]
+}
+
+/// A single command, as plaintext. Coordinates stray a little beyond the
+/// largest tabletop, so that some commands are rejected.
+fn command() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (-2..10, -2..10, orientation()).prop_map(|(x, y, f)| format!("PLACE {},{},{}", x, y, f)),
+        (-2..10, -2..10).prop_map(|(x, y)| format!("OBSTACLE {},{}", x, y)),
+        Just("MOVE".to_string()),
+        (-2..10, prop_oneof![Just(""), Just(" AS_FAR_AS_POSSIBLE")])
+            .prop_map(|(steps, policy)| format!("MOVE {}{}", steps, policy)),
+        Just("LEFT".to_string()),
+        Just("RIGHT".to_string()),
+        Just("BACK".to_string()),
+        Just("STRAFE LEFT".to_string()),
+        Just("STRAFE RIGHT".to_string()),
+        Just("UTURN".to_string()),
+        orientation().prop_map(|f| format!("FACE {}", f)),
+        Just("REPORT".to_string()),
+    ]
+}
+
+fn script() -> impl Strategy<Value = Vec<String>> {
+    prop::collection::vec(command(), 0..40)
+}
+
+// :: ---
+
+fn create_robot((width, height, obstacles): &TabletopSpec) -> Robot {
+    let mut tabletop = Tabletop::new(*width, *height).unwrap();
+    for (x, y) in obstacles.iter() {
+        let _ = tabletop.add_obstacle((*x).into(), (*y).into());
+    }
+
+    Robot::create(&tabletop).unwrap()
+}
+
+/// Everything a command can change about a Robot and its Tabletop.
+type State = (Option<Position>, Option<Orientation>, Tabletop);
+
+fn state_of(robot: &Robot) -> State {
+    (
+        robot.position(),
+        robot.orientation(),
+        robot.tabletop().clone(),
+    )
+}
+
+fn run(robot: &mut Robot, script: &[String]) {
+    for command in script.iter() {
+        let _ = robot.evaluate(command);
+    }
+}
+
+proptest! {
+    #[test]
+    fn placed_robots_stay_on_free_positions(tabletop in tabletop(), script in script()) {
+        let mut robot = create_robot(&tabletop);
+
+        for command in script.iter() {
+            let _ = robot.evaluate(command);
+
+            if let Some(position) = robot.position() {
+                prop_assert!(robot.tabletop().is_within_bounds(&position));
+                prop_assert!(!robot.tabletop().obstacles().contains(&position));
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_commands_change_nothing(tabletop in tabletop(), script in script()) {
+        let mut robot = create_robot(&tabletop);
+
+        for command in script.iter() {
+            let before = state_of(&robot);
+            if robot.evaluate(command).is_err() {
+                prop_assert_eq!(state_of(&robot), before, "after {}", command);
+            }
+        }
+    }
+
+    #[test]
+    fn four_turns_either_way_restore_the_heading(
+        tabletop in tabletop(),
+        script in script(),
+        turn in prop_oneof![Just("LEFT"), Just("RIGHT")],
+    ) {
+        let mut robot = create_robot(&tabletop);
+        run(&mut robot, &script);
+
+        let before = state_of(&robot);
+        for _ in 0..4 {
+            let _ = robot.evaluate(turn);
+        }
+
+        prop_assert_eq!(state_of(&robot), before);
+    }
+
+    #[test]
+    fn place_is_idempotent(
+        tabletop in tabletop(),
+        script in script(),
+        place in (-2..10, -2..10, orientation())
+            .prop_map(|(x, y, f)| format!("PLACE {},{},{}", x, y, f)),
+    ) {
+        let mut robot = create_robot(&tabletop);
+        run(&mut robot, &script);
+
+        let once = robot.evaluate(&place);
+        let placed_once = state_of(&robot);
+        let twice = robot.evaluate(&place);
+
+        prop_assert_eq!(once, twice);
+        prop_assert_eq!(state_of(&robot), placed_once);
+    }
+
+    #[test]
+    fn programs_behave_like_the_scripts_they_were_compiled_from(
+        tabletop in tabletop(),
+        script in script(),
+        repeats in prop::option::of(0..3),
+    ) {
+        let script = match repeats {
+            Some(times) => format!("REPEAT {} {{\n{}\n}}", times, script.join("\n")),
+            None => script.join("\n"),
+        };
+
+        // :: Saved and loaded, too, since that should make no difference either.
+        let program = Program::compile(&script).unwrap();
+        let program = Program::from_bytes(&program.to_bytes()).unwrap();
+
+        let mut scripted = create_robot(&tabletop);
+        let mut compiled = create_robot(&tabletop);
+
+        prop_assert_eq!(compiled.run_program(&program), scripted.run_script(&script));
+        prop_assert_eq!(state_of(&compiled), state_of(&scripted));
+    }
+}
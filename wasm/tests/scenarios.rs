@@ -0,0 +1,278 @@
+//! Golden-file scenarios, run natively (no browser needed).
+//!
+//! Every `*.trs` file under `tests/fixtures/` is run through the engine, one line
+//! at a time, and the transcript it produces (outputs, and rejections prefixed
+//! with `!` and the line they came from) is compared with the `*.expected` file
+//! next to it.
+//!
+//! Lines starting with `#` are comments, except for `#!` directives that set the
+//! scenario up:
+//!
+//! ```text
+//! #! tabletop 8x6           (default: 5x5)
+//! #! policy as-far-as-possible
+//! #! energy 10 1 1          (capacity, move cost, turn cost)
+//! #! report json            (or csv, plain, or a template like {x}:{y})
+//! #! step-limit 50
+//! #! script                 (run the whole file as one script instead, up
+//!                           until the first instruction that is rejected,
+//!                           other than a failed EXPECT)
+//! ```
+//!
+//! To accept the current behaviour as correct (e.g. after adding a scenario, or
+//! changing an output on purpose), rewrite the expected files with
+//!
+//! ```sh
+//! BLESS=1 cargo test --test scenarios
+//! ```
+//!
+//! and review the resulting diff before committing it.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rules_engine::components::robot::Robot;
+use rules_engine::components::stream;
+use rules_engine::components::tabletop::Tabletop;
+
+#[test]
+fn scenarios_match_their_expected_transcripts() {
+    let bless = env::var_os("BLESS").is_some();
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let mut scenarios = find_scenarios(&fixtures);
+    scenarios.sort();
+    assert!(
+        !scenarios.is_empty(),
+        "No scenarios found in {:?}.",
+        fixtures
+    );
+
+    let mut failures = vec![];
+    for scenario in scenarios.iter() {
+        let expected_path = scenario.with_extension("expected");
+        let source = fs::read_to_string(scenario).unwrap();
+
+        let actual = match run_scenario(&source) {
+            Ok(transcript) => transcript,
+            Err(message) => {
+                failures.push(format!("{}: {}", scenario.display(), message));
+                continue;
+            }
+        };
+
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => (),
+            Ok(expected) => failures.push(format!(
+                "{} differs from {}:\n{}",
+                scenario.display(),
+                expected_path.display(),
+                diff(&expected, &actual)
+            )),
+
+            Err(_) => failures.push(format!(
+                "{} has no expected transcript; run with BLESS=1 to create it.",
+                scenario.display()
+            )),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} scenarios failed:\n\n{}",
+        failures.len(),
+        scenarios.len(),
+        failures.join("\n\n")
+    );
+}
+
+fn find_scenarios(directory: &Path) -> Vec<PathBuf> {
+    let mut scenarios = vec![];
+
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            scenarios.extend(find_scenarios(&path));
+        } else if path.extension() == Some("trs".as_ref()) {
+            scenarios.push(path);
+        }
+    }
+
+    scenarios
+}
+
+// :: ---
+
+/// A step in setting up a scenario's robot, as asked for by a directive.
+type Setup = Box<dyn Fn(&mut Robot) -> Result<(), String>>;
+
+/// Runs a scenario, returning its transcript; or an error if the scenario itself
+/// (rather than the robot) is broken, e.g. because of an unknown directive.
+fn run_scenario(source: &str) -> Result<String, String> {
+    let mut size = (5, 5);
+    let mut script = false;
+    let mut setup: Vec<Setup> = vec![];
+
+    for line in source.lines() {
+        let directive = match line.trim().strip_prefix("#!") {
+            Some(directive) => directive.trim(),
+            None => continue,
+        };
+
+        let (name, value) = directive.split_once(' ').unwrap_or((directive, ""));
+        let value = value.trim().to_string();
+
+        match name {
+            "tabletop" => {
+                size = value
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .ok_or(format!("Tabletop size {} is invalid.", value))?;
+            }
+
+            "policy" => setup.push(Box::new(move |robot| robot.set_move_policy(&value))),
+            "report" if value.contains('{') => {
+                setup.push(Box::new(move |robot| robot.set_report_template(&value)))
+            }
+
+            "report" => setup.push(Box::new(move |robot| robot.set_report_format(&value))),
+            "step-limit" => {
+                let limit = value
+                    .parse::<u32>()
+                    .map_err(|_| format!("Step limit {} is invalid.", value))?;
+
+                setup.push(Box::new(move |robot| {
+                    robot.set_step_limit(limit);
+                    Ok(())
+                }));
+            }
+
+            "energy" => {
+                let numbers = value
+                    .split_whitespace()
+                    .map(|number| number.parse::<u32>())
+                    .collect::<Result<Vec<u32>, _>>()
+                    .map_err(|_| format!("Energy model {} is invalid.", value))?;
+
+                if numbers.len() != 3 {
+                    return Err(format!("Energy model {} is invalid.", value));
+                }
+
+                setup.push(Box::new(move |robot| {
+                    robot.enable_energy(numbers[0], numbers[1], numbers[2]);
+                    Ok(())
+                }));
+            }
+
+            "script" => script = true,
+            _ => return Err(format!("Directive {} was not recognized.", name)),
+        }
+    }
+
+    let tabletop = Tabletop::new(size.0, size.1)?;
+    let mut robot = Robot::create(&tabletop)?;
+    robot.set_id(1);
+
+    for step in setup.iter() {
+        step(&mut robot)?;
+    }
+
+    // :: ---
+
+    // :: Comments are blanked out (rather than dropped), so that line numbers
+    //    in rejections still match the scenario file.
+    let commands = source
+        .lines()
+        .map(|line| match line.trim_start().starts_with('#') {
+            true => "",
+            false => line,
+        })
+        .collect::<Vec<&str>>();
+
+    let mut transcript = vec![];
+    match script {
+        true => {
+            let instructions = match robot.parse_numbered_script(&commands.join("\n")) {
+                Ok(instructions) => instructions,
+                Err(message) => return Ok(format!("! {}\n", message)),
+            };
+
+            for (line, instruction) in instructions.iter() {
+                match robot.run_instruction(instruction) {
+                    Ok(Some(output)) => transcript.push(output),
+                    Ok(None) => (),
+                    Err(message) => {
+                        transcript.push(format!("! Line {}: {}", line, message));
+                        if !robot.expectation_failed() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        false => {
+            stream::run_lines(&mut robot, commands, |_, line| {
+                match &line.result {
+                    Ok(Some(output)) => transcript.push(output.clone()),
+                    Ok(None) => (),
+                    Err(message) => transcript.push(format!("! {}: {}", line.location(), message)),
+                }
+
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(transcript
+        .iter()
+        .map(|entry| format!("{}\n", entry))
+        .collect())
+}
+
+/// A line-by-line diff of two transcripts, marking lines only in `expected`
+/// with `-` and lines only in `actual` with `+`.
+fn diff(expected: &str, actual: &str) -> String {
+    let (expected, actual) = (
+        expected.lines().collect::<Vec<&str>>(),
+        actual.lines().collect::<Vec<&str>>(),
+    );
+
+    // :: Longest common subsequence, by dynamic programming from the end.
+    let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = match expected[i] == actual[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j, mut lines) = (0, 0, vec![]);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+
+    lines.join("\n")
+}
@@ -6,6 +6,7 @@ extern crate rules_engine;
 extern crate wasm_bindgen_test;
 
 use rules_engine::components::*;
+use wasm_bindgen::JsValue;
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -58,3 +59,154 @@ fn example_c() {
 
     assert_eq!(status, "3,3,NORTH");
 }
+
+#[wasm_bindgen_test]
+fn custom_commands_can_be_registered_from_js() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+
+    let handler = js_sys::Function::new_with_args(
+        "args, state",
+        "if (!state.placed) throw new Error('Robot is not placed.');
+         return { run: 'MOVE;'.repeat(Number(args[0])), output: 'Dashed from ' + state.y };",
+    );
+
+    assert!(robot.register_command("dash", handler).is_ok());
+    assert!(robot.evaluate_command("DASH 2").is_err());
+
+    assert!(robot.evaluate_command("PLACE 0,0,NORTH").is_ok());
+    assert_eq!(robot.evaluate_command("DASH 2").unwrap(), "Dashed from 0");
+    assert_eq!(robot.evaluate_command("REPORT").unwrap(), "0,2,NORTH");
+}
+
+#[wasm_bindgen_test]
+fn events_are_delivered_to_js_callbacks() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+
+    let events = js_sys::Array::new();
+    let callback = js_sys::Function::new_with_args("event", "this.push(event.type);").bind(&events);
+
+    robot.subscribe(callback);
+    assert!(robot.evaluate_command("PLACE 0,0,SOUTH").is_ok());
+    assert!(robot.evaluate_command("MOVE").is_err());
+    assert!(robot.evaluate_command("LEFT").is_ok());
+
+    let names = events
+        .iter()
+        .map(|name| name.as_string().unwrap())
+        .collect::<Vec<String>>();
+
+    assert_eq!(
+        names,
+        vec!["Placed", "FallPrevented", "CommandIgnored", "Turned"]
+    );
+}
+
+#[wasm_bindgen_test]
+fn tabletop_can_be_exported_as_svg() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+
+    assert!(robot.evaluate_command("PLACE 0,0,NORTH").is_ok());
+    assert!(robot.evaluate_command("MOVE").is_ok());
+
+    let drawing = robot.render_svg(true).unwrap();
+
+    assert!(drawing.starts_with("<svg"));
+    assert!(drawing.contains("class=\"robot\""));
+    assert!(drawing.contains("class=\"trail\""));
+}
+
+#[wasm_bindgen_test]
+fn report_format_can_be_chosen() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+    robot.set_id(1);
+
+    assert!(robot.evaluate_command("PLACE 1,2,EAST").is_ok());
+    assert!(robot.set_report_format("json").is_ok());
+
+    let status = robot.evaluate_command("REPORT").unwrap();
+    assert_eq!(
+        status,
+        r#"{"id":1,"placed":true,"position":{"x":1,"y":2},"orientation":"EAST"}"#
+    );
+
+    assert!(robot.set_report_template("{x}:{y} facing {f}").is_ok());
+    assert_eq!(robot.evaluate_command("REPORT").unwrap(), "1:2 facing EAST");
+}
+
+#[wasm_bindgen_test]
+fn scripts_report_failed_expectations() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+
+    let outcome = robot
+        .check_script_to_js("PLACE 0,0,NORTH\nEXPECT 1,1,NORTH\nMOVE\nREPORT")
+        .unwrap();
+
+    let field = |name: &str| js_sys::Reflect::get(&outcome, &JsValue::from_str(name)).unwrap();
+    let failures = js_sys::Array::from(&field("failures"));
+
+    assert_eq!(failures.length(), 1);
+    assert!(failures
+        .get(0)
+        .as_string()
+        .unwrap()
+        .starts_with("Line 2: Expectation failed"));
+
+    assert_eq!(js_sys::Array::from(&field("outputs")).get(0), "0,1,NORTH");
+    assert!(field("error").is_null());
+}
+
+#[wasm_bindgen_test]
+fn programs_can_be_saved_loaded_and_run() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let program = program::Program::compile("PLACE 0,0,NORTH\nREPEAT 2 { MOVE }\nREPORT").unwrap();
+    let program = program::Program::from_bytes(&program.to_bytes()).unwrap();
+
+    for _ in 0..2 {
+        let mut robot = robot::Robot::create(&tabletop).unwrap();
+        assert_eq!(robot.evaluate_program(&program).unwrap(), "0,2,NORTH");
+    }
+}
+
+#[wasm_bindgen_test]
+fn lines_can_hold_several_commands() {
+    let tabletop = tabletop::Tabletop::new(5, 5).unwrap();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+
+    assert_eq!(
+        robot
+            .evaluate_command("PLACE 0,0,NORTH; MOVE; REPORT # comment")
+            .unwrap(),
+        "0,1,NORTH"
+    );
+
+    let results = robot.evaluate_statements_to_js("REPORT; JUMP!");
+    let field = |index: u32, name: &str| {
+        js_sys::Reflect::get(&results.get(index), &JsValue::from_str(name)).unwrap()
+    };
+
+    assert_eq!(results.length(), 2);
+    assert_eq!(field(0, "output"), "0,1,NORTH");
+    assert!(field(0, "error").is_null());
+    assert!(field(1, "output").is_null());
+    assert!(field(1, "error").as_string().unwrap().contains("JUMP!"));
+}
+
+#[wasm_bindgen_test]
+fn unbounded_tabletops_have_no_size() {
+    let tabletop = tabletop::Tabletop::unbounded();
+    let mut robot = robot::Robot::create(&tabletop).unwrap();
+
+    assert_eq!(tabletop.width_to_js(), None);
+    assert_eq!(
+        robot
+            .evaluate_command("PLACE 0,0,SOUTH; MOVE 3; REPORT")
+            .unwrap(),
+        "0,-3,SOUTH"
+    );
+    assert!(robot.render_svg(false).is_err());
+}
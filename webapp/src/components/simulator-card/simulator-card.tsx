@@ -38,23 +38,29 @@ const SimulatorCard: FC<SimulatorCardProps> = () => {
     //    maintains it's state until explicitly reset.
     const commandStatements = commands?.split('\n')
     for (const statement of commandStatements || []) {
-      // :: `Robot.evaluate_command/1` returns a string | null on success,
-      //    and throws an error when the corresponding operation in WASM results in
-      //    a Rust `Result::Err` result.
+      // :: A line may hold several commands, separated by `;`.
+      //    `Robot.evaluate_statements/1` runs each of them, and returns what each
+      //    came to as an `{ output, error }` object (either of which may be null),
+      //    so that a rejected command doesn't swallow the output of the others.
       //
       //    Just kinda weird that the generated return type for this method is `any`.
-      try {
-        const result = robot?.evaluate_command(statement)
-        console.debug(`[${statement}]: OK. ${result || 'Null result.'}`)
+      const statementResults: { output: string | null; error: string | null }[] =
+        robot?.evaluate_statements(statement) || []
+
+      for (const { output, error } of statementResults) {
+        if (error) {
+          console.warn(`[${statement}]: DROPPED. ${error}`)
+          continue
+        }
+
+        console.debug(`[${statement}]: OK. ${output || 'Null result.'}`)
 
         // :: We really only wanna add successful results with a corresponding message,
         //    although if we wanted, we could catalog all responses for each of
         //    the parsed command statements for clarity to the user.
-        if (result) {
-          setResults((results) => [...results, { type: 'ok', message: result }])
+        if (output) {
+          setResults((results) => [...results, { type: 'ok', message: output }])
         }
-      } catch (error) {
-        console.warn(`[${statement}]: DROPPED. ${error}`)
       }
     }
   }, [robot, commands])